@@ -3,6 +3,17 @@
 // Texture bindings for input/output
 @group(0) @binding(0) var input: texture_storage_2d<rgba32float, read>;
 @group(0) @binding(1) var output: texture_storage_2d<rgba32float, write>;
+// Primary-hit normal (xyz) and distance from the camera (w, negative on a miss)
+@group(0) @binding(2) var gbuffer_input: texture_storage_2d<rgba32float, read>;
+@group(0) @binding(3) var gbuffer_output: texture_storage_2d<rgba32float, write>;
+// Accumulated color of the pixel at (camera.probe_x, camera.probe_y), read back by the CPU
+@group(0) @binding(4) var<storage, read_write> probe: vec4<f32>;
+// Index + 1 of the sphere hit by the primary ray, 0 on a miss or for other primitives
+@group(0) @binding(5) var sphere_ids: texture_storage_2d<r32uint, write>;
+// Mean luminance (x) and mean squared luminance (y) of each pixel's samples, row by row
+@group(0) @binding(6) var<storage, read_write> moments: array<vec2<f32>>;
+// Albedo of the primary hit (xyz) and 1 (w), or 0 on a miss; see surface_albedo
+@group(0) @binding(7) var albedo_output: texture_storage_2d<rgba32float, write>;
 
 struct SceneCamera {
     center: vec3<f32>,
@@ -18,10 +29,10 @@ struct SceneCamera {
     aspect_ratio: f32,       // No uses
 
     defocus_disk_u: vec3<f32>,
-    _padding0: f32,
+    probe_x: f32,
 
     viewport_u: vec3<f32>,   // No uses
-    _padding1: f32,
+    probe_y: f32,
 
     defocus_disk_v: vec3<f32>,
     max_depth: f32,
@@ -30,21 +41,195 @@ struct SceneCamera {
     samples_per_pixel: f32,
 
     look_at: vec3<f32>,    // No uses
-    camera_has_moved: f32,
+    camera_has_moved: f32,  // Read from frame_constants
 
     vup: vec3<f32>,   // No uses
-    random_seed: f32,
+    random_seed: f32,  // Read from frame_constants
 
     viewport_v: vec3<f32>,  // No uses
-    defocus_radius: f32     // No uses
+    defocus_radius: f32,    // No uses
+
+    prev_center: vec3<f32>,
+    temporal_enabled: f32,
+
+    prev_viewport_upper_left: vec3<f32>,
+    temporal_blend: f32,
+
+    prev_pixel_delta_u: vec3<f32>,
+    temporal_depth_tolerance: f32,
+
+    prev_pixel_delta_v: vec3<f32>,
+    temporal_normal_threshold: f32,
+
+    edge_antialiasing: f32,
+    // Shadow rays per light at each diffuse hit in direct lighting mode
+    light_samples: f32,
+    // 1 for parallel rays from the camera plane (orthographic), 0 for perspective
+    orthographic: f32,
+    // 1 to place samples in a grid of sub-pixel cells, 0 for purely random positions
+    stratified: f32,
+
+    // Bounces before Russian roulette can end a path
+    min_bounces_before_rr: f32,
+    // Relative variance below which a pixel stops sampling, 0 when adaptive sampling is off
+    variance_threshold: f32,
+    // Part of the frame interval the shutter is open for; samples pick a time in between
+    shutter_open: f32,
+    shutter_close: f32,
+
+    // 1 to sample a light at every diffuse bounce in path tracing mode
+    next_event_estimation: f32,
+    // 1 for cosine-weighted diffuse bounces, 0 to spread them evenly over the hemisphere
+    cosine_weighted: f32,
+    // Samples each pixel takes per dispatch, see CameraSettings::samples_per_frame
+    samples_per_frame: f32,
+    _padding1: f32,
+    // Pixels that trace rays this frame, see TiledRendering
+    tile_min: vec2<f32>,
+    tile_max: vec2<f32>,
+    // Color of pixels that have no samples yet, see CameraSettings::clear_color
+    clear_color: vec3<f32>,
+    _padding2: f32
 }
 
 @group(1) @binding(0) var<uniform> camera: SceneCamera;
 
+// The camera values that change every frame; the copies in the camera uniform are left at
+// 0. See FrameConstants.
+struct FrameConstants {
+    random_seed: f32,
+    camera_has_moved: f32,
+    _padding0: f32,
+    _padding1: f32
+}
+#ifdef PUSH_CONSTANTS
+var<push_constant> frame_constants: FrameConstants;
+#else
+@group(1) @binding(1) var<uniform> frame_constants: FrameConstants;
+#endif
+
+
+// Number of objects in one of the buffers below, padded to the 16 bytes every backend
+// accepts for a uniform buffer. Keep in sync with GpuCount in src/lib.rs.
+struct Count {
+    value: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32
+}
 
 // Sphere data
-@group(2) @binding(0) var<uniform> sphere_count: u32;
+@group(2) @binding(0) var<uniform> sphere_count: Count;
 @group(2) @binding(1) var<storage, read_write> spheres: array<Sphere>;
+// Box data, sharing the group with the spheres
+@group(2) @binding(2) var<uniform> box_count: Count;
+@group(2) @binding(3) var<storage, read_write> boxes: array<Box>;
+// Triangle mesh data; each triangle refers to one of mesh_materials
+@group(2) @binding(4) var<uniform> triangle_count: Count;
+@group(2) @binding(5) var<storage, read_write> triangles: array<Triangle>;
+@group(2) @binding(6) var<storage, read_write> mesh_materials: array<Material>;
+// Infinite planes
+@group(2) @binding(7) var<uniform> plane_count: Count;
+@group(2) @binding(8) var<storage, read_write> planes: array<Plane>;
+// Materials the spheres refer to by index, shared between spheres that look the same
+@group(2) @binding(9) var<storage, read_write> sphere_materials: array<Material>;
+// Bounding volume hierarchy over the spheres, see GpuBvhNode. With 0 nodes every sphere
+// is tested instead.
+@group(2) @binding(10) var<storage, read_write> bvh_nodes: array<BvhNode>;
+@group(2) @binding(11) var<uniform> bvh_node_count: Count;
+// The numbers 0 to 255 shuffled by NoiseSettings::seed, four to a vector
+struct NoiseTable {
+    permutation: array<vec4<u32>, 64>,
+}
+@group(2) @binding(12) var<uniform> noise_table: NoiseTable;
+// Quads, such as walls and light panels
+@group(2) @binding(13) var<uniform> quad_count: Count;
+@group(2) @binding(14) var<storage, read_write> quads: array<Quad>;
+// Emissive spheres, quads and disks that diffuse surfaces sample directly, see GpuLight
+struct Light {
+    // LIGHT_SPHERE, LIGHT_QUAD or LIGHT_DISK
+    kind: u32,
+    // Index into spheres, quads or disks
+    index: u32
+}
+@group(2) @binding(15) var<uniform> light_count: Count;
+@group(2) @binding(16) var<storage, read_write> lights: array<Light>;
+// Corner normals of each triangle, for the ones with smooth_shading set
+struct TriangleNormals {
+    n0: vec3<f32>,
+    n1: vec3<f32>,
+    n2: vec3<f32>
+}
+@group(2) @binding(17) var<storage, read_write> triangle_normals: array<TriangleNormals>;
+// Disks and rings, such as round lights
+@group(2) @binding(18) var<uniform> disk_count: Count;
+@group(2) @binding(19) var<storage, read_write> disks: array<Disk>;
+// Spheres that share a radius and material and differ only in position, see
+// SphereInstances. The centers are packed three floats apiece, as an array of vec3 would
+// pad each one to 16 bytes.
+struct InstanceGroup {
+    material: Material,
+    aabb_min: vec3<f32>,
+    radius: f32,
+    aabb_max: vec3<f32>,
+    // Range of instance_positions, in centers, holding the group's instances
+    first: u32,
+    count: u32,
+    // Root of the group's hierarchy in instance_nodes
+    root: u32
+}
+@group(2) @binding(20) var<uniform> instance_group_count: Count;
+@group(2) @binding(21) var<storage, read_write> instance_groups: array<InstanceGroup>;
+@group(2) @binding(22) var<storage, read_write> instance_positions: array<f32>;
+// Every group's hierarchy over its centers. Leaves hold `count` instances from the
+// position at `left_or_primitive`.
+@group(2) @binding(25) var<storage, read_write> instance_nodes: array<BvhNode>;
+// Shapes given by signed distance functions, blended into one ray-marched surface, see
+// GpuSdfPrimitive
+struct SdfPrimitive {
+    center: vec3<f32>,
+    // SDF_SPHERE, SDF_BOX or SDF_TORUS
+    kind: u32,
+    size: vec3<f32>,
+    // Distance over which the primitive blends into the ones before it
+    blend: f32,
+    material: Material
+}
+// Number of SDF primitives and the box around them, see GpuSdfBounds
+struct SdfBounds {
+    aabb_min: vec3<f32>,
+    count: u32,
+    aabb_max: vec3<f32>
+}
+@group(2) @binding(23) var<uniform> sdf_bounds: SdfBounds;
+@group(2) @binding(24) var<storage, read_write> sdf_primitives: array<SdfPrimitive>;
+
+// Sky data, see SkySettings
+struct Sky {
+    top_color: vec3<f32>,
+    intensity: f32,
+    bottom_color: vec3<f32>,
+    gradient_power: f32,
+    // Fill light for the first surface a ray hits, see SkySettings::ambient
+    ambient: vec3<f32>,
+    // 1 if environment_map holds an image to use instead of the gradient
+    environment_map: u32
+}
+
+@group(3) @binding(0) var<uniform> sky: Sky;
+// Equirectangular environment map, only read when sky.environment_map is 1
+@group(3) @binding(1) var environment_map: texture_2d<f32>;
+@group(3) @binding(2) var environment_sampler: sampler;
+// Directional light, see SunLight
+struct Sun {
+    // Towards the sun
+    direction: vec3<f32>,
+    cos_angular_radius: f32,
+    irradiance: vec3<f32>,
+    // 1 to trace shadow rays towards the sun
+    enabled: u32
+}
+@group(3) @binding(3) var<uniform> sun: Sun;
 
 // Random number utilities
 fn hash(value: u32) -> u32 {
@@ -62,11 +247,20 @@ fn random_float(value: u32) -> f32 {
     return f32(hash(value)) / 4294967295.0;
 }
 
-@compute @workgroup_size(8, 8, 1)
+@compute @workgroup_size(#{WORKGROUP_SIZE}, #{WORKGROUP_SIZE}, 1)
 fn init(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
-    // Initialize the texture
+    // The last workgroups can overhang the image when its size isn't a multiple of the
+    // workgroup size
+    if (any(invocation_id.xy >= textureDimensions(output))) {
+        return;
+    }
+
+    // Initialize the texture; the clear color counts as no samples
     let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
-    textureStore(output, location, vec4<f32>(0.0));
+    textureStore(output, location, vec4<f32>(camera.clear_color, 0.0));
+    textureStore(gbuffer_output, location, vec4<f32>(0.0, 0.0, 0.0, -1.0));
+    textureStore(albedo_output, location, vec4<f32>(0.0));
+    moments[invocation_id.y * textureDimensions(output).x + invocation_id.x] = vec2<f32>(0.0);
 }
 
 struct Ray {
@@ -74,24 +268,51 @@ struct Ray {
     direction: vec3<f32>
 }
 
-// material.albedo.z < -1.0 means the material is lambertian
-// material.albedo.z between -1.0 and 1.0 means the material is metallic
-// material.albedo.z > 1.0 means the material is refractive
+// material.albedo.w < -3.0 means the material is noise (lambertian with a marble pattern)
+// material.albedo.w between -3.0 and -1.0 means the material is lambertian
+// material.albedo.w between -1.0 and 1.0 means the material is metallic (w is the fuzz)
+// material.albedo.w between 1.0 and 2.5 means the material is refractive (x is the index,
+// absorption the Beer-Lambert attenuation per unit of distance inside)
+// material.albedo.w > 2.5 means the material is emissive (xyz is the radiance)
+// Keep in sync with GpuMaterial in src/scene/sphere.rs
 struct Material {
     albedo: vec4<f32>,
+    absorption: vec3<f32>,
 }
 
+// Diffuse bounce. With cosine weighting the direction's density, cosine / pi, cancels the
+// cosine and the 1/pi of the Lambertian BRDF, so every bounce carries just the albedo.
+// Uniform directions have to be weighted by 2 cosine instead, which is noisier.
 fn lambertian_scatter(material: Material, ray: Ray, hit_record: HitRecord, attenuation: ptr<function, vec3<f32>>, scattered: ptr<function, Ray>, seed: u32) -> bool {
-    var scattered_direction = hit_record.normal + random_unit_vector(seed);
-    // Ignore zero-length vectors
-    if (dot(scattered_direction, scattered_direction) < 1e-6) {
-        scattered_direction = hit_record.normal;
+    if (camera.cosine_weighted > 0.5) {
+        *scattered = Ray(hit_record.p, cosine_hemisphere_direction(hit_record.normal, seed));
+        *attenuation = material.albedo.xyz;
+    } else {
+        let direction = random_in_hemisphere(hit_record.normal, seed);
+        *scattered = Ray(hit_record.p, direction);
+        *attenuation = material.albedo.xyz * 2.0 * dot(direction, hit_record.normal);
     }
-    *scattered =  Ray(hit_record.p, scattered_direction);
-    *attenuation = material.albedo.xyz;
     return true;
 }
 
+// Density per unit solid angle with which lambertian_scatter picks a direction at
+// `cosine` to the normal
+fn diffuse_pdf(cosine: f32) -> f32 {
+    return select(1.0 / (2.0 * PI), max(cosine, 0.0) / PI, camera.cosine_weighted > 0.5);
+}
+
+// Unit direction around the unit `normal` with density cosine / pi: a point spread evenly
+// over the unit disk, lifted straight up onto the hemisphere
+fn cosine_hemisphere_direction(normal: vec3<f32>, seed: u32) -> vec3<f32> {
+    let radius = sqrt(random_float(seed));
+    let phi = 2.0 * PI * random_float(hash(seed ^ 0x9e3779b9u));
+    let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.x) > 0.9);
+    let u = normalize(cross(normal, helper));
+    let v = cross(normal, u);
+    let height = sqrt(max(0.0, 1.0 - radius * radius));
+    return normalize(u * (radius * cos(phi)) + v * (radius * sin(phi)) + normal * height);
+}
+
 fn metal_scatter(material: Material, ray: Ray, hit_record: HitRecord, attenuation: ptr<function, vec3<f32>>, scattered: ptr<function, Ray>, seed: u32) -> bool {
     let reflected = normalize(reflect(ray.direction, hit_record.normal)) + material.albedo.w * random_unit_vector(seed);
     *scattered = Ray(hit_record.p, normalize(reflected));
@@ -100,8 +321,13 @@ fn metal_scatter(material: Material, ray: Ray, hit_record: HitRecord, attenuatio
 }
 
 fn dielectric_scatter(material: Material, ray: Ray, hit_record: HitRecord, attenuation: ptr<function, vec3<f32>>, scattered: ptr<function, Ray>, seed: u32) -> bool {
-    // Dielectric materials don't absorb light, so attenuation is 1.0
+    // Hitting the surface from inside means the ray just crossed the glass, absorbing light
+    // over the distance traveled (Beer-Lambert); clear glass has no absorption
     *attenuation = vec3<f32>(1.0);
+    if (!hit_record.front_face) {
+        let distance = hit_record.t * length(ray.direction);
+        *attenuation = exp(-material.absorption * distance);
+    }
 
     // Calculate the refraction ratio based on whether we're entering or exiting the material
     // When front_face is true, we're entering the material (air->glass)
@@ -145,33 +371,122 @@ struct HitRecord {
     p: vec3<f32>,
     normal: vec3<f32>,
     front_face: bool,
-    material: Material
+    material: Material,
+    // Index of the sphere that was hit, or NO_SPHERE for other primitives
+    sphere: u32
 }
 
+const NO_SPHERE: u32 = 0xffffffffu;
+
+const LIGHT_SPHERE: u32 = 0u;
+const LIGHT_QUAD: u32 = 1u;
+const LIGHT_DISK: u32 = 2u;
+
+// Index of the quad or disk the last world_hit landed on, or NO_QUAD and NO_DISK. HitRecord
+// only names spheres, but next event estimation needs to know which light a ray found.
+var<private> hit_quad: u32;
+const NO_QUAD: u32 = 0xffffffffu;
+var<private> hit_disk: u32;
+const NO_DISK: u32 = 0xffffffffu;
+
 struct Sphere {
     center: vec3<f32>,
     radius: f32,
+    // Indices into sphere_materials
+    material_index: u32,
+    // Probability of a sample using morph_target_index instead of material_index
+    morph_target_index: u32,
+    morph: f32,
+    // Distance moved over the frame interval, see sphere_center. Its alignment leaves the
+    // same gap before it as GpuSphere::_padding0.
+    velocity: vec3<f32>
+}
+
+// Time in the frame interval of the sample being traced, picked between the shutter
+// times in update. Every ray of a path sees the scene at the same moment.
+var<private> ray_time: f32;
+
+// Where a sphere is at ray_time; static spheres have no velocity
+fn sphere_center(sphere: Sphere) -> vec3<f32> {
+    return sphere.center + sphere.velocity * ray_time;
+}
+
+// Infinite plane through `point`, facing along the unit `normal`
+struct Plane {
+    point: vec3<f32>,
+    normal: vec3<f32>,
+    material: Material
+}
+
+// Parallelogram with a corner at `q` and the edges `u` and `v`, facing along u × v
+struct Quad {
+    q: vec3<f32>,
+    u: vec3<f32>,
+    v: vec3<f32>,
+    material: Material
+}
+
+// Disk of `radius` around `center`, facing along the unit `normal`; a ring when
+// inner_radius is above 0
+struct Disk {
+    center: vec3<f32>,
+    radius: f32,
+    normal: vec3<f32>,
+    inner_radius: f32,
     material: Material
 }
 
+struct Triangle {
+    v0: vec3<f32>,
+    // 1 to interpolate the corner normals in triangle_normals
+    smooth_shading: u32,
+    v1: vec3<f32>,
+    v2: vec3<f32>,
+    material_index: u32
+}
+
+// Inner nodes have count 0 and children at left_or_primitive and left_or_primitive + 1;
+// leaves have count 1 and the sphere index in left_or_primitive
+struct BvhNode {
+    aabb_min: vec3<f32>,
+    left_or_primitive: u32,
+    aabb_max: vec3<f32>,
+    count: u32
+}
+
+// Deepest tree the traversal can handle. bvh.rs builds balanced trees, about
+// log2(sphere_count) + 1 deep, so this covers more spheres than fit in the buffer.
+const BVH_STACK_SIZE: u32 = 32u;
+
+// Axis-aligned box between two corners
+struct Box {
+    min: vec3<f32>,
+    max: vec3<f32>,
+    material: Material
+}
 
 fn hit_record_set_face_normal(rec: ptr<function, HitRecord>, r: Ray, outward_normal: vec3<f32>) {
     let front_face = dot(r.direction, outward_normal) < 0.0;
     let normal = select(-outward_normal, outward_normal, front_face);
-    *rec = HitRecord((*rec).t, (*rec).p, normal, front_face, (*rec).material);
+    *rec = HitRecord((*rec).t, (*rec).p, normal, front_face, (*rec).material, (*rec).sphere);
 }
 
 fn sphere_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
-    var temp_rec = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0)));
+    if (bvh_node_count.value > 0u) {
+        return sphere_bvh_hit(r, ray_tmin, ray_tmax, rec);
+    }
+
+    var temp_rec = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
     var hit_anything = false;
     var closest_so_far = ray_tmax;
 
-    for (var i: u32 = 0u; i < sphere_count; i++) {
+    for (var i: u32 = 0u; i < sphere_count.value; i++) {
         let sphere = spheres[i];  // Dereference the pointer here
         let hit = sphere_hit(sphere, r, ray_tmin, closest_so_far, &temp_rec);
         if hit {
             hit_anything = true;
             closest_so_far = temp_rec.t;
+            temp_rec.sphere = i;
             *rec = temp_rec;
         }
     }
@@ -179,11 +494,485 @@ fn sphere_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitR
     return hit_anything;
 }
 
+// True if the ray enters the box between ray_tmin and ray_tmax
+fn aabb_hit(aabb_min: vec3<f32>, aabb_max: vec3<f32>, r: Ray, inverse_direction: vec3<f32>, ray_tmin: f32, ray_tmax: f32) -> bool {
+    let t0 = (aabb_min - r.origin) * inverse_direction;
+    let t1 = (aabb_max - r.origin) * inverse_direction;
+    let t_near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), max(min(t0.z, t1.z), ray_tmin));
+    let t_far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), min(max(t0.z, t1.z), ray_tmax));
+    return t_near <= t_far;
+}
+
+// Closest sphere hit, walking the BVH with a fixed-size stack instead of testing every
+// sphere
+fn sphere_bvh_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var temp_rec = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+    let inverse_direction = 1.0 / r.direction;
+
+    var stack: array<u32, BVH_STACK_SIZE>;
+    var stack_size = 1u;
+    stack[0] = 0u;
+    while (stack_size > 0u) {
+        stack_size--;
+        let node = bvh_nodes[stack[stack_size]];
+        if (!aabb_hit(node.aabb_min, node.aabb_max, r, inverse_direction, ray_tmin, closest_so_far)) {
+            continue;
+        }
+
+        if (node.count > 0u) {
+            let i = node.left_or_primitive;
+            if (sphere_hit(spheres[i], r, ray_tmin, closest_so_far, &temp_rec)) {
+                hit_anything = true;
+                closest_so_far = temp_rec.t;
+                temp_rec.sphere = i;
+                *rec = temp_rec;
+            }
+        } else if (stack_size + 2u <= BVH_STACK_SIZE) {
+            stack[stack_size] = node.left_or_primitive + 1u;
+            stack[stack_size + 1u] = node.left_or_primitive;
+            stack_size += 2u;
+        }
+    }
+
+    return hit_anything;
+}
+
+// Closest sphere instance hit. Groups whose box the ray misses are skipped whole; inside a
+// group the ray walks the group's hierarchy like sphere_bvh_hit.
+fn instance_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+    let inverse_direction = 1.0 / r.direction;
+
+    for (var g: u32 = 0u; g < instance_group_count.value; g++) {
+        let group = instance_groups[g];
+        if (group.count == 0u || !aabb_hit(group.aabb_min, group.aabb_max, r, inverse_direction, ray_tmin, closest_so_far)) {
+            continue;
+        }
+
+        var stack: array<u32, BVH_STACK_SIZE>;
+        var stack_size = 1u;
+        stack[0] = group.root;
+        while (stack_size > 0u) {
+            stack_size--;
+            let node = instance_nodes[stack[stack_size]];
+            if (!aabb_hit(node.aabb_min, node.aabb_max, r, inverse_direction, ray_tmin, closest_so_far)) {
+                continue;
+            }
+
+            if (node.count > 0u) {
+                for (var i: u32 = node.left_or_primitive; i < node.left_or_primitive + node.count; i++) {
+                    let center = vec3<f32>(instance_positions[3u * i], instance_positions[3u * i + 1u], instance_positions[3u * i + 2u]);
+                    if (centered_sphere_hit(center, group.radius, group.material, r, ray_tmin, closest_so_far, rec)) {
+                        hit_anything = true;
+                        closest_so_far = (*rec).t;
+                    }
+                }
+            } else if (stack_size + 2u <= BVH_STACK_SIZE) {
+                stack[stack_size] = node.left_or_primitive + 1u;
+                stack[stack_size + 1u] = node.left_or_primitive;
+                stack_size += 2u;
+            }
+        }
+    }
+
+    return hit_anything;
+}
+
+fn box_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+
+    for (var i: u32 = 0u; i < box_count.value; i++) {
+        if box_hit(boxes[i], r, ray_tmin, closest_so_far, rec) {
+            hit_anything = true;
+            closest_so_far = (*rec).t;
+        }
+    }
+
+    return hit_anything;
+}
+
+// Closest hit among all primitives in the scene
+fn world_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    hit_quad = NO_QUAD;
+    hit_disk = NO_DISK;
+    var hit_anything = sphere_list_hit(r, ray_tmin, ray_tmax, rec);
+    let closest_after_spheres = select(ray_tmax, (*rec).t, hit_anything);
+    if instance_list_hit(r, ray_tmin, closest_after_spheres, rec) {
+        hit_anything = true;
+    }
+    let closest_so_far = select(ray_tmax, (*rec).t, hit_anything);
+    if box_list_hit(r, ray_tmin, closest_so_far, rec) {
+        hit_anything = true;
+    }
+    let closest_after_boxes = select(ray_tmax, (*rec).t, hit_anything);
+    if triangle_list_hit(r, ray_tmin, closest_after_boxes, rec) {
+        hit_anything = true;
+    }
+    let closest_after_triangles = select(ray_tmax, (*rec).t, hit_anything);
+    if quad_list_hit(r, ray_tmin, closest_after_triangles, rec) {
+        hit_anything = true;
+    }
+    let closest_after_quads = select(ray_tmax, (*rec).t, hit_anything);
+    if disk_list_hit(r, ray_tmin, closest_after_quads, rec) {
+        hit_anything = true;
+        hit_quad = NO_QUAD;
+    }
+    let closest_after_disks = select(ray_tmax, (*rec).t, hit_anything);
+    if sdf_list_hit(r, ray_tmin, closest_after_disks, rec) {
+        hit_anything = true;
+        hit_quad = NO_QUAD;
+        hit_disk = NO_DISK;
+    }
+    let closest_after_sdfs = select(ray_tmax, (*rec).t, hit_anything);
+    if plane_list_hit(r, ray_tmin, closest_after_sdfs, rec) {
+        hit_anything = true;
+        hit_quad = NO_QUAD;
+        hit_disk = NO_DISK;
+    }
+    return hit_anything;
+}
+
+const SDF_SPHERE: u32 = 0u;
+const SDF_BOX: u32 = 1u;
+const SDF_TORUS: u32 = 2u;
+// Steps a ray marches through the SDF primitives before it is taken to miss them
+const SDF_MAX_STEPS: u32 = 128u;
+// Distance from the surface that counts as a hit; the normal is sampled this far apart too
+const SDF_HIT_DISTANCE: f32 = 1e-4;
+
+// Signed distance from p to one primitive, negative inside it
+fn sdf_primitive(primitive: SdfPrimitive, p: vec3<f32>) -> f32 {
+    let local = p - primitive.center;
+    switch primitive.kind {
+        case SDF_BOX: {
+            let q = abs(local) - primitive.size;
+            return length(max(q, vec3<f32>(0.0))) + min(max(q.x, max(q.y, q.z)), 0.0);
+        }
+        case SDF_TORUS: {
+            let q = vec2<f32>(length(local.xz) - primitive.size.x, local.y);
+            return length(q) - primitive.size.y;
+        }
+        default: {
+            return length(local) - primitive.size.x;
+        }
+    }
+}
+
+// Polynomial smooth minimum, which rounds off the crease where a and b meet over a
+// distance k and lies at most k / 4 below min(a, b). A k of 0 is a plain minimum.
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    if (k <= 0.0) {
+        return min(a, b);
+    }
+    let h = max(k - abs(a - b), 0.0) / k;
+    return min(a, b) - h * h * k * 0.25;
+}
+
+// Signed distance from p to the surface of all SDF primitives, each blended into the ones
+// before it. Only called when there is at least one primitive.
+fn sdf_scene(p: vec3<f32>) -> f32 {
+    var distance = sdf_primitive(sdf_primitives[0], p);
+    for (var i: u32 = 1u; i < sdf_bounds.count; i++) {
+        let primitive = sdf_primitives[i];
+        distance = smooth_min(distance, sdf_primitive(primitive, p), primitive.blend);
+    }
+    return distance;
+}
+
+// Outward normal of the SDF surface at p, from the distance's gradient sampled at the
+// corners of a small tetrahedron
+fn sdf_normal(p: vec3<f32>) -> vec3<f32> {
+    let e = vec2<f32>(1.0, -1.0) * SDF_HIT_DISTANCE;
+    return normalize(e.xyy * sdf_scene(p + e.xyy) + e.yyx * sdf_scene(p + e.yyx)
+                     + e.yxy * sdf_scene(p + e.yxy) + e.xxx * sdf_scene(p + e.xxx));
+}
+
+// Material of the primitive nearest to p, which colors its part of a blended surface
+fn sdf_material(p: vec3<f32>) -> Material {
+    var nearest = 0u;
+    var nearest_distance = sdf_primitive(sdf_primitives[0], p);
+    for (var i: u32 = 1u; i < sdf_bounds.count; i++) {
+        let distance = sdf_primitive(sdf_primitives[i], p);
+        if (distance < nearest_distance) {
+            nearest = i;
+            nearest_distance = distance;
+        }
+    }
+    return sdf_primitives[nearest].material;
+}
+
+// Sphere trace the SDF surface: step along the ray by the distance to the surface, which
+// can't overshoot it, until that distance is below SDF_HIT_DISTANCE. Rays only march
+// through the box around every primitive, and not at all when there are none.
+fn sdf_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    if (sdf_bounds.count == 0u) {
+        return false;
+    }
+
+    let aabb_min = sdf_bounds.aabb_min - SDF_HIT_DISTANCE;
+    let aabb_max = sdf_bounds.aabb_max + SDF_HIT_DISTANCE;
+    let inverse_direction = 1.0 / r.direction;
+    let t0 = (aabb_min - r.origin) * inverse_direction;
+    let t1 = (aabb_max - r.origin) * inverse_direction;
+    let t_near = max(max(min(t0.x, t1.x), min(t0.y, t1.y)), max(min(t0.z, t1.z), ray_tmin));
+    let t_far = min(min(max(t0.x, t1.x), max(t0.y, t1.y)), min(max(t0.z, t1.z), ray_tmax));
+    if (t_near > t_far) {
+        return false;
+    }
+
+    // Distances are in world units, while t counts lengths of the ray's direction
+    let step_scale = 1.0 / length(r.direction);
+    // Rays that start inside, such as ones refracted into glass, march towards the surface
+    // from within
+    var t = t_near;
+    let side = select(-1.0, 1.0, sdf_scene(r.origin + t * r.direction) >= 0.0);
+    for (var i: u32 = 0u; i < SDF_MAX_STEPS && t <= t_far; i++) {
+        let distance = side * sdf_scene(r.origin + t * r.direction);
+        // The first step may start on the surface a bounce just left, which isn't a hit
+        if (distance < SDF_HIT_DISTANCE && i > 0u) {
+            let p = r.origin + t * r.direction;
+            *rec = HitRecord(t, p, vec3<f32>(0.0), false, sdf_material(p), NO_SPHERE);
+            hit_record_set_face_normal(rec, r, sdf_normal(p));
+            return true;
+        }
+        t += max(distance, SDF_HIT_DISTANCE) * step_scale;
+    }
+
+    return false;
+}
+
+fn plane_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+
+    for (var i: u32 = 0u; i < plane_count.value; i++) {
+        if plane_hit(planes[i], r, ray_tmin, closest_so_far, rec) {
+            hit_anything = true;
+            closest_so_far = (*rec).t;
+        }
+    }
+
+    return hit_anything;
+}
+
+fn plane_hit(plane: Plane, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    let denominator = dot(plane.normal, r.direction);
+    // The ray runs parallel to the plane
+    if (abs(denominator) < 1e-8) {
+        return false;
+    }
+    let root = dot(plane.point - r.origin, plane.normal) / denominator;
+    if root <= ray_tmin || ray_tmax <= root {
+        return false;
+    }
+
+    *rec = HitRecord(root, r.origin + root * r.direction, plane.normal, false, plane.material, NO_SPHERE);
+    hit_record_set_face_normal(rec, r, plane.normal);
+
+    return true;
+}
+
+fn quad_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+
+    for (var i: u32 = 0u; i < quad_count.value; i++) {
+        if quad_hit(quads[i], r, ray_tmin, closest_so_far, rec) {
+            hit_anything = true;
+            closest_so_far = (*rec).t;
+            hit_quad = i;
+        }
+    }
+
+    return hit_anything;
+}
+
+// Intersect the quad's plane, then find the hit point's coordinates along u and v; it is
+// on the quad if both are in 0..1
+fn quad_hit(quad: Quad, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    let n = cross(quad.u, quad.v);
+    let normal = normalize(n);
+    let denominator = dot(normal, r.direction);
+    // The ray runs parallel to the quad
+    if (abs(denominator) < 1e-8) {
+        return false;
+    }
+    let root = dot(quad.q - r.origin, normal) / denominator;
+    if root <= ray_tmin || ray_tmax <= root {
+        return false;
+    }
+
+    let hit_point = r.origin + root * r.direction;
+    let planar = hit_point - quad.q;
+    let w = n / dot(n, n);
+    let alpha = dot(w, cross(planar, quad.v));
+    let beta = dot(w, cross(quad.u, planar));
+    if (alpha < 0.0 || alpha > 1.0 || beta < 0.0 || beta > 1.0) {
+        return false;
+    }
+
+    *rec = HitRecord(root, hit_point, normal, false, quad.material, NO_SPHERE);
+    hit_record_set_face_normal(rec, r, normal);
+
+    return true;
+}
+
+fn disk_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+
+    for (var i: u32 = 0u; i < disk_count.value; i++) {
+        if disk_hit(disks[i], r, ray_tmin, closest_so_far, rec) {
+            hit_anything = true;
+            closest_so_far = (*rec).t;
+            hit_disk = i;
+        }
+    }
+
+    return hit_anything;
+}
+
+// Intersect the disk's plane, then keep the hit if its distance from the center is between
+// the inner and the outer radius
+fn disk_hit(disk: Disk, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    let denominator = dot(disk.normal, r.direction);
+    // The ray runs parallel to the disk
+    if (abs(denominator) < 1e-8) {
+        return false;
+    }
+    let root = dot(disk.center - r.origin, disk.normal) / denominator;
+    if root <= ray_tmin || ray_tmax <= root {
+        return false;
+    }
+
+    let hit_point = r.origin + root * r.direction;
+    let offset = hit_point - disk.center;
+    let distance_squared = dot(offset, offset);
+    if (distance_squared >= disk.radius * disk.radius || distance_squared < disk.inner_radius * disk.inner_radius) {
+        return false;
+    }
+
+    *rec = HitRecord(root, hit_point, disk.normal, false, disk.material, NO_SPHERE);
+    hit_record_set_face_normal(rec, r, disk.normal);
+
+    return true;
+}
+
+fn triangle_list_hit(r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    var hit_anything = false;
+    var closest_so_far = ray_tmax;
+
+    for (var i: u32 = 0u; i < triangle_count.value; i++) {
+        if triangle_hit(i, r, ray_tmin, closest_so_far, rec) {
+            hit_anything = true;
+            closest_so_far = (*rec).t;
+        }
+    }
+
+    return hit_anything;
+}
+
+// Moller-Trumbore: solve for the distance and barycentric coordinates of the hit at once
+fn triangle_hit(index: u32, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    let triangle = triangles[index];
+    let edge1 = triangle.v1 - triangle.v0;
+    let edge2 = triangle.v2 - triangle.v0;
+    let p = cross(r.direction, edge2);
+    let determinant = dot(edge1, p);
+    // The ray is parallel to the triangle's plane
+    if (abs(determinant) < 1e-8) {
+        return false;
+    }
+
+    let inverse_determinant = 1.0 / determinant;
+    let s = r.origin - triangle.v0;
+    let u = dot(s, p) * inverse_determinant;
+    if (u < 0.0 || u > 1.0) {
+        return false;
+    }
+    let q = cross(s, edge1);
+    let v = dot(r.direction, q) * inverse_determinant;
+    if (v < 0.0 || u + v > 1.0) {
+        return false;
+    }
+    let root = dot(edge2, q) * inverse_determinant;
+    if root <= ray_tmin || ray_tmax <= root {
+        return false;
+    }
+
+    let outward_normal = normalize(cross(edge1, edge2));
+    let material = mesh_materials[triangle.material_index];
+    *rec = HitRecord(root, r.origin + root * r.direction, outward_normal, false, material, NO_SPHERE);
+    hit_record_set_face_normal(rec, r, outward_normal);
+    if (triangle.smooth_shading != 0u) {
+        // The face normal still decides which side was hit; the interpolated one is only
+        // used for shading, turned to that side
+        let normals = triangle_normals[index];
+        let shading_normal = normalize((1.0 - u - v) * normals.n0 + u * normals.n1 + v * normals.n2);
+        (*rec).normal = select(-shading_normal, shading_normal, (*rec).front_face);
+    }
+
+    return true;
+}
+
+// Slab test: the ray is inside the box between the largest entry and the smallest exit
+// distance over the three axes
+fn box_hit(b: Box, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    let inverse_direction = 1.0 / r.direction;
+    let t0 = (b.min - r.origin) * inverse_direction;
+    let t1 = (b.max - r.origin) * inverse_direction;
+    let t_near = min(t0, t1);
+    let t_far = max(t0, t1);
+    let entry = max(max(t_near.x, t_near.y), t_near.z);
+    let exit = min(min(t_far.x, t_far.y), t_far.z);
+    if (entry > exit) {
+        return false;
+    }
+
+    // Rays starting inside the box, such as refracted ones, hit it on the way out
+    var root = entry;
+    if root <= ray_tmin || ray_tmax <= root {
+        root = exit;
+        if root <= ray_tmin || ray_tmax <= root {
+            return false;
+        }
+    }
+
+    let hit_point = r.origin + root * r.direction;
+    // The face that was hit is along the axis where the point is furthest from the center,
+    // relative to the box's size
+    let half_size = max((b.max - b.min) * 0.5, vec3<f32>(1e-6));
+    let local = (hit_point - (b.min + b.max) * 0.5) / half_size;
+    let distance = abs(local);
+    var outward_normal = vec3<f32>(0.0, 0.0, sign(local.z));
+    if (distance.x >= distance.y && distance.x >= distance.z) {
+        outward_normal = vec3<f32>(sign(local.x), 0.0, 0.0);
+    } else if (distance.y >= distance.z) {
+        outward_normal = vec3<f32>(0.0, sign(local.y), 0.0);
+    }
+
+    *rec = HitRecord(root, hit_point, outward_normal, false, b.material, NO_SPHERE);
+    hit_record_set_face_normal(rec, r, outward_normal);
+
+    return true;
+}
+
 fn sphere_hit(sphere: Sphere, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
-    let oc = sphere.center - r.origin;
+    // The caller fills in which sphere it was
+    return centered_sphere_hit(sphere_center(sphere), sphere.radius, sphere_materials[sphere.material_index], r, ray_tmin, ray_tmax, rec);
+}
+
+// Hit with a sphere of `radius` around `center`, shared by the spheres and their
+// instances. The record names no sphere.
+fn centered_sphere_hit(center: vec3<f32>, radius: f32, material: Material, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<function, HitRecord>) -> bool {
+    let oc = center - r.origin;
     let a = dot(r.direction, r.direction);
     let h = dot(oc, r.direction);
-    let c = dot(oc, oc) - sphere.radius * sphere.radius;
+    let c = dot(oc, oc) - radius * radius;
     let discriminant = h*h - a*c;
 
     if discriminant < 0.0 {
@@ -203,7 +992,7 @@ fn sphere_hit(sphere: Sphere, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<fun
 
     // Calculate hit point
     let hit_point = r.origin + root * r.direction;
-    let outward_normal = (hit_point - sphere.center) / sphere.radius;
+    let outward_normal = (hit_point - center) / radius;
 
     // Create new HitRecord with updated values
     *rec = HitRecord(
@@ -211,7 +1000,8 @@ fn sphere_hit(sphere: Sphere, r: Ray, ray_tmin: f32, ray_tmax: f32, rec: ptr<fun
         hit_point,
         outward_normal,
         false,
-        sphere.material
+        material,
+        NO_SPHERE
     );
 
     // Set the face normal
@@ -258,83 +1048,775 @@ fn random_in_hemisphere(normal: vec3<f32>, seed: u32) -> vec3<f32> {
     }
 }
 
-fn ray_color(ray: Ray, seed: u32) -> vec3<f32> {
+// `primary` receives the first hit along the ray; its t stays negative if the ray misses
+#ifdef PREVIEW
+// Bounce limit of the interactive preview used while the camera moves
+const PREVIEW_MAX_DEPTH: u32 = 3u;
+#endif
+
+// Lowest chance of a path surviving Russian roulette, so the survivors' weights stay bounded
+const RR_MIN_SURVIVAL: f32 = 0.05;
+
+fn max_bounces() -> u32 {
+#ifdef PREVIEW
+    return min(u32(camera.max_depth), PREVIEW_MAX_DEPTH);
+#else
+    return u32(camera.max_depth);
+#endif
+}
+
+// Shadow rays traced per shading point, see CameraSettings::light_samples. The preview
+// makes do with one.
+fn light_samples() -> u32 {
+#ifdef PREVIEW
+    return 1u;
+#else
+    return max(u32(camera.light_samples), 1u);
+#endif
+}
+
+// Values of material.albedo.w below this mark a noise material; see NOISE_MATERIAL_W in
+// src/scene/sphere.rs
+const NOISE_MATERIAL_W: f32 = -3.0;
+// Octaves summed into the turbulence that bends the marble veins
+const TURBULENCE_DEPTH: u32 = 7u;
+
+fn noise_permutation(index: u32) -> u32 {
+    let i = index & 255u;
+    return noise_table.permutation[i / 4u][i % 4u];
+}
+
+fn noise_fade(t: vec3<f32>) -> vec3<f32> {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+// Dot product of the offset with one of 12 edge directions of a cube picked by the hash
+fn noise_gradient(hash: u32, offset: vec3<f32>) -> f32 {
+    let h = hash & 15u;
+    let u = select(offset.y, offset.x, h < 8u);
+    let v = select(select(offset.z, offset.x, h == 12u || h == 14u), offset.y, h < 4u);
+    return select(-u, u, (h & 1u) == 0u) + select(-v, v, (h & 2u) == 0u);
+}
+
+// Ken Perlin's improved noise, roughly in -1..1
+fn perlin_noise(p: vec3<f32>) -> f32 {
+    let cell = floor(p);
+    let f = p - cell;
+    let i = vec3<u32>(vec3<i32>(cell) & vec3<i32>(255));
+    let w = noise_fade(f);
+
+    let a = noise_permutation(i.x) + i.y;
+    let aa = noise_permutation(a) + i.z;
+    let ab = noise_permutation(a + 1u) + i.z;
+    let b = noise_permutation(i.x + 1u) + i.y;
+    let ba = noise_permutation(b) + i.z;
+    let bb = noise_permutation(b + 1u) + i.z;
+
+    let near = mix(
+        mix(noise_gradient(noise_permutation(aa), f), noise_gradient(noise_permutation(ba), f - vec3<f32>(1.0, 0.0, 0.0)), w.x),
+        mix(noise_gradient(noise_permutation(ab), f - vec3<f32>(0.0, 1.0, 0.0)), noise_gradient(noise_permutation(bb), f - vec3<f32>(1.0, 1.0, 0.0)), w.x),
+        w.y
+    );
+    let far = mix(
+        mix(noise_gradient(noise_permutation(aa + 1u), f - vec3<f32>(0.0, 0.0, 1.0)), noise_gradient(noise_permutation(ba + 1u), f - vec3<f32>(1.0, 0.0, 1.0)), w.x),
+        mix(noise_gradient(noise_permutation(ab + 1u), f - vec3<f32>(0.0, 1.0, 1.0)), noise_gradient(noise_permutation(bb + 1u), f - vec3<f32>(1.0, 1.0, 1.0)), w.x),
+        w.y
+    );
+    return mix(near, far, w.z);
+}
+
+// Sum of noise octaves, each at twice the frequency and half the weight of the last
+fn turbulence(p: vec3<f32>) -> f32 {
+    var sum = 0.0;
+    var x = p;
+    var weight = 1.0;
+    for (var i: u32 = 0u; i < TURBULENCE_DEPTH; i++) {
+        sum += weight * perlin_noise(x);
+        weight *= 0.5;
+        x *= 2.0;
+    }
+    return abs(sum);
+}
+
+// Albedo of a noise material at `p`: sine stripes along z, bent by turbulence into veins
+fn noise_albedo(material: Material, p: vec3<f32>) -> vec3<f32> {
+    let scale = NOISE_MATERIAL_W - material.albedo.w;
+    return material.albedo.xyz * 0.5 * (1.0 + sin(scale * p.z + 10.0 * turbulence(p)));
+}
+
+// Color a surface reflects, for the albedo G-buffer: the albedo of diffuse and metal
+// surfaces, white for glass and the normalized color of lights
+fn surface_albedo(hit_record: HitRecord) -> vec3<f32> {
+    let material = hit_record.material;
+    if (material.albedo.w < NOISE_MATERIAL_W) {
+        return noise_albedo(material, hit_record.p);
+    }
+    if (material.albedo.w <= 1.0) {
+        return material.albedo.xyz;
+    }
+    if (material.albedo.w <= 2.5) {
+        return vec3<f32>(1.0);
+    }
+    let radiance = material.albedo.xyz;
+    return radiance / max(max(radiance.x, radiance.y), max(radiance.z, 1e-6));
+}
+
+// Material used for one sample of a hit. Morphing spheres pick between their two
+// materials at random, so accumulation averages them into a cross-fade.
+fn sample_material(hit_record: HitRecord, seed: u32) -> Material {
+    var material = hit_record.material;
+    if (hit_record.sphere != NO_SPHERE) {
+        let sphere = spheres[hit_record.sphere];
+        if (random_float(seed ^ 0x68e31da4u) < sphere.morph) {
+            material = sphere_materials[sphere.morph_target_index];
+        }
+    }
+    // Noise materials scatter like lambertian ones with the albedo found at the hit
+    if (material.albedo.w < NOISE_MATERIAL_W) {
+        return Material(vec4<f32>(noise_albedo(material, hit_record.p), -2.0), vec3<f32>(0.0));
+    }
+    return material;
+}
+
+const PI: f32 = 3.14159265;
+
+#ifdef DIRECT_ONLY
+// Light reaching a diffuse surface straight from the emissive spheres, divided by its
+// albedo. Each light is sampled light_samples() times with directions inside the cone it
+// subtends.
+fn sample_sphere_lights(hit_record: HitRecord, seed: u32) -> vec3<f32> {
+    let samples = light_samples();
+    var direct = vec3<f32>(0.0);
+    for (var i: u32 = 0u; i < sphere_count.value; i++) {
+        let light = spheres[i];
+        let emitted = sphere_materials[light.material_index].albedo;
+        if (emitted.w <= 2.5 || i == hit_record.sphere) {
+            continue;
+        }
+        let to_center = sphere_center(light) - hit_record.p;
+        let distance_squared = dot(to_center, to_center);
+        let radius_squared = light.radius * light.radius;
+        if (distance_squared <= radius_squared) {
+            continue;
+        }
+
+        let cos_theta_max = sqrt(1.0 - radius_squared / distance_squared);
+        let w = normalize(to_center);
+        let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(w.x) > 0.9);
+        let u = normalize(cross(w, helper));
+        let v = cross(w, u);
+        var light_color = vec3<f32>(0.0);
+        for (var sample: u32 = 0u; sample < samples; sample++) {
+            let light_seed = hash(seed + i * 7919u + sample * 104729u);
+            let cos_theta = 1.0 - random_float(light_seed) * (1.0 - cos_theta_max);
+            let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+            let phi = 2.0 * PI * random_float(hash(light_seed));
+            let direction = normalize((u * cos(phi) + v * sin(phi)) * sin_theta + w * cos_theta);
+
+            let cosine = dot(direction, hit_record.normal);
+            if (cosine <= 0.0) {
+                continue;
+            }
+            var shadow = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+            if (!world_hit(Ray(hit_record.p, direction), 0.001, 3.4e35, &shadow) || shadow.sphere != i) {
+                continue;
+            }
+            // Lambertian BRDF (1/pi) over the cone pdf 1 / (2pi (1 - cos_theta_max))
+            light_color += emitted.xyz * cosine * 2.0 * (1.0 - cos_theta_max);
+        }
+        direct += light_color / f32(samples);
+    }
+    return direct;
+}
+#else
+// Power heuristic weight of a sample drawn with density `pdf` when `other_pdf` could have
+// drawn it too
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let squared = pdf * pdf;
+    return squared / (squared + other_pdf * other_pdf);
+}
+
+// Light reaching a diffuse surface from a light picked at random from the light list,
+// through a shadow ray towards a point on it, divided by the surface's albedo. It is
+// weighted against the surface's own cosine sampling finding the light, which ray_color
+// weights the other way.
+fn sample_direct_light(hit_record: HitRecord, seed: u32) -> vec3<f32> {
+    let count = light_count.value;
+    if (count == 0u) {
+        return vec3<f32>(0.0);
+    }
+    let light = lights[min(u32(random_float(seed) * f32(count)), count - 1u)];
+
+    // Direction to the sampled point and its density per unit solid angle
+    var direction: vec3<f32>;
+    var pdf: f32;
+    var radiance: vec3<f32>;
+    if (light.kind == LIGHT_SPHERE) {
+        if (light.index == hit_record.sphere) {
+            return vec3<f32>(0.0);
+        }
+        // A direction inside the cone the sphere subtends, as in sample_sphere_lights
+        let sphere = spheres[light.index];
+        let to_center = sphere_center(sphere) - hit_record.p;
+        let distance_squared = dot(to_center, to_center);
+        let radius_squared = sphere.radius * sphere.radius;
+        if (distance_squared <= radius_squared) {
+            return vec3<f32>(0.0);
+        }
+        let cos_theta_max = sqrt(1.0 - radius_squared / distance_squared);
+        let w = normalize(to_center);
+        let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(w.x) > 0.9);
+        let u = normalize(cross(w, helper));
+        let v = cross(w, u);
+        let cos_theta = 1.0 - random_float(hash(seed)) * (1.0 - cos_theta_max);
+        let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * PI * random_float(hash(seed ^ 0x27d4eb2fu));
+        direction = normalize((u * cos(phi) + v * sin(phi)) * sin_theta + w * cos_theta);
+        pdf = 1.0 / (2.0 * PI * (1.0 - cos_theta_max));
+        radiance = sphere_materials[sphere.material_index].albedo.xyz;
+    } else if (light.kind == LIGHT_QUAD) {
+        // A point spread evenly over the quad, turned into a density per solid angle
+        let quad = quads[light.index];
+        let point = quad.q + random_float(hash(seed)) * quad.u + random_float(hash(seed ^ 0x27d4eb2fu)) * quad.v;
+        let to_light = point - hit_record.p;
+        let distance_squared = dot(to_light, to_light);
+        direction = to_light / sqrt(distance_squared);
+        let n = cross(quad.u, quad.v);
+        let area = length(n);
+        let light_cosine = abs(dot(n, direction)) / area;
+        if (light_cosine < 1e-6) {
+            return vec3<f32>(0.0);
+        }
+        pdf = distance_squared / (light_cosine * area);
+        radiance = quad.material.albedo.xyz;
+    } else {
+        // A point spread evenly over the disk or ring: the square of its distance from the
+        // center is uniform between the squared radii
+        let disk = disks[light.index];
+        let inner_squared = disk.inner_radius * disk.inner_radius;
+        let outer_squared = disk.radius * disk.radius;
+        let radius = sqrt(mix(inner_squared, outer_squared, random_float(hash(seed))));
+        let phi = 2.0 * PI * random_float(hash(seed ^ 0x27d4eb2fu));
+        let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(disk.normal.x) > 0.9);
+        let u = normalize(cross(disk.normal, helper));
+        let v = cross(disk.normal, u);
+        let point = disk.center + radius * (u * cos(phi) + v * sin(phi));
+        let to_light = point - hit_record.p;
+        let distance_squared = dot(to_light, to_light);
+        direction = to_light / sqrt(distance_squared);
+        let area = PI * (outer_squared - inner_squared);
+        let light_cosine = abs(dot(disk.normal, direction));
+        if (light_cosine < 1e-6 || area <= 0.0) {
+            return vec3<f32>(0.0);
+        }
+        pdf = distance_squared / (light_cosine * area);
+        radiance = disk.material.albedo.xyz;
+    }
+    pdf /= f32(count);
+
+    let cosine = dot(direction, hit_record.normal);
+    if (cosine <= 0.0) {
+        return vec3<f32>(0.0);
+    }
+    var shadow = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+    if (!world_hit(Ray(hit_record.p, direction), 0.001, 3.4e35, &shadow)) {
+        return vec3<f32>(0.0);
+    }
+    var reached = shadow.sphere == light.index;
+    if (light.kind == LIGHT_QUAD) {
+        reached = hit_quad == light.index;
+    } else if (light.kind == LIGHT_DISK) {
+        reached = hit_disk == light.index;
+    }
+    if (!reached) {
+        return vec3<f32>(0.0);
+    }
+
+    // Lambertian BRDF without the albedo (1/pi), over the density of the light sample. Each
+    // of the light_samples() shadow rays could have found the light, so the light's
+    // strategy counts that many times in the weight.
+    let weight = power_heuristic(f32(light_samples()) * pdf, diffuse_pdf(cosine));
+    return radiance * cosine / PI / pdf * weight;
+}
+
+// Density with which sample_direct_light, from `origin`, picks the direction towards
+// the emitter `hit_record` is on. 0 for emitters that aren't in the light list, such as
+// emissive boxes, which only the path's own bounces can find.
+fn direct_light_pdf(origin: vec3<f32>, hit_record: HitRecord) -> f32 {
+    let count = f32(light_count.value);
+    if (hit_record.sphere != NO_SPHERE) {
+        let sphere = spheres[hit_record.sphere];
+        // A material animation can make a sphere glow that isn't in the list
+        if (sphere_materials[sphere.material_index].albedo.w <= 2.5) {
+            return 0.0;
+        }
+        let to_center = sphere_center(sphere) - origin;
+        let distance_squared = dot(to_center, to_center);
+        let radius_squared = sphere.radius * sphere.radius;
+        if (distance_squared <= radius_squared) {
+            return 0.0;
+        }
+        let cos_theta_max = sqrt(1.0 - radius_squared / distance_squared);
+        return 1.0 / (count * 2.0 * PI * (1.0 - cos_theta_max));
+    }
+    if (hit_quad != NO_QUAD) {
+        let quad = quads[hit_quad];
+        let n = cross(quad.u, quad.v);
+        let area = length(n);
+        let to_light = hit_record.p - origin;
+        let distance_squared = dot(to_light, to_light);
+        let light_cosine = abs(dot(n, to_light)) / (area * sqrt(distance_squared));
+        return distance_squared / (count * max(light_cosine, 1e-6) * area);
+    }
+    if (hit_disk != NO_DISK) {
+        let disk = disks[hit_disk];
+        let area = PI * (disk.radius * disk.radius - disk.inner_radius * disk.inner_radius);
+        let to_light = hit_record.p - origin;
+        let distance_squared = dot(to_light, to_light);
+        let light_cosine = abs(dot(disk.normal, to_light)) / sqrt(distance_squared);
+        return distance_squared / (count * max(light_cosine, 1e-6) * area);
+    }
+    return 0.0;
+}
+#endif
+
+// Light from the sun reaching a diffuse surface, divided by the surface's albedo. Each of
+// the light_samples() shadow rays heads for a random point on the sun's disk, which
+// softens the shadows. The disk's radiance times the solid angle it covers is the sun's
+// irradiance, so the angular radius doesn't change how bright the sun is.
+fn sample_sun(hit_record: HitRecord, seed: u32) -> vec3<f32> {
+    if (sun.enabled == 0u) {
+        return vec3<f32>(0.0);
+    }
+    let w = sun.direction;
+    let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(w.x) > 0.9);
+    let u = normalize(cross(w, helper));
+    let v = cross(w, u);
+    let samples = light_samples();
+    var unoccluded = 0.0;
+    for (var sample: u32 = 0u; sample < samples; sample++) {
+        // A direction spread evenly over the cone the disk subtends
+        let sample_seed = hash(seed + sample * 104729u);
+        let cos_theta = 1.0 - random_float(sample_seed) * (1.0 - sun.cos_angular_radius);
+        let sin_theta = sqrt(max(0.0, 1.0 - cos_theta * cos_theta));
+        let phi = 2.0 * PI * random_float(hash(sample_seed ^ 0x27d4eb2fu));
+        let direction = normalize((u * cos(phi) + v * sin(phi)) * sin_theta + w * cos_theta);
+
+        let cosine = dot(direction, hit_record.normal);
+        if (cosine <= 0.0) {
+            continue;
+        }
+        var shadow = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+        if (!world_hit(Ray(hit_record.p, direction), 0.001, 3.4e35, &shadow)) {
+            unoccluded += cosine;
+        }
+    }
+    // Lambertian BRDF without the albedo (1/pi)
+    return sun.irradiance * unoccluded / f32(samples) / PI;
+}
+
+// Radiance arriving from `direction` (a unit vector) when a ray leaves the scene, before
+// the sky intensity is applied
+fn background(direction: vec3<f32>) -> vec3<f32> {
+    if (sky.environment_map == 1u) {
+        // Equirectangular lookup: longitude around y to u, angle from straight up to v
+        let uv = vec2<f32>(
+            0.5 + atan2(direction.z, direction.x) / (2.0 * 3.14159265),
+            acos(clamp(direction.y, -1.0, 1.0)) / 3.14159265
+        );
+        return textureSampleLevel(environment_map, environment_sampler, uv, 0.0).xyz;
+    }
+    let a = pow(0.5*(direction.y + 1.0), sky.gradient_power);
+    return mix(sky.bottom_color, sky.top_color, a);
+}
+
+fn ray_color(ray: Ray, seed: u32, primary: ptr<function, HitRecord>) -> vec3<f32> {
     var r = ray;
     var color_factor = vec3<f32>(1.0);
-    for (var i: u32 = 0; i < u32(camera.max_depth); i++) {
-        var hit_record = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0)));
-        let t = sphere_list_hit(r, 0.001, 3.4e35, &hit_record);
+    // Light sampled explicitly at diffuse hits
+    var direct = vec3<f32>(0.0);
+#ifdef DIRECT_ONLY
+    var diffuse_primary = false;
+#else
+    // Where the last bounce left from and the density of the direction it took, when it
+    // also sampled a light directly; 0 after bounces that didn't
+    var bounce_origin = vec3<f32>(0.0);
+    var bounce_pdf = 0.0;
+#endif
+    for (var i: u32 = 0; i < max_bounces(); i++) {
+        var hit_record = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+        let t = world_hit(r, 0.001, 3.4e35, &hit_record);
         if t {
+            if (i == 0u) {
+                *primary = hit_record;
+            }
             let seed = hash(seed + i * 1000u);
             var scattered = Ray(vec3<f32>(0.0), vec3<f32>(0.0));
             var attenuation = vec3<f32>(0.0);
+            let material = sample_material(hit_record, seed);
+#ifdef DIRECT_ONLY
+            // Past the first surface, only light arriving straight from an emitter counts.
+            // Diffuse surfaces have already sampled the emissive spheres explicitly.
+            if (i == 1u) {
+                if (material.albedo.w > 2.5 && !diffuse_primary) {
+                    return direct + color_factor * material.albedo.xyz;
+                }
+                return direct;
+            }
+            if (material.albedo.w < -1.0) {
+                diffuse_primary = true;
+                direct = material.albedo.xyz * sample_sphere_lights(hit_record, seed);
+            }
+#else
+            var scatter_pdf = 0.0;
+#endif
             // If material is lambertian
-            if (hit_record.material.albedo.w < -1.0) {
-                if (!lambertian_scatter(hit_record.material, r, hit_record, &attenuation, &scattered, seed)) {
-                    return vec3<f32>(0.0);
+            if (material.albedo.w < -1.0) {
+                // Non-physical fill light, added once per path at its primary hit
+                if (i == 0u) {
+                    direct += sky.ambient * material.albedo.xyz;
+                }
+                direct += color_factor * material.albedo.xyz * sample_sun(hit_record, hash(seed ^ 0x68e31da4u));
+                if (!lambertian_scatter(material, r, hit_record, &attenuation, &scattered, seed)) {
+                    return direct;
+                }
+#ifndef DIRECT_ONLY
+                if (camera.next_event_estimation > 0.5) {
+                    let samples = light_samples();
+                    var light = vec3<f32>(0.0);
+                    for (var sample: u32 = 0u; sample < samples; sample++) {
+                        light += sample_direct_light(hit_record, hash((seed ^ 0x1b873593u) + sample * 104729u));
+                    }
+                    direct += color_factor * material.albedo.xyz * light / f32(samples);
+                    scatter_pdf = diffuse_pdf(dot(scattered.direction, hit_record.normal));
                 }
-            } else if (hit_record.material.albedo.w <= 1.0) {
-                if (!metal_scatter(hit_record.material, r, hit_record, &attenuation, &scattered, seed)) {
-                    return vec3<f32>(0.0);
+#endif
+            } else if (material.albedo.w <= 1.0) {
+                if (!metal_scatter(material, r, hit_record, &attenuation, &scattered, seed)) {
+                    return direct;
+                }
+            } else if (material.albedo.w <= 2.5) {
+                if (!dielectric_scatter(material, r, hit_record, &attenuation, &scattered, seed)) {
+                    return direct;
                 }
             } else {
-                if (!dielectric_scatter(hit_record.material, r, hit_record, &attenuation, &scattered, seed)) {
-                    return vec3<f32>(0.0);
+                // Emissive surfaces end the path with their own radiance
+                var emitted = material.albedo.xyz;
+#ifndef DIRECT_ONLY
+                // The last bounce may have sampled this light directly as well
+                if (bounce_pdf > 0.0) {
+                    emitted *= power_heuristic(bounce_pdf, f32(light_samples()) * direct_light_pdf(bounce_origin, hit_record));
                 }
+#endif
+                return direct + color_factor * emitted;
             }
+#ifndef DIRECT_ONLY
+            bounce_origin = hit_record.p;
+            bounce_pdf = scatter_pdf;
+#endif
             color_factor *= attenuation;
             r = scattered;
+
+            // Russian roulette: end dim paths early, and scale up the survivors by the
+            // chance they had so the average is unchanged
+            if (i + 1u >= u32(camera.min_bounces_before_rr)) {
+                let survival = clamp(max(color_factor.x, max(color_factor.y, color_factor.z)), RR_MIN_SURVIVAL, 1.0);
+                if (random_float(hash(seed ^ 0x5bd1e995u)) > survival) {
+                    color_factor = vec3<f32>(0.0);
+                    break;
+                }
+                color_factor /= survival;
+            }
         }
         else {
             break;
         }
     }
 
-    let unit_direction = normalize(r.direction);
-    let a = 0.5*(unit_direction.y + 1.0);
-    let sky_color = (1.0-a)*vec3<f32>(1.0, 1.0, 1.0) + a*vec3<f32>(0.5, 0.7, 1.0);
-    return color_factor * sky_color;
+    let sky_color = sky.intensity * background(normalize(r.direction));
+    return direct + color_factor * sky_color;
 }
 
 fn sample_square(seed: u32) -> vec3<f32> {
     let x = random_float(seed) - 0.5;
-    let y = random_float(seed * seed) - 0.5;
+    let y = random_float(hash(seed ^ 0x85ebca6bu)) - 0.5;
     return vec3<f32>(x, y, 0.0);
 }
 
+// Offset of a pixel's `sample_index`th sample from its center. With stratification the
+// first n*n samples, n = floor(sqrt(samples_per_pixel)), each land in a different cell of
+// an n by n grid, jittered within the cell. Cells are visited along wrapping diagonals from
+// a per-pixel starting cell, so every n consecutive samples cover each column once and an
+// unfinished accumulation isn't lopsided. Later samples, which can't fill another whole
+// grid, are purely random so the average stays unbiased.
+fn pixel_sample_offset(location: vec2<i32>, sample_index: u32) -> vec3<f32> {
+    let jitter = sample_square(jitter_seed(location, sample_index));
+    let n = u32(sqrt(camera.samples_per_pixel));
+    let cells = n * n;
+    if (camera.stratified < 0.5 || sample_index >= cells) {
+        return jitter;
+    }
+
+    let start = hash(u32(location.x) * 19u + hash(u32(location.y) * 41u)) % cells;
+    let cell = (sample_index * (n + 1u) + start) % cells;
+    let corner = vec2<f32>(f32(cell % n), f32(cell / n));
+    let in_cell = (corner + jitter.xy + 0.5) / f32(n) - 0.5;
+    return vec3<f32>(in_cell, 0.0);
+}
+
+// Seed of the sub-pixel jitter of a pixel's `sample_index`th sample. It is hashed from the
+// sample index and the camera's random seed on their own, apart from the path's seed, so
+// every frame moves the sample to a fresh spot in the pixel, and a still camera keeps
+// anti-aliasing edges as the image converges. The sample index comes from the pixel's own
+// history, so with CameraSettings::deterministic_seed the jitter repeats exactly.
+fn jitter_seed(location: vec2<i32>, sample_index: u32) -> u32 {
+    let frame = hash(sample_index * 0x9e3779b9u ^ u32(frame_constants.random_seed * 4294967295.0));
+    return hash(frame ^ hash(u32(location.x) * 61u) ^ hash(u32(location.y) * 113u));
+}
+
 fn get_ray(
     location: vec2<i32>,
+    frame_seed: u32,
     sample_index: u32
 ) -> Ray {
     let seed = hash(hash(u32(location.x) * 73u) ^
                (hash(u32(location.y) * 51u)) ^
-               (sample_index * 25u + u32(camera.random_seed * 4294967295.0)));
-    let offset = sample_square(seed);
+               (frame_seed * 25u + u32(frame_constants.random_seed * 4294967295.0)));
+    let offset = pixel_sample_offset(location, sample_index);
 
     // Calculate pixel center
     let pixel_center = camera.viewport_upper_left
         + camera.pixel_delta_u * (f32(location.x) + 0.5 + offset.x)
         + camera.pixel_delta_v * (f32(location.y) + 0.5 + offset.y);
 
-    let ray_origin = select(camera.center, defocus_disk_sample(seed+1u), camera.defocus_angle > 0.0);
-    // let ray_origin = defocus_disk_sample(seed+30u);
-
+    let ray_origin = ray_origin_for(pixel_center, seed+1u);
     let ray_direction = pixel_center - ray_origin;
 
     return Ray(ray_origin, ray_direction);
 }
 
+// Closest sphere limb near a pixel's center ray, see silhouette_coverage
+struct Silhouette {
+    coverage: f32,
+    // Where the center ray enters the sphere, or reaches its limb if it misses
+    depth: f32,
+    inside: Ray,
+    outside: Ray
+}
+
+// Make sphere `i` the pixel's silhouette if it covers part of the pixel in front of the
+// current one
+fn silhouette_test(i: u32, origin: vec3<f32>, direction: vec3<f32>, footprint_scale: f32, pixel_width: f32, best: ptr<function, Silhouette>) {
+    let sphere = spheres[i];
+    let center = sphere_center(sphere);
+    let oc = center - origin;
+    let t = dot(oc, direction);
+    if (t <= 0.0) {
+        return;
+    }
+
+    // Offset from the sphere's center to the closest point on the ray
+    let offset = t * direction - oc;
+    let d = length(offset);
+    let footprint = select(footprint_scale * t, pixel_width, camera.orthographic > 0.5);
+    let coverage = clamp(0.5 + (sphere.radius - d) / footprint, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        return;
+    }
+
+    var depth = t;
+    if (d < sphere.radius) {
+        depth = t - sqrt(sphere.radius * sphere.radius - d * d);
+    }
+    if (depth >= (*best).depth) {
+        return;
+    }
+
+    var across = normalize(camera.pixel_delta_u);
+    if (d > 1e-6) {
+        across = offset / d;
+    }
+    let limb = center + across * sphere.radius;
+    *best = Silhouette(
+        coverage,
+        depth,
+        Ray(origin, limb - across * 0.25 * footprint - origin),
+        Ray(origin, limb + across * 0.5 * footprint - origin)
+    );
+}
+
+// Analytic anti-aliasing of sphere silhouettes for primary rays. Finds the closest sphere
+// seen through the pixel and estimates how much of the pixel it covers from the distance
+// between the pixel's center ray and the sphere's limb. Only spheres in front of whatever
+// the center ray hits count, so a limb hidden behind a box or a wall is left alone, and
+// the sphere BVH narrows the search to spheres within a pixel of the center ray. For
+// partial coverage, `inside` and `outside` are set to rays aimed just inside and just
+// outside the limb.
+fn silhouette_coverage(location: vec2<i32>, seed: u32, inside: ptr<function, Ray>, outside: ptr<function, Ray>) -> f32 {
+    let pixel_center = camera.viewport_upper_left
+        + camera.pixel_delta_u * (f32(location.x) + 0.5)
+        + camera.pixel_delta_v * (f32(location.y) + 0.5);
+    let origin = ray_origin_for(pixel_center, seed);
+    let distance_to_viewport = length(pixel_center - origin);
+    let direction = (pixel_center - origin) / distance_to_viewport;
+    // Width of a pixel per unit of distance along the ray; parallel rays keep the width
+    // of a pixel on the viewport at every distance
+    let pixel_width = length(camera.pixel_delta_u);
+    let footprint_scale = pixel_width / distance_to_viewport;
+
+    // Anything the center ray hits hides the limbs behind it
+    var occluder = HitRecord(0.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+    var max_depth = 3.4e35;
+    if (world_hit(Ray(origin, direction), 0.001, 3.4e35, &occluder)) {
+        // The sphere the center ray hits enters at the same depth, give or take rounding
+        max_depth = occluder.t * 1.0001 + 1e-4;
+    }
+    var best = Silhouette(0.0, max_depth, Ray(origin, direction), Ray(origin, direction));
+
+    if (bvh_node_count.value > 0u) {
+        // Walk the BVH with every box grown by a pixel's footprint at its far side, so
+        // spheres the center ray only grazes are still found
+        let inverse_direction = 1.0 / direction;
+        var stack: array<u32, BVH_STACK_SIZE>;
+        var stack_size = 1u;
+        stack[0] = 0u;
+        while (stack_size > 0u) {
+            stack_size--;
+            let node = bvh_nodes[stack[stack_size]];
+            let far = length(0.5 * (node.aabb_min + node.aabb_max) - origin) + 0.5 * length(node.aabb_max - node.aabb_min);
+            let margin = select(footprint_scale * far, pixel_width, camera.orthographic > 0.5);
+            if (!aabb_hit(node.aabb_min - margin, node.aabb_max + margin, Ray(origin, direction), inverse_direction, 0.0, best.depth)) {
+                continue;
+            }
+
+            if (node.count > 0u) {
+                silhouette_test(node.left_or_primitive, origin, direction, footprint_scale, pixel_width, &best);
+            } else if (stack_size + 2u <= BVH_STACK_SIZE) {
+                stack[stack_size] = node.left_or_primitive + 1u;
+                stack[stack_size + 1u] = node.left_or_primitive;
+                stack_size += 2u;
+            }
+        }
+    } else {
+        for (var i: u32 = 0u; i < sphere_count.value; i++) {
+            silhouette_test(i, origin, direction, footprint_scale, pixel_width, &best);
+        }
+    }
+
+    *inside = best.inside;
+    *outside = best.outside;
+    return best.coverage;
+}
+
+// Where a ray through `pixel_center` starts: the camera center, or for orthographic
+// projection the point behind the pixel on the camera plane. Defocus blur jitters it over
+// the lens disk.
+fn ray_origin_for(pixel_center: vec3<f32>, seed: u32) -> vec3<f32> {
+    var origin = camera.center;
+    if (camera.orthographic > 0.5) {
+        let viewport_center = camera.viewport_upper_left + 0.5 * (camera.viewport_u + camera.viewport_v);
+        origin = pixel_center + camera.center - viewport_center;
+    }
+    if (camera.defocus_angle > 0.0) {
+        origin += defocus_disk_sample(seed) - camera.center;
+    }
+    return origin;
+}
+
 fn defocus_disk_sample(seed: u32) -> vec3<f32> {
     let angle = 2.0 * 3.1415926 * random_float(seed);
     let p = normalize(vec2<f32>(cos(angle), sin(angle)));
     return camera.center + (p.x * camera.defocus_disk_u) + (p.y * camera.defocus_disk_v);
 }
 
-@compute @workgroup_size(8, 8, 1)
+// Project a world-space point onto the previous frame's viewport, in pixel coordinates
+fn reproject(p: vec3<f32>, previous: ptr<function, vec2<f32>>) -> bool {
+    let viewport_normal = cross(camera.prev_pixel_delta_u, camera.prev_pixel_delta_v);
+    let to_point = p - camera.prev_center;
+    let denominator = dot(to_point, viewport_normal);
+    if (abs(denominator) < 1e-8) {
+        return false;
+    }
+    let t = dot(camera.prev_viewport_upper_left - camera.prev_center, viewport_normal) / denominator;
+    if (t <= 0.0) {
+        return false;
+    }
+    let on_viewport = camera.prev_center + t * to_point - camera.prev_viewport_upper_left;
+    *previous = vec2<f32>(
+        dot(on_viewport, camera.prev_pixel_delta_u) / dot(camera.prev_pixel_delta_u, camera.prev_pixel_delta_u),
+        dot(on_viewport, camera.prev_pixel_delta_v) / dot(camera.prev_pixel_delta_v, camera.prev_pixel_delta_v)
+    );
+    return true;
+}
+
+// Blend a fresh sample with last frame's color at the same surface point (exponential moving average).
+// History is rejected where the surface was not visible last frame, judged by depth and normal.
+fn temporal_resolve(color: vec3<f32>, primary: HitRecord, size: vec2<u32>) -> vec3<f32> {
+    // The sky has no depth to validate against, so it is never reprojected
+    if (primary.t < 0.0) {
+        return color;
+    }
+
+    var previous = vec2<f32>(0.0);
+    if (!reproject(primary.p, &previous)) {
+        return color;
+    }
+    let previous_location = vec2<i32>(floor(previous));
+    if (any(previous_location < vec2<i32>(0)) || any(previous_location >= vec2<i32>(size))) {
+        return color;
+    }
+
+    let history_gbuffer = textureLoad(gbuffer_input, previous_location);
+    let expected_depth = distance(primary.p, camera.prev_center);
+    let depth_matches = history_gbuffer.w > 0.0
+        && abs(history_gbuffer.w - expected_depth) <= camera.temporal_depth_tolerance * expected_depth;
+    let normal_matches = dot(history_gbuffer.xyz, primary.normal) >= camera.temporal_normal_threshold;
+    if (!depth_matches || !normal_matches) {
+        return color;
+    }
+
+    let history = textureLoad(input, previous_location).xyz;
+    return mix(history, color, camera.temporal_blend);
+}
+
+#ifdef NAN_GUARD
+// True if any component is NaN or infinite (all exponent bits set)
+fn has_non_finite(v: vec3<f32>) -> bool {
+    let exponent_mask = 0x7f800000u;
+    let exponents = bitcast<vec3<u32>>(v) & vec3<u32>(exponent_mask);
+    return any(exponents == vec3<u32>(exponent_mask));
+}
+#endif
+
+// Samples a pixel takes before adaptive sampling may consider it converged, so a few
+// samples that happen to agree don't stop it
+const ADAPTIVE_MIN_SAMPLES: u32 = 16u;
+
+// Whether a pixel's mean has settled: the variance of the mean luminance, relative to its
+// square, is below the camera's threshold
+fn converged(moments: vec2<f32>, samples: u32) -> bool {
+    if (camera.variance_threshold <= 0.0 || samples < ADAPTIVE_MIN_SAMPLES) {
+        return false;
+    }
+    let variance = max(moments.y - moments.x * moments.x, 0.0);
+    let variance_of_mean = variance / f32(samples);
+    // The epsilon keeps black pixels from needing zero variance
+    return variance_of_mean < camera.variance_threshold * (moments.x * moments.x + 1e-4);
+}
+
+@compute @workgroup_size(#{WORKGROUP_SIZE}, #{WORKGROUP_SIZE}, 1)
 fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
-    let location = vec2<i32>(i32(invocation_id.x), i32(invocation_id.y));
     let size = textureDimensions(output);
-
+    // Only the workgroups covering the current tile are dispatched, counted from its top
+    // left corner; the rest of the image was copied to the output beforehand
+    let pixel_id = invocation_id.xy + vec2<u32>(camera.tile_min);
+    // The last workgroups can overhang the tile when its size isn't a multiple of the
+    // workgroup size
+    if (any(pixel_id >= min(vec2<u32>(camera.tile_max), size))) {
+        return;
+    }
+    let location = vec2<i32>(pixel_id);
+    let moments_index = pixel_id.y * size.x + pixel_id.x;
 
     let progress = textureLoad(input, location);
     var color_until_now = progress.xyz;
@@ -342,23 +1824,103 @@ fn update(@builtin(global_invocation_id) invocation_id: vec3<u32>) {
 
     let samples_per_pixel = u32(camera.samples_per_pixel);
 
-    let reset = camera.camera_has_moved > 0.5;
+    // The history is dropped in the same dispatch that writes the first new sample, so a
+    // reset never shows a cleared or half-cleared frame
+    let reset = frame_constants.camera_has_moved > 0.5;
+
+    var pixel_moments = moments[moments_index];
 
     if (reset) {
         color_until_now = vec3<f32>(0.0);
         samples_until_now = 0u;
+        pixel_moments = vec2<f32>(0.0);
     }
 
-    if (samples_until_now < samples_per_pixel) {
-        let seed = 1u + samples_until_now + u32(camera.random_seed * 4294967295.0);
-        let ray = get_ray(location, seed);
-        let color = ray_color(ray, seed+1u);
-        color_until_now += (color - color_until_now) / f32(samples_until_now + 1u);
-        samples_until_now += 1u;
-    }
-
+    var gbuffer = textureLoad(gbuffer_input, location);
+
+    // Several samples per dispatch converge a still image in fewer frames; only the first
+    // of them restarts the history on a reset
+    var samples_this_frame = max(u32(camera.samples_per_frame), 1u);
+#ifdef PREVIEW
+    // Preview samples are thrown away, so one keeps the moving camera responsive
+    samples_this_frame = 1u;
+#endif
+    for (var frame_sample: u32 = 0u; frame_sample < samples_this_frame; frame_sample++) {
+        if (samples_until_now >= samples_per_pixel || converged(pixel_moments, samples_until_now)) {
+            break;
+        }
+        // Every random number of the sample follows from the pixel, its sample count and the
+        // camera's seed, so neighboring pixels don't share their bounce directions
+        let seed = hash(hash(u32(location.x) * 2654435761u) ^ hash(u32(location.y) * 40503u)
+                        ^ (1u + samples_until_now + u32(frame_constants.random_seed * 4294967295.0)));
+        // Pixels pick their own times, so a moving sphere blurs smoothly instead of leaving
+        // a copy of itself for every sample
+        let time_seed = hash(seed ^ hash(u32(location.x) * 83u) ^ hash(u32(location.y) * 97u));
+        ray_time = mix(camera.shutter_open, camera.shutter_close, random_float(time_seed));
+        var primary = HitRecord(-1.0, vec3<f32>(0.0), vec3<f32>(0.0), false, Material(vec4<f32>(0.0), vec3<f32>(0.0)), 0u);
+        var color = vec3<f32>(0.0);
+        var inside: Ray;
+        var outside: Ray;
+        var coverage = 1.0;
+        if (camera.edge_antialiasing > 0.5) {
+            coverage = silhouette_coverage(location, seed + 2u, &inside, &outside);
+        }
+        if (coverage > 0.0 && coverage < 1.0) {
+            // Blend both sides of the silhouette instead of leaving coverage to the jitter
+            var outside_primary = primary;
+            let inside_color = ray_color(inside, seed + 1u, &primary);
+            let outside_color = ray_color(outside, seed + 3u, &outside_primary);
+            color = mix(outside_color, inside_color, coverage);
+            if (coverage < 0.5) {
+                primary = outside_primary;
+            }
+        } else {
+            let ray = get_ray(location, seed, samples_until_now);
+            color = ray_color(ray, seed + 1u, &primary);
+        }
 
+        if (reset && frame_sample == 0u && camera.temporal_enabled > 0.5) {
+            // Restart from the reprojected history; it counts as a single sample from here on
+            color_until_now = temporal_resolve(color, primary, size);
+            samples_until_now = 1u;
+        } else {
+            // Running average of all samples since the last reset
+            samples_until_now += 1u;
+            color_until_now = mix(color_until_now, color, 1.0 / f32(samples_until_now));
+        }
+        // After a temporal restart the reprojected color is the pixel's one sample
+        let newest = select(color, color_until_now, samples_until_now == 1u);
+        // Rec. 709 weights, like the CPU-side probe
+        let luminance = dot(newest, vec3<f32>(0.2126, 0.7152, 0.0722));
+        pixel_moments = mix(pixel_moments, vec2<f32>(luminance, luminance * luminance), 1.0 / f32(samples_until_now));
+
+        gbuffer = vec4<f32>(primary.normal, select(-1.0, distance(primary.p, camera.center), primary.t >= 0.0));
+        var albedo = vec4<f32>(0.0);
+        if (primary.t >= 0.0) {
+            albedo = vec4<f32>(surface_albedo(primary), 1.0);
+        }
+        textureStore(albedo_output, location, albedo);
+        textureStore(sphere_ids, location, vec4<u32>(select(0u, primary.sphere + 1u, primary.t >= 0.0 && primary.sphere != NO_SPHERE)));
+    }
+    textureStore(gbuffer_output, location, gbuffer);
+
+#ifdef PREVIEW
+    // Preview samples don't count, so the first full-quality frame replaces them
+    samples_until_now = 0u;
+    pixel_moments = vec2<f32>(0.0);
+#endif
+    moments[moments_index] = pixel_moments;
+
+#ifdef NAN_GUARD
+    if (has_non_finite(color_until_now)) {
+        color_until_now = vec3<f32>(1.0, 0.0, 1.0);
+    }
+#endif
 
     let final_color = vec4<f32>(color_until_now, f32(samples_until_now));
     textureStore(output, location, final_color);
+
+    if (all(location == vec2<i32>(i32(camera.probe_x), i32(camera.probe_y)))) {
+        probe = final_color;
+    }
 }
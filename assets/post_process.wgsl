@@ -0,0 +1,204 @@
+// Fragment shader that draws the accumulated image to the screen
+
+#import bevy_sprite::mesh2d_vertex_output::VertexOutput
+
+struct Display {
+    exposure_scale: f32,
+    vignette_strength: f32,
+    vignette_radius: f32,
+    // Draw the sample count in the upper left corner when above 0.5
+    frame_count_label: f32,
+    // 0 = none, 1 = Reinhard, 2 = ACES filmic, 3 = exponential exposure; see TonemapMode
+    tonemap: u32,
+    // Show the sample count as a heatmap with this count as the hottest color when above 0
+    heatmap_samples: f32,
+    // Strength of the edge-aware denoiser, 0 to show the raw image; see denoise
+    denoise_strength: f32,
+    // 0 = color, 1 = depth, 2 = normals, 3 = albedo; see DisplayChannel
+    channel: u32,
+    // Distance shown as black in the depth channel
+    depth_range: f32,
+    // Sample count the frame count label shows
+    label_samples: f32,
+    _padding1: f32,
+    _padding2: f32
+}
+
+@group(2) @binding(0) var<uniform> display: Display;
+// Accumulated color (xyz) and sample count (w)
+@group(2) @binding(1) var source: texture_2d<f32>;
+// Normal (xyz) and distance (w, -1 for the sky) of the surface each pixel last saw
+@group(2) @binding(2) var gbuffer: texture_2d<f32>;
+// Albedo (xyz) of the surface each pixel last saw, w is 0 for the sky
+@group(2) @binding(3) var albedo: texture_2d<f32>;
+
+// The denoiser averages (2 * DENOISE_RADIUS + 1)^2 taps, DENOISE_STEP pixels apart
+const DENOISE_RADIUS: i32 = 2;
+const DENOISE_STEP: i32 = 2;
+
+// 3x5 bitmap glyphs for the digits 0-9, same as output.rs. Row r of a glyph is in bits
+// 12 - 3r to 14 - 3r, with the leftmost pixel in the highest bit.
+var<private> DIGITS: array<u32, 10> = array<u32, 10>(
+    0x7b6fu, 0x2c97u, 0x73e7u, 0x73cfu, 0x5bc9u, 0x79cfu, 0x79efu, 0x7249u, 0x7befu, 0x7bcfu
+);
+// Size of one glyph pixel, matching FRAME_COUNT_SCALE in output.rs
+const LABEL_SCALE: u32 = 2u;
+
+// Brightness of the frame count label at `texel`: white digits on a black box, or -1
+// outside the box
+fn frame_count_label(texel: vec2<u32>, number: u32) -> f32 {
+    var digits = 1u;
+    var divisor = 1u;
+    while (number / divisor >= 10u) {
+        digits++;
+        divisor *= 10u;
+    }
+
+    let origin = vec2<u32>(2u * LABEL_SCALE);
+    let glyph_advance = 4u * LABEL_SCALE;
+    let box_size = vec2<u32>(digits * glyph_advance + LABEL_SCALE, 7u * LABEL_SCALE);
+    if (any(texel < origin) || any(texel >= origin + box_size)) {
+        return -1.0;
+    }
+
+    let p = texel - origin;
+    if (p.x < LABEL_SCALE || p.y < LABEL_SCALE) {
+        return 0.0;
+    }
+    let q = (p - LABEL_SCALE) / LABEL_SCALE;
+    let index = q.x / 4u;
+    let column = q.x % 4u;
+    if (index >= digits || column >= 3u || q.y >= 5u) {
+        return 0.0;
+    }
+
+    var place = 1u;
+    for (var i = 1u; i < digits - index; i++) {
+        place *= 10u;
+    }
+    let row = (DIGITS[(number / place) % 10u] >> (12u - 3u * q.y)) & 7u;
+    return f32((row >> (2u - column)) & 1u);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces_filmic(c: vec3<f32>) -> vec3<f32> {
+    return clamp((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+fn tonemap(c: vec3<f32>) -> vec3<f32> {
+    switch display.tonemap {
+        case 1u: {
+            return c / (1.0 + c);
+        }
+        case 2u: {
+            return aces_filmic(c);
+        }
+        case 3u: {
+            return 1.0 - exp(-c);
+        }
+        default: {
+            return c;
+        }
+    }
+}
+
+// Blue through green and yellow to red as t goes from 0 to 1
+fn heatmap(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0.0, 1.0) * 3.0;
+    if (x < 1.0) {
+        return mix(vec3<f32>(0.0, 0.0, 1.0), vec3<f32>(0.0, 1.0, 0.0), x);
+    }
+    if (x < 2.0) {
+        return mix(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 1.0, 0.0), x - 1.0);
+    }
+    return mix(vec3<f32>(1.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), x - 2.0);
+}
+
+fn luminance(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Edge-aware blur of the accumulated color around `texel`, guided by the G-buffer. Taps
+// count less the further away they are, and hardly at all across a change in normal,
+// depth or brightness. The brightness tolerance shrinks like the noise as samples
+// accumulate, so a converged image comes through almost unchanged.
+fn denoise(texel: vec2<u32>, size: vec2<u32>) -> vec3<f32> {
+    let center = textureLoad(source, texel, 0);
+    let center_gbuffer = textureLoad(gbuffer, texel, 0);
+    let center_luminance = luminance(center.xyz);
+    let color_sigma = display.denoise_strength / sqrt(max(center.w, 1.0));
+
+    var sum = vec3<f32>(0.0);
+    var total_weight = 0.0;
+    for (var y = -DENOISE_RADIUS; y <= DENOISE_RADIUS; y++) {
+        for (var x = -DENOISE_RADIUS; x <= DENOISE_RADIUS; x++) {
+            let position = vec2<i32>(texel) + vec2<i32>(x, y) * DENOISE_STEP;
+            let tap = vec2<u32>(clamp(position, vec2<i32>(0), vec2<i32>(size) - 1));
+            let tap_gbuffer = textureLoad(gbuffer, tap, 0);
+            // The sky only blends with the sky
+            if ((center_gbuffer.w > 0.0) != (tap_gbuffer.w > 0.0)) {
+                continue;
+            }
+            let color = textureLoad(source, tap, 0).xyz;
+
+            var weight = exp(-f32(x * x + y * y) / f32(DENOISE_RADIUS * DENOISE_RADIUS));
+            if (center_gbuffer.w > 0.0) {
+                weight *= pow(max(dot(center_gbuffer.xyz, tap_gbuffer.xyz), 0.0), 32.0);
+                weight *= exp(-abs(tap_gbuffer.w - center_gbuffer.w) / (0.02 * center_gbuffer.w));
+            }
+            // Difference relative to the pair's brightness, so it works the same in
+            // shadows and highlights
+            let difference = abs(luminance(color) - center_luminance)
+                / (0.5 * (luminance(color) + center_luminance) + 0.1);
+            weight *= exp(-difference * difference / (2.0 * color_sigma * color_sigma));
+
+            sum += color * weight;
+            total_weight += weight;
+        }
+    }
+    // The center tap always has weight 1
+    return sum / total_weight;
+}
+
+@fragment
+fn fragment(mesh: VertexOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(source);
+    let texel = min(vec2<u32>(mesh.uv * vec2<f32>(size)), size - 1u);
+    if (display.heatmap_samples > 0.0) {
+        return vec4<f32>(heatmap(textureLoad(source, texel, 0).w / display.heatmap_samples), 1.0);
+    }
+    // G-buffer channels, with the sky left black
+    let surface = textureLoad(gbuffer, texel, 0);
+    switch display.channel {
+        case 1u: {
+            let depth = select(0.0, 1.0 - clamp(surface.w / display.depth_range, 0.0, 1.0), surface.w > 0.0);
+            return vec4<f32>(vec3<f32>(depth), 1.0);
+        }
+        case 2u: {
+            return vec4<f32>(select(vec3<f32>(0.0), surface.xyz * 0.5 + 0.5, surface.w > 0.0), 1.0);
+        }
+        case 3u: {
+            return vec4<f32>(textureLoad(albedo, texel, 0).xyz, 1.0);
+        }
+        default: {}
+    }
+    var color = textureLoad(source, texel, 0).xyz;
+    if (display.denoise_strength > 0.0) {
+        color = denoise(texel, size);
+    }
+    color = tonemap(color * display.exposure_scale);
+
+    // Darken towards the corners; distance is 1 at the corners and 0 in the center
+    let distance = length(mesh.uv - 0.5) / length(vec2<f32>(0.5));
+    let falloff = smoothstep(display.vignette_radius, 1.0, distance);
+    color *= 1.0 - display.vignette_strength * falloff;
+
+    if (display.frame_count_label > 0.5) {
+        let label = frame_count_label(texel, u32(display.label_samples));
+        if (label >= 0.0) {
+            color = vec3<f32>(label);
+        }
+    }
+
+    return vec4<f32>(color, 1.0);
+}
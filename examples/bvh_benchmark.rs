@@ -0,0 +1,41 @@
+use bevy::math::Vec3;
+use gpu_ray_tracing::{run_scene, SceneBuilder, SurfaceMaterial};
+
+// A 71x71 grid of small spheres, about 5000 in all. Press J to switch between the BVH and
+// testing every sphere, and F12 to watch how fast the sample count climbs with each.
+const GRID: i32 = 71;
+
+fn main() {
+    let mut builder = SceneBuilder::new().add_plane(
+        Vec3::ZERO,
+        Vec3::Y,
+        SurfaceMaterial::Lambertian(Vec3::splat(0.5)),
+    );
+    for x in 0..GRID {
+        for z in 0..GRID {
+            let center = Vec3::new(
+                (x - GRID / 2) as f32 * 0.5,
+                0.2,
+                (z - GRID / 2) as f32 * 0.5,
+            );
+            let material = match (x + z) % 3 {
+                0 => SurfaceMaterial::Lambertian(Vec3::new(0.8, 0.3, 0.2)),
+                1 => SurfaceMaterial::Metal {
+                    albedo: Vec3::new(0.8, 0.8, 0.9),
+                    fuzz: 0.1,
+                },
+                _ => SurfaceMaterial::Dielectric(1.5),
+            };
+            builder = builder.add_sphere(center, 0.2, material);
+        }
+    }
+
+    let scene = builder
+        .add_light(Vec3::new(0.0, 6.0, 0.0), 1.0, Vec3::ONE, 6.0)
+        .camera(Vec3::new(0.0, 8.0, 20.0), Vec3::ZERO, 40.0)
+        .defocus(0.0, 20.0)
+        .build()
+        .expect("scene fits in the sphere buffer");
+
+    run_scene(scene);
+}
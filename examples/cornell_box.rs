@@ -0,0 +1,7 @@
+use gpu_ray_tracing::{create_cornell_box, run_scene};
+
+// The Cornell box, lit only by the panel in its ceiling. The same scene runs with
+// `cargo run --release -- --cornell`.
+fn main() {
+    run_scene(create_cornell_box());
+}
@@ -0,0 +1,42 @@
+use bevy::math::Vec3;
+use gpu_ray_tracing::{load_obj, run_scene, SceneBuilder, SurfaceMaterial};
+
+fn main() {
+    let scene = SceneBuilder::new()
+        .add_plane(
+            Vec3::ZERO,
+            Vec3::Y,
+            SurfaceMaterial::Lambertian(Vec3::splat(0.5)),
+        )
+        .add_sphere(
+            Vec3::new(-2.2, 1.0, 0.0),
+            1.0,
+            SurfaceMaterial::Dielectric(1.5),
+        )
+        .add_sphere(
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            SurfaceMaterial::Lambertian(Vec3::new(0.8, 0.3, 0.2)),
+        )
+        .add_sphere(
+            Vec3::new(2.2, 1.0, 0.0),
+            1.0,
+            SurfaceMaterial::Metal {
+                albedo: Vec3::new(0.8, 0.8, 0.9),
+                fuzz: 0.05,
+            },
+        )
+        .add_box(
+            Vec3::new(-1.0, 0.0, 1.6),
+            Vec3::new(-0.2, 0.8, 2.4),
+            SurfaceMaterial::Lambertian(Vec3::new(0.2, 0.4, 0.7)),
+        )
+        .mesh(load_obj("assets/meshes/cube.obj"))
+        .add_light(Vec3::new(0.0, 4.0, 2.0), 0.75, Vec3::ONE, 8.0)
+        .camera(Vec3::new(0.0, 2.5, 12.0), Vec3::new(0.0, 1.0, 0.0), 30.0)
+        .defocus(0.0, 12.0)
+        .build()
+        .expect("scene fits in the sphere buffer");
+
+    run_scene(scene);
+}
@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+use bevy::app::AppExit;
+use gpu_ray_tracing::{run_headless, RenderConfig};
+
+// Render a scene file at 1920x1080 without opening a window and save it as render.png.
+// The exit code tells whether the image was written.
+fn main() -> AppExit {
+    run_headless(RenderConfig {
+        width: 1920,
+        height: 1080,
+        samples: 2048,
+        output: PathBuf::from("render.png"),
+        scene: Some(PathBuf::from("assets/scenes/three_spheres.ron")),
+        ..Default::default()
+    })
+}
@@ -0,0 +1,65 @@
+use bevy::math::Vec3;
+use gpu_ray_tracing::{run_scene, SceneBuilder, SurfaceMaterial};
+
+// Metaballs: SDF spheres that melt into each other where they come close, resting on a
+// rounded box, next to an analytic sphere for comparison. The SDF primitives are ray
+// marched; everything else is intersected as usual.
+fn main() {
+    let scene = SceneBuilder::new()
+        .add_plane(
+            Vec3::ZERO,
+            Vec3::Y,
+            SurfaceMaterial::Lambertian(Vec3::splat(0.5)),
+        )
+        .add_sdf_box(
+            Vec3::new(0.0, 0.25, 0.0),
+            Vec3::new(2.0, 0.25, 1.0),
+            0.0,
+            SurfaceMaterial::Lambertian(Vec3::new(0.2, 0.3, 0.6)),
+        )
+        .add_sdf_sphere(
+            Vec3::new(-0.8, 1.0, 0.0),
+            0.6,
+            0.6,
+            SurfaceMaterial::Metal {
+                albedo: Vec3::new(0.9, 0.6, 0.3),
+                fuzz: 0.1,
+            },
+        )
+        .add_sdf_sphere(
+            Vec3::new(0.3, 1.2, 0.1),
+            0.5,
+            0.6,
+            SurfaceMaterial::Metal {
+                albedo: Vec3::new(0.9, 0.6, 0.3),
+                fuzz: 0.1,
+            },
+        )
+        .add_sdf_sphere(
+            Vec3::new(1.1, 0.9, -0.2),
+            0.4,
+            0.6,
+            SurfaceMaterial::Metal {
+                albedo: Vec3::new(0.9, 0.6, 0.3),
+                fuzz: 0.1,
+            },
+        )
+        .add_sdf_torus(
+            Vec3::new(0.0, 2.3, 0.0),
+            0.7,
+            0.15,
+            0.0,
+            SurfaceMaterial::Dielectric(1.5),
+        )
+        .add_sphere(
+            Vec3::new(3.5, 1.0, 0.0),
+            1.0,
+            SurfaceMaterial::Lambertian(Vec3::new(0.8, 0.3, 0.2)),
+        )
+        .add_light(Vec3::new(0.0, 6.0, 3.0), 1.0, Vec3::ONE, 8.0)
+        .camera(Vec3::new(2.0, 3.0, 9.0), Vec3::new(0.5, 1.0, 0.0), 35.0)
+        .build()
+        .expect("scene fits in the GPU buffers");
+
+    run_scene(scene);
+}
@@ -0,0 +1,46 @@
+use bevy::math::Vec3;
+use gpu_ray_tracing::{run_scene, SceneBuilder, SurfaceMaterial};
+
+// Three spheres moving at different speeds in front of a static one. Each moves by its
+// velocity over the frame interval, so the faster ones leave longer streaks.
+fn main() {
+    let scene = SceneBuilder::new()
+        .add_plane(
+            Vec3::ZERO,
+            Vec3::Y,
+            SurfaceMaterial::Lambertian(Vec3::splat(0.5)),
+        )
+        .add_sphere(
+            Vec3::new(0.0, 1.0, -3.0),
+            1.0,
+            SurfaceMaterial::Metal {
+                albedo: Vec3::new(0.8, 0.8, 0.9),
+                fuzz: 0.05,
+            },
+        )
+        .add_moving_sphere(
+            Vec3::new(-3.0, 0.5, 0.0),
+            0.5,
+            Vec3::new(0.5, 0.0, 0.0),
+            SurfaceMaterial::Lambertian(Vec3::new(0.8, 0.3, 0.2)),
+        )
+        .add_moving_sphere(
+            Vec3::new(-1.0, 0.5, 1.5),
+            0.5,
+            Vec3::new(1.5, 0.0, 0.0),
+            SurfaceMaterial::Lambertian(Vec3::new(0.2, 0.7, 0.3)),
+        )
+        .add_moving_sphere(
+            Vec3::new(1.5, 0.5, 0.0),
+            0.5,
+            Vec3::new(0.0, 1.5, 0.0),
+            SurfaceMaterial::Dielectric(1.5),
+        )
+        .add_light(Vec3::new(0.0, 5.0, 2.0), 0.75, Vec3::ONE, 8.0)
+        .camera(Vec3::new(0.0, 2.0, 10.0), Vec3::new(0.0, 1.0, 0.0), 35.0)
+        .shutter(0.0, 1.0)
+        .build()
+        .expect("scene fits in the sphere buffer");
+
+    run_scene(scene);
+}
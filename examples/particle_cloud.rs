@@ -0,0 +1,26 @@
+use bevy::math::Vec3;
+use gpu_ray_tracing::{run_scene, SceneBuilder, SurfaceMaterial};
+
+// A spiral of 20000 small glass beads above a diffuse floor. They share one radius and
+// material, so each takes 12 bytes on the GPU instead of a whole sphere.
+fn main() {
+    let beads = (0..20_000).map(|i| {
+        let t = i as f32 * 0.01;
+        let r = 0.5 + t * 0.02;
+        Vec3::new(r * (t * 3.0).cos(), 0.5 + t * 0.015, r * (t * 3.0).sin())
+    });
+
+    let scene = SceneBuilder::new()
+        .add_plane(
+            Vec3::ZERO,
+            Vec3::Y,
+            SurfaceMaterial::Lambertian(Vec3::splat(0.5)),
+        )
+        .add_sphere_instances(0.03, SurfaceMaterial::Dielectric(1.5), beads)
+        .add_light(Vec3::new(0.0, 8.0, 4.0), 1.0, Vec3::ONE, 10.0)
+        .camera(Vec3::new(0.0, 3.0, 12.0), Vec3::new(0.0, 1.5, 0.0), 40.0)
+        .build()
+        .expect("scene fits in the sphere buffer");
+
+    run_scene(scene);
+}
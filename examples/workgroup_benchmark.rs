@@ -0,0 +1,79 @@
+use std::{
+    io::{BufRead, BufReader},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use bevy::app::AppExit;
+use gpu_ray_tracing::{run_headless, RenderConfig, WORKGROUP_SIZES};
+
+// Render the same scene with each workgroup size and print how long a frame took with
+// each. The sizes to compare are given on the command line, e.g. `-- 8 16 32`, or are
+// WORKGROUP_SIZES without any; sizes the GPU can't run fall back to the default. The
+// workgroup size is fixed once the pipelines are created, so every size gets a process of
+// its own: this runs itself with `--render <size>` once per size.
+const SAMPLES: u32 = 256;
+
+fn main() -> AppExit {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag, size] if flag == "--render" => render(parse_size(size)),
+        [] => benchmark(&WORKGROUP_SIZES),
+        sizes => {
+            let sizes: Vec<u32> = sizes.iter().map(|size| parse_size(size)).collect();
+            benchmark(&sizes)
+        }
+    }
+}
+
+fn parse_size(size: &str) -> u32 {
+    size.parse().expect("workgroup size is a number")
+}
+
+// Render with one workgroup size; the headless renderer logs the frame time
+fn render(workgroup_size: u32) -> AppExit {
+    run_headless(RenderConfig {
+        samples: SAMPLES,
+        output: std::env::temp_dir().join(format!("workgroup_{workgroup_size}.png")),
+        scene: Some(PathBuf::from("assets/scenes/three_spheres.ron")),
+        workgroup_size,
+        ..Default::default()
+    })
+}
+
+fn benchmark(sizes: &[u32]) -> AppExit {
+    let exe = std::env::current_exe().expect("path of the benchmark executable");
+    let mut results = Vec::new();
+    for &size in sizes {
+        let mut child = Command::new(&exe)
+            .args(["--render", &size.to_string()])
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("benchmark can run itself");
+
+        // Pass the log through while looking for the frame time
+        let mut frame_time: Option<f32> = None;
+        for line in BufReader::new(child.stderr.take().unwrap()).lines() {
+            let line = line.expect("log is text");
+            eprintln!("{line}");
+            if let Some((before, _)) = line.split_once(" ms per frame") {
+                frame_time = before
+                    .split_whitespace()
+                    .last()
+                    .and_then(|ms| ms.parse().ok());
+            }
+        }
+
+        let status = child.wait().expect("render process finished");
+        results.push((size, frame_time.filter(|_| status.success())));
+    }
+
+    println!("Workgroup size  ms per frame");
+    for (size, frame_time) in results {
+        match frame_time {
+            Some(ms) => println!("{:>8}x{size:<6} {ms:>12.3}", size),
+            None => println!("{:>8}x{size:<6} {:>12}", size, "failed"),
+        }
+    }
+    AppExit::Success
+}
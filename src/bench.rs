@@ -0,0 +1,147 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{
+    camera::CameraSettings,
+    probe::PixelProbe,
+    scene::builder::{RayTracedScene, SceneBuilder},
+    SurfaceMaterial,
+};
+
+// Side of the grid of spheres in the benchmark scene
+const GRID: i32 = 20;
+
+// How to run the benchmark, see run_benchmark
+#[derive(Resource, Debug, Clone)]
+pub struct BenchConfig {
+    pub width: u32,
+    pub height: u32,
+    // Frames timed for the summary
+    pub frames: u32,
+    // Frames left out after the first samples come back, while the GPU clocks settle
+    pub warmup_frames: u32,
+    // Side of the compute shader's workgroups, see WorkgroupSize
+    pub workgroup_size: u32,
+}
+
+impl Default for BenchConfig {
+    fn default() -> Self {
+        Self {
+            width: crate::SIZE.0,
+            height: crate::SIZE.1,
+            frames: 300,
+            warmup_frames: 30,
+            workgroup_size: *crate::WorkgroupSize::default(),
+        }
+    }
+}
+
+// The scene every benchmark renders: a grid of spheres in each material over a ground
+// plane, lit by one light and seen from a fixed camera. It is built in code so results
+// don't depend on the assets folder or the random startup scene.
+pub fn bench_scene() -> RayTracedScene {
+    let mut builder = SceneBuilder::new().add_plane(
+        Vec3::ZERO,
+        Vec3::Y,
+        SurfaceMaterial::Lambertian(Vec3::splat(0.5)),
+    );
+    for x in 0..GRID {
+        for z in 0..GRID {
+            let center = Vec3::new(
+                (x - GRID / 2) as f32 * 0.6,
+                0.25,
+                (z - GRID / 2) as f32 * 0.6,
+            );
+            let material = match (x + z) % 3 {
+                0 => SurfaceMaterial::Lambertian(Vec3::new(0.8, 0.3, 0.2)),
+                1 => SurfaceMaterial::Metal {
+                    albedo: Vec3::new(0.8, 0.8, 0.9),
+                    fuzz: 0.1,
+                },
+                _ => SurfaceMaterial::Dielectric(1.5),
+            };
+            builder = builder.add_sphere(center, 0.25, material);
+        }
+    }
+
+    builder
+        .add_light(Vec3::new(0.0, 6.0, 0.0), 1.0, Vec3::ONE, 6.0)
+        .camera(Vec3::new(0.0, 5.0, 12.0), Vec3::ZERO, 40.0)
+        .defocus(0.0, 12.0)
+        .build()
+        .expect("benchmark scene fits in the buffers")
+}
+
+// Keep accumulating for the whole benchmark
+pub fn setup_benchmark(config: Res<BenchConfig>, mut camera_settings: ResMut<CameraSettings>) {
+    // Pixels that reached their samples or converged would stop tracing rays
+    camera_settings.samples_per_pixel = u32::MAX;
+    camera_settings.sample_budget = 0;
+    camera_settings.variance_threshold = 0.0;
+    info!(
+        "Benchmarking {} frames at {}x{} with {}x{} workgroups",
+        config.frames, config.width, config.height, config.workgroup_size, config.workgroup_size
+    );
+}
+
+/// System to collect the frame times from Bevy's diagnostics once the first samples come
+/// back, then print the throughput and exit
+pub fn bench_system(
+    config: Res<BenchConfig>,
+    probe: Res<PixelProbe>,
+    diagnostics: Res<DiagnosticsStore>,
+    camera_settings: Res<CameraSettings>,
+    mut warmup_left: Local<Option<u32>>,
+    // Frame times in milliseconds
+    mut frame_times: Local<Vec<f64>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    // The probe's sample count comes back from the GPU, so frames spent compiling the
+    // shader are left out
+    if probe.color.w <= 0.0 {
+        return;
+    }
+    let warmup_left = warmup_left.get_or_insert(config.warmup_frames);
+    if *warmup_left > 0 {
+        *warmup_left -= 1;
+        return;
+    }
+
+    let Some(frame_time) = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|diagnostic| diagnostic.value())
+    else {
+        return;
+    };
+    frame_times.push(frame_time);
+    if frame_times.len() < config.frames as usize {
+        return;
+    }
+
+    let min = frame_times.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = frame_times.iter().copied().fold(0.0, f64::max);
+    let total: f64 = frame_times.iter().sum();
+    let avg = total / frame_times.len() as f64;
+    // samples_per_frame samples per pixel per frame, each counted as a full path of
+    // max_depth bounces.
+    // Paths that miss or are ended early by Russian roulette trace fewer, so this is an
+    // upper bound, but a fixed one for comparing runs.
+    let rays = config.width as f64
+        * config.height as f64
+        * frame_times.len() as f64
+        * camera_settings.samples_per_frame.max(1) as f64
+        * camera_settings.max_depth as f64;
+    let megarays = rays / (total / 1000.0) / 1e6;
+    println!(
+        "{}x{}, {} frames of {} samples, {} bounces: {megarays:.1} Mrays/s, frame time min {min:.3} ms, \
+         avg {avg:.3} ms, max {max:.3} ms",
+        config.width,
+        config.height,
+        frame_times.len(),
+        camera_settings.samples_per_frame.max(1),
+        camera_settings.max_depth
+    );
+    exit.send(AppExit::Success);
+}
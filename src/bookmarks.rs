@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+use crate::camera::{CameraSettings, LensProjection};
+
+// File the bookmarks are kept in between sessions
+pub const BOOKMARKS_FILE_PATH: &str = "camera_bookmarks.json";
+
+// Keys of the bookmark slots 1 to 8; 9 and 0 adjust the vignette
+const SLOT_KEYS: [KeyCode; 8] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+];
+
+// A saved view: where the camera is and looks, and how it projects and focuses
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub look_from: [f32; 3],
+    pub look_at: [f32; 3],
+    pub vup: [f32; 3],
+    pub projection: LensProjection,
+    pub defocus_angle: f32,
+    pub focus_distance: f32,
+}
+
+// Everything that can go wrong while reading or writing the bookmarks file
+#[derive(Debug)]
+pub enum BookmarkError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for BookmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookmarkError::Io(err) => write!(f, "could not access bookmarks file: {err}"),
+            BookmarkError::Json(err) => write!(f, "invalid bookmarks file: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BookmarkError {}
+
+// Views saved into numbered slots, stored in the file as a JSON object keyed by slot
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CameraBookmarks {
+    pub slots: BTreeMap<u32, CameraBookmark>,
+}
+
+impl CameraBookmarks {
+    // Store the current view of `camera` in `slot`, replacing what was there
+    pub fn save(&mut self, slot: u32, camera: &CameraSettings) {
+        self.slots.insert(
+            slot,
+            CameraBookmark {
+                look_from: camera.look_from.to_array(),
+                look_at: camera.look_at.to_array(),
+                vup: camera.vup.to_array(),
+                projection: camera.projection,
+                defocus_angle: camera.defocus_angle,
+                focus_distance: camera.focus_distance,
+            },
+        );
+    }
+
+    // Jump `camera` to the view in `slot` and restart accumulation. Returns false, leaving
+    // the camera alone, if the slot is empty.
+    pub fn load(&self, slot: u32, camera: &mut CameraSettings) -> bool {
+        let Some(bookmark) = self.slots.get(&slot) else {
+            return false;
+        };
+        camera.look_from = Vec3::from_array(bookmark.look_from);
+        camera.look_at = Vec3::from_array(bookmark.look_at);
+        camera.vup = Vec3::from_array(bookmark.vup);
+        camera.projection = bookmark.projection;
+        camera.target_fov = None;
+        camera.defocus_angle = bookmark.defocus_angle;
+        camera.focus_distance = bookmark.focus_distance;
+        camera.camera_has_moved = true;
+        true
+    }
+
+    pub fn read_from_file(path: &Path) -> Result<Self, BookmarkError> {
+        let source = fs::read_to_string(path).map_err(BookmarkError::Io)?;
+        serde_json::from_str(&source).map_err(BookmarkError::Json)
+    }
+
+    pub fn write_to_file(&self, path: &Path) -> Result<(), BookmarkError> {
+        let source = serde_json::to_string_pretty(self).map_err(BookmarkError::Json)?;
+        fs::write(path, source).map_err(BookmarkError::Io)
+    }
+}
+
+// Load the bookmarks saved in earlier sessions, if there are any
+pub fn load_bookmarks(mut bookmarks: ResMut<CameraBookmarks>) {
+    match CameraBookmarks::read_from_file(Path::new(BOOKMARKS_FILE_PATH)) {
+        Ok(loaded) => *bookmarks = loaded,
+        Err(BookmarkError::Io(err)) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => warn!("Could not load {BOOKMARKS_FILE_PATH}: {err}"),
+    }
+}
+
+/// System to save the current view into a bookmark with Ctrl+1 to Ctrl+8 and jump back to
+/// it with 1 to 8. Saved bookmarks are written to BOOKMARKS_FILE_PATH right away.
+pub fn camera_bookmarks_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    let Some(slot) = SLOT_KEYS
+        .iter()
+        .position(|&key| keyboard_input.just_pressed(key))
+        .map(|index| index as u32 + 1)
+    else {
+        return;
+    };
+
+    if keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        bookmarks.save(slot, &camera_settings);
+        match bookmarks.write_to_file(Path::new(BOOKMARKS_FILE_PATH)) {
+            Ok(()) => info!("Saved camera bookmark {slot}"),
+            Err(err) => error!("Could not save camera bookmark {slot}: {err}"),
+        }
+    } else if bookmarks.load(slot, &mut camera_settings) {
+        info!("Recalled camera bookmark {slot}");
+    } else {
+        info!("Camera bookmark {slot} is empty");
+    }
+}
@@ -1,51 +1,369 @@
 use bevy::{
-    input::mouse::{MouseMotion, MouseWheel},
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
     prelude::*,
     render::{extract_resource::ExtractResource, render_resource::ShaderType},
 };
 use bytemuck::{Pod, Zeroable};
+use serde::{Deserialize, Serialize};
+
+use crate::{denoise::DenoiseSettings, probe::PixelProbe, step::StepMode, tiles::TiledRendering};
+
+// How rays leave the camera
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LensProjection {
+    // Rays fan out from look_from; fov is the vertical field of view in degrees
+    Perspective { fov: f32 },
+    // Parallel rays from a viewport `height` world units tall, so sizes don't change with
+    // distance
+    Orthographic { height: f32 },
+}
+
+impl LensProjection {
+    // The other kind of projection, showing the focus plane at the same size
+    pub fn toggled(self, focus_distance: f32) -> Self {
+        match self {
+            LensProjection::Perspective { fov } => LensProjection::Orthographic {
+                height: 2.0 * f32::tan(fov.to_radians() / 2.0) * focus_distance,
+            },
+            LensProjection::Orthographic { height } => LensProjection::Perspective {
+                fov: (2.0 * f32::atan(height / (2.0 * focus_distance))).to_degrees(),
+            },
+        }
+    }
+}
+
+// How movement input steers the camera, switched with F
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CameraMode {
+    // W/A/S/D and the Up/Down arrows move look_from, dragging turns the view and the wheel
+    // zooms
+    #[default]
+    Fly,
+    // The camera circles look_at: dragging, A/D and the arrows orbit it, and W/S and the
+    // wheel dolly towards or away from it. For inspecting a single object.
+    Orbit,
+}
 
 // Camera settings used in the main app
 #[derive(Resource, Debug, Clone)]
 pub struct CameraSettings {
-    pub field_of_view: f32,
+    pub mode: CameraMode,
+    pub projection: LensProjection,
     pub samples_per_pixel: u32,
+    // Samples each pixel takes per frame on the way to samples_per_pixel. More converge a
+    // still image in fewer frames, but make each frame slower; the preview while moving
+    // always takes one.
+    pub samples_per_frame: u32,
     pub camera_has_moved: bool,
     pub max_depth: u32,
     pub vup: Vec3,
     pub look_from: Vec3,
     pub look_at: Vec3,
+    // Direction look_at lies in from look_from, in radians: yaw turns around +Y starting
+    // from +Z and pitch is the angle above the horizon, within MAX_PITCH_DEGREES of it.
+    // look_around and orbit change these and rebuild the view from them, see sync_angles.
+    pub yaw: f32,
+    pub pitch: f32,
     pub defocus_angle: f32,
     pub focus_distance: f32,
+    // Samples accumulated in each pixel before the current frame since the last reset,
+    // maintained by extract_camera. Every frame adds samples_per_frame.
+    pub accumulated_frames: u32,
+    // Samples after which a still camera stops rendering, to save power on a converged
+    // image; see step::sample_budget_system. 0 renders for as long as the app runs.
+    pub sample_budget: u32,
+    // Estimate how much of each pixel a sphere's silhouette covers instead of relying on
+    // jittered samples alone, which converges edges much faster
+    pub edge_antialiasing: bool,
+    // Spread each pixel's samples over a grid of sub-pixel cells with a jitter inside each
+    // cell, instead of placing every sample at random
+    pub stratified_sampling: bool,
+    // Send diffuse bounces out with density proportional to the cosine with the normal,
+    // matching the cosine in the rendering equation, instead of evenly over the hemisphere
+    pub cosine_weighted_diffuse: bool,
+    // Bounces every path makes before Russian roulette may end it early, with a chance that
+    // grows as the path's throughput drops. Set it to max_depth or more to turn it off.
+    pub min_bounces_before_rr: u32,
+    // Shadow rays traced per shading point, independent of samples_per_pixel: towards each
+    // light in direct lighting mode, and towards the sampled light and the sun at every
+    // diffuse bounce otherwise. More smooth out penumbras at the cost of speed.
+    pub light_samples: u32,
+    // In path tracing mode, sample a point on an emissive sphere or quad at every diffuse
+    // bounce and trace a shadow ray to it, weighted against the bounce's own sampling by
+    // multiple importance sampling. Small lights converge far faster; the image is the same.
+    pub next_event_estimation: bool,
+    // Pixels stop taking samples once the variance of their mean luminance, relative to
+    // the mean squared, drops below this. 0 samples every pixel up to samples_per_pixel.
+    pub variance_threshold: f32,
+    // Field of view the mouse wheel is zooming towards, which ease_fov_system moves the
+    // perspective fov to over a few frames; None once it is reached
+    pub target_fov: Option<f32>,
+    // Part of the frame interval the shutter is open for, in 0..=1. Each sample is taken
+    // at a random time in it, so moving spheres blur over the distance they cover.
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+    // Base seed of the random numbers. With a seed, each sample's random numbers follow
+    // from it, the pixel and the sample's index, so the same scene, camera and sample count
+    // always render the same image; None draws a fresh seed every frame.
+    pub deterministic_seed: Option<u64>,
+    // Color the image starts out as, until each pixel's first sample replaces it. It never
+    // mixes into the samples.
+    pub clear_color: Vec3,
     // Camera movement is handled by keyboard and mouse controls:
     // W/S: Move forward/backward
     // A/D: Strafe left/right
     // Up/Down arrows: Move up/down
-    // Left/Right arrows: Rotate camera left/right (yaw)
+    // Left/Right arrows: Turn the view around look_from (yaw)
     // PageUp/PageDown: Look up/down (pitch)
     // Mouse wheel: Zoom in/out (change field of view)
-    // Right mouse button + drag: Rotate camera view
+    // Right mouse button + drag: Turn the view around look_from
+    // F: Switch to orbit mode, see CameraMode::Orbit
+}
+
+// Adaptive sampling thresholds cycled with Y, starting with adaptive sampling off
+const VARIANCE_THRESHOLDS: [f32; 4] = [0.0, 1e-2, 1e-3, 1e-4];
+
+// Extra room left around a framed sphere, as a multiple of its radius
+const FRAME_MARGIN: f32 = 1.3;
+
+impl CameraSettings {
+    // Samples each pixel takes per dispatch: samples_per_frame, or a single one in step
+    // mode so that every step adds exactly one sample
+    pub fn samples_this_frame(&self, step_mode: &StepMode) -> u32 {
+        if step_mode.enabled {
+            1
+        } else {
+            self.samples_per_frame.max(1)
+        }
+    }
+
+    // Most samples any pixel of the image on screen can have: the ones accumulated before
+    // the last dispatched frame and the ones it added, up to samples_per_pixel
+    pub fn samples_shown(&self, step_mode: &StepMode) -> u32 {
+        self.accumulated_frames
+            .saturating_add(self.samples_this_frame(step_mode))
+            .min(self.samples_per_pixel)
+    }
+
+    // Camera position that keeps the current viewing direction and fits a sphere at
+    // `center` with `radius` in the narrower field of view of a render target of `size`
+    // pixels. Distance doesn't change the framing of an orthographic camera, so it is kept.
+    pub fn frame_sphere(&self, center: Vec3, radius: f32, size: UVec2) -> Vec3 {
+        let distance = match self.projection {
+            LensProjection::Perspective { fov } => {
+                // fov is vertical; a portrait target is narrower across
+                let aspect_ratio = size.x as f32 / size.y.max(1) as f32;
+                let half_tan = (f32::to_radians(fov) / 2.0).tan() * aspect_ratio.min(1.0);
+                FRAME_MARGIN * radius / half_tan.atan().sin()
+            }
+            LensProjection::Orthographic { .. } => self.look_from.distance(self.look_at),
+        };
+        let direction = (self.look_from - self.look_at).normalize_or(Vec3::Z);
+        center + direction * distance
+    }
+
+    // Work yaw and pitch out again from look_from and look_at. Systems that set those
+    // directly, such as bookmarks or moving in fly mode, don't need to call this: turning
+    // the view does so itself once the stored angles no longer point along it.
+    pub fn sync_angles(&mut self) {
+        let direction = (self.look_at - self.look_from).normalize_or(Vec3::NEG_Z);
+        let max_pitch = MAX_PITCH_DEGREES.to_radians();
+        let pitch = direction.y.clamp(-1.0, 1.0).asin();
+        self.yaw = direction.x.atan2(direction.z);
+        self.pitch = pitch.clamp(-max_pitch, max_pitch);
+    }
+
+    // Add to the stored angles, clamping the pitch instead of dropping the input so that
+    // turning into the limit can always be undone, and return the new view direction
+    fn turn(&mut self, yaw: f32, pitch: f32) -> Vec3 {
+        let direction = (self.look_at - self.look_from).normalize_or(Vec3::NEG_Z);
+        if direction.angle_between(view_direction(self.yaw, self.pitch)) > ANGLE_SYNC_TOLERANCE {
+            self.sync_angles();
+        }
+        let max_pitch = MAX_PITCH_DEGREES.to_radians();
+        self.yaw = (self.yaw + yaw).rem_euclid(std::f32::consts::TAU);
+        self.pitch = (self.pitch + pitch).clamp(-max_pitch, max_pitch);
+        view_direction(self.yaw, self.pitch)
+    }
+
+    // Turn the view around look_from, which stays where it is. Angles are in radians; a
+    // positive pitch looks up.
+    pub fn look_around(&mut self, yaw: f32, pitch: f32) {
+        let distance = self.look_from.distance(self.look_at);
+        self.look_at = self.look_from + self.turn(yaw, pitch) * distance;
+    }
+
+    // Orbit look_from around look_at, keeping the distance between them. Angles are in
+    // radians; a positive pitch moves the camera up, so it looks further down.
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let distance = self.look_from.distance(self.look_at);
+        self.look_from = self.look_at - self.turn(yaw, -pitch) * distance;
+    }
+
+    // Move look_from towards look_at (factor below 1) or away from it by scaling the
+    // distance between them. The focus distance scales with it, so whatever was in focus
+    // relative to look_at stays in focus.
+    pub fn dolly(&mut self, factor: f32) {
+        let offset = self.look_from - self.look_at;
+        let distance = offset.length();
+        let new_distance = (distance * factor).max(ORBIT_MIN_DISTANCE);
+        self.look_from = self.look_at + offset.normalize_or(Vec3::Z) * new_distance;
+        if distance > 0.0 {
+            self.focus_distance *= new_distance / distance;
+        }
+    }
+}
+
+// Closest the camera can dolly to look_at
+const ORBIT_MIN_DISTANCE: f32 = 0.1;
+
+// Steepest angle above or below the horizon the camera can face. Straight up or down the
+// yaw is undefined and the view would spin, so pitch is clamped just short of it.
+const MAX_PITCH_DEGREES: f32 = 89.0;
+
+// How far, in radians, the stored yaw and pitch may be from the actual view before they
+// are worked out again. Rebuilding look_at from them rounds a little, which must not count.
+const ANGLE_SYNC_TOLERANCE: f32 = 1e-3;
+
+// Unit vector `yaw` around +Y from +Z and `pitch` above the horizon
+fn view_direction(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        yaw.cos() * pitch.cos(),
+    )
 }
 
 impl Default for CameraSettings {
     fn default() -> Self {
-        Self {
+        let mut settings = Self {
             samples_per_pixel: 500,
+            samples_per_frame: 4,
             max_depth: 30,
             camera_has_moved: true, // Start with reset flag on to render first frame
             
-            field_of_view: 20.0,
+            mode: CameraMode::Fly,
+            projection: LensProjection::Perspective { fov: 20.0 },
             look_from: Vec3::new(13.0, 2.0, 3.0),
             look_at: Vec3::new(0.0, 0.0, 0.0),
             vup: Vec3::new(0.0, 1.0, 0.0),
+            yaw: 0.0,
+            pitch: 0.0,
             
             defocus_angle: 0.6,
             focus_distance: 10.0,
-        }
+            accumulated_frames: 0,
+            sample_budget: 4096,
+            edge_antialiasing: false,
+            stratified_sampling: true,
+            cosine_weighted_diffuse: true,
+            min_bounces_before_rr: 3,
+            light_samples: 1,
+            next_event_estimation: true,
+            variance_threshold: 0.0,
+            target_fov: None,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            deterministic_seed: None,
+            clear_color: Vec3::ZERO,
+        };
+        settings.sync_angles();
+        settings
     }
 }
-/// System to handle camera control with mouse (wheel zoom, movement)
-pub fn _camera_mouse_controls_system(
+/// System to switch between fly and orbit camera controls with F. The view itself doesn't
+/// change, so neither does the image.
+pub fn toggle_camera_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyF) {
+        camera_settings.mode = match camera_settings.mode {
+            CameraMode::Fly => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Fly,
+        };
+        info!("Camera mode: {:?}", camera_settings.mode);
+    }
+}
+
+/// System to switch between perspective and orthographic projection with R
+pub fn toggle_projection_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        camera_settings.projection = camera_settings
+            .projection
+            .toggled(camera_settings.focus_distance);
+        camera_settings.camera_has_moved = true;
+        info!("Projection: {:?}", camera_settings.projection);
+    }
+}
+
+/// System to switch between stratified and purely random pixel samples with Z, and between
+/// cosine-weighted and uniform diffuse bounces with Shift+Z
+pub fn toggle_stratified_sampling_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyZ) {
+        return;
+    }
+
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        camera_settings.cosine_weighted_diffuse = !camera_settings.cosine_weighted_diffuse;
+        camera_settings.camera_has_moved = true;
+        info!(
+            "Cosine-weighted diffuse bounces: {}",
+            camera_settings.cosine_weighted_diffuse
+        );
+    } else {
+        camera_settings.stratified_sampling = !camera_settings.stratified_sampling;
+        camera_settings.camera_has_moved = true;
+        info!(
+            "Stratified sampling: {}",
+            camera_settings.stratified_sampling
+        );
+    }
+}
+
+/// System to cycle the adaptive sampling threshold with Y, from off to stricter and
+/// stricter values
+pub fn cycle_variance_threshold_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyY) {
+        return;
+    }
+
+    let next = VARIANCE_THRESHOLDS
+        .iter()
+        .position(|&threshold| threshold == camera_settings.variance_threshold)
+        .map_or(0, |index| (index + 1) % VARIANCE_THRESHOLDS.len());
+    // No reset needed: converged pixels pick up sampling again when the threshold drops
+    camera_settings.variance_threshold = VARIANCE_THRESHOLDS[next];
+    info!(
+        "Adaptive sampling threshold: {}",
+        camera_settings.variance_threshold
+    );
+}
+
+/// System to toggle analytic edge anti-aliasing with X
+pub fn toggle_edge_antialiasing_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyX) {
+        camera_settings.edge_antialiasing = !camera_settings.edge_antialiasing;
+        camera_settings.camera_has_moved = true;
+        info!("Edge anti-aliasing: {}", camera_settings.edge_antialiasing);
+    }
+}
+
+/// System to handle camera control with mouse: in fly mode the wheel zooms and dragging
+/// turns the view, in orbit mode the wheel dollies and dragging orbits look_at
+pub fn camera_mouse_controls_system(
     mut mouse_wheel: EventReader<MouseWheel>,
     mouse_button: Res<ButtonInput<MouseButton>>,
     mut mouse_motion: EventReader<MouseMotion>,
@@ -53,57 +371,45 @@ pub fn _camera_mouse_controls_system(
 ) {
     let mut moved = false;
 
-    // Handle mouse wheel for zooming (changing field of view)
+    // Handle mouse wheel for zooming (changing field of view). Each scroll line scales the
+    // target FOV by the same factor, so zooming feels the same at any FOV; scrolling up
+    // (positive y) decreases FOV (zooms in). ease_fov_system then moves towards it.
     for event in mouse_wheel.read() {
-        // Adjust field of view based on scroll direction
-        // Scrolling up (positive y) decreases FOV (zooms in)
-        // Scrolling down (negative y) increases FOV (zooms out)
-        let zoom_delta = -event.y * _MOUSE_ZOOM_SENSITIVITY;
-        let new_fov = (camera_settings.field_of_view + zoom_delta).clamp(_FOV_MIN, _FOV_MAX);
-
-        if new_fov != camera_settings.field_of_view {
-            camera_settings.field_of_view = new_fov;
+        let lines = match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / MOUSE_SCROLL_PIXELS_PER_LINE,
+        };
+        if camera_settings.mode == CameraMode::Orbit {
+            // Scrolling up moves closer
+            camera_settings.dolly(MOUSE_DOLLY_FACTOR.powf(-lines));
             moved = true;
+            continue;
         }
+        let LensProjection::Perspective { fov } = camera_settings.projection else {
+            continue;
+        };
+        let target = camera_settings.target_fov.unwrap_or(fov);
+        camera_settings.target_fov =
+            Some((target * MOUSE_ZOOM_FACTOR.powf(-lines)).clamp(FOV_MIN, FOV_MAX));
     }
 
-    // Handle mouse movement while right button is pressed
+    // Handle mouse movement while right button is pressed: horizontal movement turns the
+    // view (yaw) and vertical movement looks up/down (pitch). In orbit mode the camera
+    // moves around look_at instead, as if dragging the scene.
     if mouse_button.pressed(MouseButton::Right) {
-        // Calculate camera rotation from mouse movement
-        for event in mouse_motion.read() {
-            // Horizontal movement (x) rotates around Y axis (yaw)
-            if event.delta.x != 0.0 {
-                let rotation = Quat::from_rotation_y(-event.delta.x * _MOUSE_MOVE_SENSITIVITY);
-                let view_direction = camera_settings.look_at - camera_settings.look_from;
-                let len = view_direction.length();
-                let view_direction = rotation.mul_vec3(view_direction).normalize();
-                camera_settings.look_at = camera_settings.look_from + view_direction * len;
-                moved = true;
-            }
-
-            // Vertical movement (y) rotates around local X axis (pitch)
-            if event.delta.y != 0.0 {
-                // Get current camera basis vectors
-                let view_direction = camera_settings.look_at - camera_settings.look_from;
-                let len = view_direction.length();
-                let forward = view_direction.normalize();
-                let right = forward.cross(Vec3::Y).normalize();
-
-                // Create rotation around the right vector (pitch)
-                let rotation =
-                    Quat::from_axis_angle(right, -event.delta.y * _MOUSE_MOVE_SENSITIVITY);
-
-                // Apply rotation - but check to prevent flipping over
-                let new_direction = rotation.mul_vec3(forward).normalize();
-
-                // Prevent camera from flipping by checking if the new direction is not too close to up/down
-                if new_direction.dot(Vec3::Y).abs() < 0.95 {
-                    let view_direction = rotation.mul_vec3(view_direction).normalize();
-                    camera_settings.look_at = camera_settings.look_from + view_direction * len;
-                    moved = true;
-                }
+        let delta: Vec2 = mouse_motion.read().map(|event| event.delta).sum();
+        if delta != Vec2::ZERO {
+            let yaw = -delta.x * MOUSE_MOVE_SENSITIVITY;
+            let pitch = -delta.y * MOUSE_MOVE_SENSITIVITY;
+            match camera_settings.mode {
+                CameraMode::Fly => camera_settings.look_around(yaw, pitch),
+                CameraMode::Orbit => camera_settings.orbit(yaw, -pitch),
             }
+            moved = true;
         }
+    } else {
+        // Drop motion from before the button was pressed so the view doesn't jump
+        mouse_motion.clear();
     }
 
     // Update the camera_has_moved flag if needed
@@ -112,14 +418,41 @@ pub fn _camera_mouse_controls_system(
     }
 }
 
+/// System to ease the perspective field of view towards the target the mouse wheel set,
+/// restarting accumulation every frame until it gets there
+pub fn ease_fov_system(time: Res<Time>, mut camera_settings: ResMut<CameraSettings>) {
+    let Some(target) = camera_settings.target_fov else {
+        return;
+    };
+    // Other systems may have switched to orthographic or replaced the camera since
+    let LensProjection::Perspective { fov } = camera_settings.projection else {
+        camera_settings.target_fov = None;
+        return;
+    };
+
+    // Interpolate the ratio so zooming in and out take equally long
+    let t = 1.0 - (-FOV_EASE_RATE * time.delta_secs()).exp();
+    let mut fov = fov * (target / fov).powf(t);
+    if (fov / target - 1.0).abs() < 1e-3 {
+        fov = target;
+        camera_settings.target_fov = None;
+    }
+    camera_settings.projection = LensProjection::Perspective { fov };
+    camera_settings.camera_has_moved = true;
+}
+
 // Camera movement constants
 const CAMERA_MOVE_SPEED: f32 = 2.0; // Units per second
 const CAMERA_ROTATE_SPEED: f32 = 1.0; // Radians per second
 const CAMERA_VERTICAL_SPEED: f32 = 1.0; // Units per second
-const _MOUSE_ZOOM_SENSITIVITY: f32 = 1.0; // FOV change per scroll unit
-const _MOUSE_MOVE_SENSITIVITY: f32 = 0.002; // Movement sensitivity
-const _FOV_MIN: f32 = 10.0; // Minimum field of view (degrees)
-const _FOV_MAX: f32 = 120.0; // Maximum field of view (degrees)
+const MOUSE_ZOOM_FACTOR: f32 = 1.1; // FOV scale per scroll line
+const MOUSE_DOLLY_FACTOR: f32 = 1.1; // Orbit distance scale per scroll line
+const ORBIT_DOLLY_SPEED: f32 = 1.0; // Exponential rate of W/S dollying in orbit mode, per second
+const MOUSE_SCROLL_PIXELS_PER_LINE: f32 = 20.0; // Touchpads scroll in pixels
+const FOV_EASE_RATE: f32 = 12.0; // Exponential rate at which the FOV closes in on its target
+const MOUSE_MOVE_SENSITIVITY: f32 = 0.002; // Movement sensitivity
+const FOV_MIN: f32 = 10.0; // Minimum field of view (degrees)
+pub const FOV_MAX: f32 = 120.0; // Maximum field of view (degrees)
 
 /// System to handle camera movement based on keyboard input
 pub fn camera_movement_system(
@@ -130,129 +463,219 @@ pub fn camera_movement_system(
     let dt = time.delta_secs();
     let mut moved = false;
 
-    // Get current camera basis vectors
-    let view_direction = camera_settings.look_from - camera_settings.look_at;
-    let forward = view_direction.normalize();
-    let right = forward.cross(Vec3::Y).normalize();
-    // let up = right.cross(forward).normalize();
+    // Angles to turn the view by, or to orbit look_at by in orbit mode, collected from the
+    // keys below
+    let mut yaw = 0.0;
+    let mut pitch = 0.0;
 
-    // Handle forward/backward movement (W/S)
-    if keyboard_input.pressed(KeyCode::KeyW) {
-        camera_settings.look_from += forward * CAMERA_MOVE_SPEED * dt;
-        moved = true;
-    }
-    if keyboard_input.pressed(KeyCode::KeyS) {
-        camera_settings.look_from -= forward * CAMERA_MOVE_SPEED * dt;
-        moved = true;
-    }
+    if camera_settings.mode == CameraMode::Orbit {
+        // Dolly in/out (W/S), orbit left/right (A/D) and up/down (Up/Down arrows)
+        let mut dolly = 0.0;
+        if keyboard_input.pressed(KeyCode::KeyW) {
+            dolly -= ORBIT_DOLLY_SPEED * dt;
+        }
+        if keyboard_input.pressed(KeyCode::KeyS) {
+            dolly += ORBIT_DOLLY_SPEED * dt;
+        }
+        if dolly != 0.0 {
+            camera_settings.dolly(dolly.exp());
+            moved = true;
+        }
+        if keyboard_input.pressed(KeyCode::KeyA) {
+            yaw += CAMERA_ROTATE_SPEED * dt;
+        }
+        if keyboard_input.pressed(KeyCode::KeyD) {
+            yaw -= CAMERA_ROTATE_SPEED * dt;
+        }
+        if keyboard_input.pressed(KeyCode::ArrowUp) {
+            pitch += CAMERA_ROTATE_SPEED * dt;
+        }
+        if keyboard_input.pressed(KeyCode::ArrowDown) {
+            pitch -= CAMERA_ROTATE_SPEED * dt;
+        }
+    } else {
+        // Get current camera basis vectors
+        let view_direction = camera_settings.look_from - camera_settings.look_at;
+        let forward = view_direction.normalize();
+        let right = forward.cross(Vec3::Y).normalize();
+        // let up = right.cross(forward).normalize();
 
-    // Handle strafing left/right (A/D)
-    if keyboard_input.pressed(KeyCode::KeyA) {
-        camera_settings.look_from -= right * CAMERA_MOVE_SPEED * dt;
-        moved = true;
-    }
-    if keyboard_input.pressed(KeyCode::KeyD) {
-        camera_settings.look_from += right * CAMERA_MOVE_SPEED * dt;
-        moved = true;
-    }
+        // Handle forward/backward movement (W/S)
+        if keyboard_input.pressed(KeyCode::KeyW) {
+            camera_settings.look_from += forward * CAMERA_MOVE_SPEED * dt;
+            moved = true;
+        }
+        if keyboard_input.pressed(KeyCode::KeyS) {
+            camera_settings.look_from -= forward * CAMERA_MOVE_SPEED * dt;
+            moved = true;
+        }
 
-    // Handle vertical movement (Up/Down arrows)
-    if keyboard_input.pressed(KeyCode::ArrowUp) {
-        camera_settings.look_from += Vec3::Y * CAMERA_VERTICAL_SPEED * dt;
-        moved = true;
-    }
-    if keyboard_input.pressed(KeyCode::ArrowDown) {
-        camera_settings.look_from -= Vec3::Y * CAMERA_VERTICAL_SPEED * dt;
-        moved = true;
+        // Handle strafing left/right (A/D)
+        if keyboard_input.pressed(KeyCode::KeyA) {
+            camera_settings.look_from -= right * CAMERA_MOVE_SPEED * dt;
+            moved = true;
+        }
+        if keyboard_input.pressed(KeyCode::KeyD) {
+            camera_settings.look_from += right * CAMERA_MOVE_SPEED * dt;
+            moved = true;
+        }
+
+        // Handle vertical movement (Up/Down arrows)
+        if keyboard_input.pressed(KeyCode::ArrowUp) {
+            camera_settings.look_from += Vec3::Y * CAMERA_VERTICAL_SPEED * dt;
+            moved = true;
+        }
+        if keyboard_input.pressed(KeyCode::ArrowDown) {
+            camera_settings.look_from -= Vec3::Y * CAMERA_VERTICAL_SPEED * dt;
+            moved = true;
+        }
     }
 
-    // Handle rotation (Left/Right arrows)
+    // Handle turning the view: yaw with the Left/Right arrows and pitch with
+    // PageUp/PageDown. In orbit mode they orbit look_at like A/D and the Up/Down arrows.
     if keyboard_input.pressed(KeyCode::ArrowLeft) {
-        // Rotate around Y axis (yaw)
-        let rotation = Quat::from_rotation_y(CAMERA_ROTATE_SPEED * dt);
-        let view_direction = camera_settings.look_from - camera_settings.look_at;
-        let len = view_direction.length();
-        let view_direction = rotation.mul_vec3(view_direction).normalize();
-        camera_settings.look_from = camera_settings.look_at + view_direction * len;
-        moved = true;
+        yaw += CAMERA_ROTATE_SPEED * dt;
     }
     if keyboard_input.pressed(KeyCode::ArrowRight) {
-        // Rotate around Y axis (yaw) - opposite direction
-        let rotation = Quat::from_rotation_y(-CAMERA_ROTATE_SPEED * dt);
-        let view_direction = camera_settings.look_from - camera_settings.look_at;
-        let len = view_direction.length();
-        let view_direction = rotation.mul_vec3(view_direction).normalize();
-        camera_settings.look_from = camera_settings.look_at + view_direction * len;
-        moved = true;
+        yaw -= CAMERA_ROTATE_SPEED * dt;
     }
-    // Handle rotation (Left/Right arrows)
-    if keyboard_input.pressed(KeyCode::ArrowLeft) {
-        // Rotate around Y axis (yaw)
-        let rotation = Quat::from_rotation_y(CAMERA_ROTATE_SPEED * dt);
-        let view_direction = camera_settings.look_from - camera_settings.look_at;
-        let len = view_direction.length();
-        let view_direction = rotation.mul_vec3(view_direction).normalize();
-        camera_settings.look_from = camera_settings.look_at + view_direction * len;
-        moved = true;
+    if keyboard_input.pressed(KeyCode::PageUp) {
+        pitch += CAMERA_ROTATE_SPEED * dt;
     }
-    if keyboard_input.pressed(KeyCode::ArrowRight) {
-        // Rotate around Y axis (yaw) - opposite direction
-        let rotation = Quat::from_rotation_y(-CAMERA_ROTATE_SPEED * dt);
-        let view_direction = camera_settings.look_from - camera_settings.look_at;
-        let len = view_direction.length();
-        let view_direction = rotation.mul_vec3(view_direction).normalize();
-        camera_settings.look_from = camera_settings.look_at + view_direction * len;
+    if keyboard_input.pressed(KeyCode::PageDown) {
+        pitch -= CAMERA_ROTATE_SPEED * dt;
+    }
+    if yaw != 0.0 || pitch != 0.0 {
+        match camera_settings.mode {
+            CameraMode::Fly => camera_settings.look_around(yaw, pitch),
+            CameraMode::Orbit => camera_settings.orbit(yaw, pitch),
+        }
         moved = true;
     }
 
-    // Handle looking up/down (PageUp/PageDown)
-    if keyboard_input.pressed(KeyCode::Digit1) {
-        // Get right vector (perpendicular to view direction and world up)
-        let view_direction = camera_settings.look_from - camera_settings.look_at;
-        let len = view_direction.length();
-        let forward = view_direction.normalize();
-        let right = forward.cross(Vec3::Y).normalize();
+    // Update the reset flag if movement occurred. It is cleared again in extract_camera
+    // once the reset has been handed to the render world.
+    if moved {
+        camera_settings.camera_has_moved = true;
+    }
+}
 
-        // Create rotation around the right vector (pitch up)
-        let rotation = Quat::from_axis_angle(right, CAMERA_ROTATE_SPEED * dt);
-        let new_direction = rotation.mul_vec3(forward).normalize();
+// The camera the app started with: the launch camera or the scene's, with the plugin's
+// overrides (see RayTracingPluginBuilder::fov) applied. Home returns to it.
+#[derive(Resource, Debug, Clone)]
+pub struct StartingView(pub CameraSettings);
 
-        // Prevent camera from flipping by checking if the new direction is not too close to up/down
-        if new_direction.dot(Vec3::Y).abs() < 0.95 {
-            camera_settings.look_from = camera_settings.look_at + new_direction * len;
-            moved = true;
-        }
+/// System to record the starting view once the camera overrides have been applied
+pub fn record_starting_view(mut commands: Commands, camera_settings: Res<CameraSettings>) {
+    commands.insert_resource(StartingView(camera_settings.clone()));
+}
+
+/// System to snap the camera back to its starting view with Home: the position, look-at
+/// point, field of view and defocus it had after startup, see StartingView. Render
+/// settings such as the sample count are kept.
+pub fn reset_camera_view_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    starting_view: Res<StartingView>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Home) {
+        return;
     }
-    if keyboard_input.pressed(KeyCode::Digit2) {
-        // Get right vector (perpendicular to view direction and world up)
-        let view_direction = camera_settings.look_from - camera_settings.look_at;
-        let len = view_direction.length();
-        let forward = view_direction.normalize();
-        let right = forward.cross(Vec3::Y).normalize();
 
-        // Create rotation around the right vector (pitch down)
-        let rotation = Quat::from_axis_angle(right, -CAMERA_ROTATE_SPEED * dt);
-        let new_direction = rotation.mul_vec3(forward).normalize();
+    let start = &starting_view.0;
+    camera_settings.look_from = start.look_from;
+    camera_settings.look_at = start.look_at;
+    camera_settings.vup = start.vup;
+    camera_settings.projection = start.projection;
+    camera_settings.target_fov = None;
+    camera_settings.defocus_angle = start.defocus_angle;
+    camera_settings.focus_distance = start.focus_distance;
+    camera_settings.camera_has_moved = true;
+    info!("Camera reset to the starting view");
+}
 
-        // Prevent camera from flipping by checking if the new direction is not too close to up/down
-        if new_direction.dot(Vec3::Y).abs() < 0.95 {
-            camera_settings.look_from = camera_settings.look_at + new_direction * len;
-            moved = true;
-        }
+/// System to restart accumulation with Delete, without moving the camera. Most settings
+/// already restart it when they change; this is for those that don't, or to watch the
+/// image converge again. The shader drops every pixel's samples in the next frame.
+pub fn restart_accumulation_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Delete) {
+        camera_settings.camera_has_moved = true;
+        info!("Accumulation restarted");
     }
+}
 
-    // Update the reset flag if movement occurred
-    if moved {
-        camera_settings.camera_has_moved = true;
-    } else {
-        // Reset the flag if no movement this frame and it was previously set
-        if camera_settings.camera_has_moved {
-            camera_settings.camera_has_moved = false;
+// Stick deflection below this is treated as zero, so worn sticks don't drift the camera
+const GAMEPAD_DEAD_ZONE: f32 = 0.1;
+
+// Rescale a stick so it is zero inside the dead zone and reaches full speed at the edge
+fn apply_dead_zone(stick: Vec2) -> Vec2 {
+    let length = stick.length();
+    if length <= GAMEPAD_DEAD_ZONE {
+        return Vec2::ZERO;
+    }
+    stick / length * ((length.min(1.0) - GAMEPAD_DEAD_ZONE) / (1.0 - GAMEPAD_DEAD_ZONE))
+}
+
+/// System to move the camera with a gamepad: the left stick moves and strafes like W/A/S/D,
+/// the right stick turns and looks up/down like the arrows and PageUp/PageDown, and the
+/// right/left triggers move up/down. In orbit mode the left stick dollies and orbits.
+pub fn gamepad_camera_system(
+    gamepads: Query<&Gamepad>,
+    time: Res<Time>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    let dt = time.delta_secs();
+    for gamepad in &gamepads {
+        let movement = apply_dead_zone(gamepad.left_stick());
+        let look = apply_dead_zone(gamepad.right_stick());
+        let trigger = |button| {
+            let value = gamepad.get(button).unwrap_or(0.0);
+            if value > GAMEPAD_DEAD_ZONE {
+                value
+            } else {
+                0.0
+            }
+        };
+        let vertical = trigger(GamepadButton::RightTrigger2) - trigger(GamepadButton::LeftTrigger2);
+        if movement == Vec2::ZERO && look == Vec2::ZERO && vertical == 0.0 {
+            continue;
         }
+
+        let mut yaw = -look.x * CAMERA_ROTATE_SPEED * dt;
+        match camera_settings.mode {
+            CameraMode::Fly => {
+                let view_direction = camera_settings.look_from - camera_settings.look_at;
+                let forward = view_direction.normalize();
+                let right = forward.cross(Vec3::Y).normalize();
+                camera_settings.look_from +=
+                    (forward * movement.y + right * movement.x) * CAMERA_MOVE_SPEED * dt
+                        + Vec3::Y * vertical * CAMERA_VERTICAL_SPEED * dt;
+            }
+            // The left stick dollies and orbits like W/A/S/D
+            CameraMode::Orbit => {
+                camera_settings.dolly((-movement.y * ORBIT_DOLLY_SPEED * dt).exp());
+                yaw -= movement.x * CAMERA_ROTATE_SPEED * dt;
+            }
+        }
+
+        // The right stick turns the view, or orbits look_at in orbit mode
+        let pitch = look.y * CAMERA_ROTATE_SPEED * dt;
+        match camera_settings.mode {
+            CameraMode::Fly => camera_settings.look_around(yaw, pitch),
+            CameraMode::Orbit => camera_settings.orbit(yaw, pitch),
+        }
+
+        camera_settings.camera_has_moved = true;
     }
 }
 
-// GPU-compatible camera representation that matches shader's expectations
+// GPU-compatible camera representation that matches shader's expectations. This is the
+// only camera the render world sees: it is extracted by `ExtractResourcePlugin` in lib.rs
+// and bound at group 1 as `SceneCamera` in compute_shader.wgsl, so the two layouts must
+// be changed together.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Resource, ExtractResource, ShaderType, Pod, Zeroable)]
 pub struct SceneCamera {
@@ -269,10 +692,11 @@ pub struct SceneCamera {
     pub aspect_ratio: f32,
 
     pub defocus_disk_u: Vec3,
-    pub _padding0: f32,
+    // Pixel copied into the probe buffer for readback
+    pub probe_x: f32,
 
     pub viewport_u: Vec3,
-    pub _padding1: f32,
+    pub probe_y: f32,
 
     pub defocus_disk_v: Vec3,
     pub max_depth: f32,
@@ -288,18 +712,67 @@ pub struct SceneCamera {
 
     pub viewport_v: Vec3,
     pub defocus_radius: f32,
+
+    // Previous frame's viewport, used to reproject history for temporal denoising
+    pub prev_center: Vec3,
+    pub temporal_enabled: f32,
+
+    pub prev_viewport_upper_left: Vec3,
+    pub temporal_blend: f32,
+
+    pub prev_pixel_delta_u: Vec3,
+    pub temporal_depth_tolerance: f32,
+
+    pub prev_pixel_delta_v: Vec3,
+    pub temporal_normal_threshold: f32,
+
+    pub edge_antialiasing: f32,
+    pub light_samples: f32,
+    // 1 for parallel rays (LensProjection::Orthographic), 0 for perspective
+    pub orthographic: f32,
+    // 1 to stratify pixel samples, see CameraSettings::stratified_sampling
+    pub stratified: f32,
+
+    // See CameraSettings::min_bounces_before_rr
+    pub min_bounces_before_rr: f32,
+    // See CameraSettings::variance_threshold
+    pub variance_threshold: f32,
+    // See CameraSettings::shutter_open and shutter_close
+    pub shutter_open: f32,
+    pub shutter_close: f32,
+
+    // 1 to sample lights at diffuse bounces, see CameraSettings::next_event_estimation
+    pub next_event_estimation: f32,
+    // 1 for cosine-weighted diffuse bounces, see CameraSettings::cosine_weighted_diffuse
+    pub cosine_weighted: f32,
+    // See CameraSettings::samples_per_frame
+    pub samples_per_frame: f32,
+    pub _padding1: f32,
+
+    // Pixels that trace rays this frame, from tile_min up to but not including tile_max;
+    // see TiledRendering
+    pub tile_min: Vec2,
+    pub tile_max: Vec2,
+
+    // See CameraSettings::clear_color
+    pub clear_color: Vec3,
+    pub _padding2: f32,
 }
 
-impl From<&CameraSettings> for SceneCamera {
-    fn from(settings: &CameraSettings) -> Self {
+impl SceneCamera {
+    // GPU camera for a render target of `size` pixels
+    pub fn new(settings: &CameraSettings, size: UVec2) -> Self {
         let camera = settings;
-        let aspect_ratio = crate::SIZE.0 as f32 / crate::SIZE.1 as f32;
+        let aspect_ratio = size.x as f32 / size.y as f32;
 
         let camera_center = camera.look_from;
         
-        let theta = f32::to_radians(camera.field_of_view);
-        let h = f32::tan(theta / 2.0);
-        let viewport_height = 2.0 * h * camera.focus_distance;
+        let viewport_height = match camera.projection {
+            LensProjection::Perspective { fov } => {
+                2.0 * f32::tan(f32::to_radians(fov) / 2.0) * camera.focus_distance
+            }
+            LensProjection::Orthographic { height } => height,
+        };
         let viewport_width = viewport_height * aspect_ratio;
 
         // Calculate viewport vectors
@@ -312,8 +785,8 @@ impl From<&CameraSettings> for SceneCamera {
         let viewport_v = -viewport_height * v; // Negative to flip y-axis
 
         // Calculate pixel deltas
-        let pixel_delta_u = viewport_u / crate::SIZE.0 as f32;
-        let pixel_delta_v = viewport_v / crate::SIZE.1 as f32;
+        let pixel_delta_u = viewport_u / size.x as f32;
+        let pixel_delta_v = viewport_v / size.y as f32;
 
         // Calculate viewport upper left corner
         let viewport_upper_left =
@@ -340,10 +813,147 @@ impl From<&CameraSettings> for SceneCamera {
             viewport_u,
             viewport_v,
             defocus_radius,
-            max_depth: camera.max_depth as f32,
+            max_depth: camera.max_depth.max(1) as f32,
             samples_per_pixel: camera.samples_per_pixel as f32,
+            samples_per_frame: camera.samples_per_frame.max(1) as f32,
             camera_has_moved: if camera.camera_has_moved { 1.0 } else { 0.0 },
-            random_seed: rand::random(),
+            random_seed: match camera.deterministic_seed {
+                Some(seed) => base_seed(seed),
+                None => rand::random(),
+            },
+            probe_x: -1.0,
+            probe_y: -1.0,
+            prev_center: camera.look_from,
+            temporal_enabled: 0.0,
+            prev_viewport_upper_left: viewport_upper_left,
+            temporal_blend: 1.0,
+            prev_pixel_delta_u: pixel_delta_u,
+            temporal_depth_tolerance: 0.0,
+            prev_pixel_delta_v: pixel_delta_v,
+            temporal_normal_threshold: 1.0,
+            edge_antialiasing: if camera.edge_antialiasing { 1.0 } else { 0.0 },
+            light_samples: camera.light_samples as f32,
+            orthographic: match camera.projection {
+                LensProjection::Perspective { .. } => 0.0,
+                LensProjection::Orthographic { .. } => 1.0,
+            },
+            stratified: if camera.stratified_sampling { 1.0 } else { 0.0 },
+            min_bounces_before_rr: camera.min_bounces_before_rr as f32,
+            variance_threshold: camera.variance_threshold,
+            shutter_open: camera.shutter_open.clamp(0.0, 1.0),
+            shutter_close: camera.shutter_close.clamp(0.0, 1.0),
+            next_event_estimation: if camera.next_event_estimation {
+                1.0
+            } else {
+                0.0
+            },
+            cosine_weighted: if camera.cosine_weighted_diffuse {
+                1.0
+            } else {
+                0.0
+            },
+            _padding1: 0.0,
+            tile_min: Vec2::ZERO,
+            tile_max: size.as_vec2(),
+            clear_color: camera.clear_color,
+            _padding2: 0.0,
+        }
+    }
+
+    // The camera with its per-frame values cleared, which stays the same for as long as
+    // the view does, so its uniform only has to be written when the view changes
+    pub fn without_frame_constants(&self) -> Self {
+        Self {
+            random_seed: 0.0,
+            camera_has_moved: 0.0,
+            ..*self
+        }
+    }
+
+    // Pixels the update pass traces this frame, see tile_min
+    pub fn tile(&self) -> URect {
+        URect::from_corners(self.tile_min.as_uvec2(), self.tile_max.as_uvec2())
+    }
+
+    // Center of the viewport, which lies on the focus plane
+    fn viewport_center(&self) -> Vec3 {
+        self.viewport_upper_left + (self.viewport_u + self.viewport_v) / 2.0
+    }
+
+    // Unit vector pointing from the camera into the scene
+    pub fn forward(&self) -> Vec3 {
+        (self.viewport_center() - self.center).normalize()
+    }
+
+    // Distance of a point in front of the camera, measured along the view direction
+    pub fn depth_of(&self, point: Vec3) -> f32 {
+        (point - self.center).dot(self.forward())
+    }
+
+    // Origin and unit direction of the ray through the center of a pixel, ignoring
+    // defocus blur
+    pub fn ray_through(&self, pixel: UVec2) -> (Vec3, Vec3) {
+        let pixel_center = self.viewport_upper_left
+            + (pixel.x as f32 + 0.5) * self.pixel_delta_u
+            + (pixel.y as f32 + 0.5) * self.pixel_delta_v;
+        if self.orthographic > 0.5 {
+            let offset = self.center - self.viewport_center();
+            (pixel_center + offset, -offset.normalize())
+        } else {
+            (self.center, (pixel_center - self.center).normalize())
+        }
+    }
+
+    // Project a world-space point to pixel coordinates on the render target, with (0, 0) at
+    // the upper left corner. Returns None for points on or behind the camera plane.
+    pub fn project_to_pixel(&self, point: Vec3) -> Option<Vec2> {
+        let depth = self.depth_of(point);
+        if depth <= 0.0 {
+            return None;
+        }
+
+        let focus_depth = self.depth_of(self.viewport_center());
+        let on_viewport = if self.orthographic > 0.5 {
+            point + self.forward() * (focus_depth - depth) - self.viewport_upper_left
+        } else {
+            self.center + (point - self.center) * (focus_depth / depth) - self.viewport_upper_left
+        };
+        Some(Vec2::new(
+            on_viewport.dot(self.pixel_delta_u) / self.pixel_delta_u.length_squared(),
+            on_viewport.dot(self.pixel_delta_v) / self.pixel_delta_v.length_squared(),
+        ))
+    }
+}
+
+// Random seed in 0..1 for CameraSettings::deterministic_seed. The shader draws each sample's
+// random numbers from it, the pixel and the pixel's sample count, which unlike the frames
+// counted here doesn't run ahead while the shader is still compiling. SplitMix64 spreads
+// neighboring seeds apart; the top 24 bits are kept since the seed travels as an f32.
+fn base_seed(seed: u64) -> f32 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+    (z >> 40) as f32 / (1u32 << 24) as f32
+}
+
+// The values of SceneCamera that change every frame. The shader reads them from push
+// constants where the GPU supports them, or from a small uniform of their own, instead of
+// from the camera uniform.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, ShaderType, Pod, Zeroable)]
+pub struct FrameConstants {
+    pub random_seed: f32,
+    pub camera_has_moved: f32,
+    pub _padding0: f32,
+    pub _padding1: f32,
+}
+
+impl FrameConstants {
+    pub fn new(camera: &SceneCamera) -> Self {
+        Self {
+            random_seed: camera.random_seed,
+            camera_has_moved: camera.camera_has_moved,
             _padding0: 0.0,
             _padding1: 0.0,
         }
@@ -351,10 +961,62 @@ impl From<&CameraSettings> for SceneCamera {
 }
 
 // Extract camera settings into the render world
-pub fn extract_camera(camera_settings: Res<CameraSettings>, mut commands: Commands) {
+pub fn extract_camera(
+    mut camera_settings: ResMut<CameraSettings>,
+    denoise_settings: Res<DenoiseSettings>,
+    (step_mode, tiles): (Res<StepMode>, Res<TiledRendering>),
+    probe: Res<PixelProbe>,
+    render_size: Res<crate::RenderSize>,
+    mut previous_camera: Local<Option<SceneCamera>>,
+    mut commands: Commands,
+) {
     // Convert CameraSettings to the GPU-compatible SceneCamera
-    let scene_camera = SceneCamera::from(camera_settings.as_ref());
+    let mut scene_camera = SceneCamera::new(camera_settings.as_ref(), **render_size);
+
+    // Hand the previous viewport to the shader so it can reproject last frame's pixels
+    if let Some(previous) = previous_camera.as_ref() {
+        scene_camera.prev_center = previous.center;
+        scene_camera.prev_viewport_upper_left = previous.viewport_upper_left;
+        scene_camera.prev_pixel_delta_u = previous.pixel_delta_u;
+        scene_camera.prev_pixel_delta_v = previous.pixel_delta_v;
+    }
+    if step_mode.should_dispatch() {
+        *previous_camera = Some(scene_camera);
+    }
+
+    // Reprojection assumes rays leave from a single point, so it is off for parallel rays
+    let temporal = denoise_settings.temporal && scene_camera.orthographic < 0.5;
+    scene_camera.temporal_enabled = if temporal { 1.0 } else { 0.0 };
+    scene_camera.temporal_blend = denoise_settings.temporal_blend;
+    scene_camera.temporal_depth_tolerance = denoise_settings.temporal_depth_tolerance;
+    scene_camera.temporal_normal_threshold = denoise_settings.temporal_normal_threshold;
+    scene_camera.samples_per_frame = camera_settings.samples_this_frame(&step_mode) as f32;
+    scene_camera.probe_x = probe.location.x as f32;
+    scene_camera.probe_y = probe.location.y as f32;
+    // A reset, which is every frame while the camera moves, renders the whole image, so
+    // tiles only take over once the view is still
+    let (tile_min, tile_max) = if camera_settings.camera_has_moved {
+        (UVec2::ZERO, **render_size)
+    } else {
+        tiles.bounds(**render_size)
+    };
+    scene_camera.tile_min = tile_min.as_vec2();
+    scene_camera.tile_max = tile_max.as_vec2();
 
     // Insert as a resource that will be extracted to the render world
     commands.insert_resource(scene_camera);
+
+    // The reset request has been consumed; any system can set it again to restart accumulation.
+    // In step mode it is kept until the next step actually runs the compute pass.
+    if step_mode.should_dispatch() {
+        if camera_settings.camera_has_moved {
+            camera_settings.accumulated_frames = 0;
+        } else if tiles.tile == 0 {
+            // Every tile of the last pass got its samples
+            camera_settings.accumulated_frames = camera_settings
+                .accumulated_frames
+                .saturating_add(camera_settings.samples_this_frame(&step_mode));
+        }
+        camera_settings.camera_has_moved = false;
+    }
 }
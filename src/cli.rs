@@ -0,0 +1,227 @@
+use std::{fmt, path::PathBuf};
+
+use crate::camera::FOV_MAX;
+
+pub const USAGE: &str = "\
+Usage: gpu_ray_tracing [OPTIONS]
+
+Modes (the default opens a window):
+  --cornell            Render the Cornell box instead of the startup scene
+  --headless <PNG>     Render without a window, save the image to <PNG> and exit
+  --bench              Time a fixed scene from a fixed camera and exit; takes no other
+                       options but --workgroup-size
+
+Options:
+  --scene <FILE>       Scene file to render, in either scene format
+  --samples <N>        Samples per pixel to accumulate
+  --fov <DEGREES>      Vertical field of view up to 120, switching to a perspective camera
+  --defocus <DEGREES>  Defocus blur angle; 0 keeps everything sharp
+  --focus <DISTANCE>   Distance to the plane in focus
+  --workgroup-size <N> Side of the compute shader's square workgroups, 8 by default
+  -h, --help           Print this help";
+
+// What the command line asks for, see parse_args
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Command {
+    #[default]
+    Window,
+    Cornell,
+    // Render to this PNG without a window
+    Headless(PathBuf),
+    Bench,
+}
+
+// Parsed command line. Camera options left out keep the scene's camera as it is.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CliArgs {
+    pub command: Command,
+    pub scene: Option<PathBuf>,
+    pub samples: Option<u32>,
+    pub fov: Option<f32>,
+    pub defocus_angle: Option<f32>,
+    pub focus_distance: Option<f32>,
+    pub workgroup_size: Option<u32>,
+}
+
+// Why the command line couldn't be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum CliError {
+    // -h or --help; not a mistake, but there is nothing to render
+    Help,
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue { flag: String, value: String },
+    // --bench with options it would ignore, or two modes at once
+    Conflict(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Help => write!(f, "help requested"),
+            CliError::UnknownFlag(flag) => write!(f, "unknown option `{flag}`"),
+            CliError::MissingValue(flag) => write!(f, "`{flag}` needs a value"),
+            CliError::InvalidValue { flag, value } => {
+                write!(f, "invalid value `{value}` for `{flag}`")
+            }
+            CliError::Conflict(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+// Parse the arguments after the program name
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<CliArgs, CliError> {
+    let mut parsed = CliArgs::default();
+    let mut args = args.into_iter();
+    while let Some(flag) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| CliError::MissingValue(flag.clone()))
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Err(CliError::Help),
+            "--cornell" => set_command(&mut parsed, Command::Cornell)?,
+            "--bench" => set_command(&mut parsed, Command::Bench)?,
+            "--headless" => {
+                let output = PathBuf::from(value()?);
+                set_command(&mut parsed, Command::Headless(output))?;
+            }
+            "--scene" => parsed.scene = Some(PathBuf::from(value()?)),
+            "--samples" => {
+                parsed.samples = Some(parse_value(&flag, value()?, |samples: &u32| *samples > 0)?)
+            }
+            "--fov" => {
+                parsed.fov = Some(parse_value(&flag, value()?, |fov: &f32| {
+                    *fov > 0.0 && *fov <= FOV_MAX
+                })?)
+            }
+            "--defocus" => {
+                parsed.defocus_angle = Some(parse_value(&flag, value()?, |angle: &f32| {
+                    *angle >= 0.0 && *angle < 180.0
+                })?)
+            }
+            "--focus" => {
+                parsed.focus_distance = Some(parse_value(&flag, value()?, |distance: &f32| {
+                    distance.is_finite() && *distance > 0.0
+                })?)
+            }
+            "--workgroup-size" => {
+                parsed.workgroup_size = Some(parse_value(&flag, value()?, |size: &u32| *size > 0)?)
+            }
+            _ => return Err(CliError::UnknownFlag(flag)),
+        }
+    }
+
+    let has_options = parsed.scene.is_some()
+        || parsed.samples.is_some()
+        || parsed.fov.is_some()
+        || parsed.defocus_angle.is_some()
+        || parsed.focus_distance.is_some();
+    if parsed.command == Command::Bench && has_options {
+        return Err(CliError::Conflict(
+            "`--bench` renders a fixed scene and camera and takes no options but `--workgroup-size`"
+                .to_string(),
+        ));
+    }
+    if parsed.command == Command::Cornell && parsed.scene.is_some() {
+        return Err(CliError::Conflict(
+            "`--cornell` and `--scene` both pick the scene".to_string(),
+        ));
+    }
+    Ok(parsed)
+}
+
+fn set_command(parsed: &mut CliArgs, command: Command) -> Result<(), CliError> {
+    if parsed.command != Command::Window {
+        return Err(CliError::Conflict(
+            "`--cornell`, `--headless` and `--bench` can't be combined".to_string(),
+        ));
+    }
+    parsed.command = command;
+    Ok(())
+}
+
+// Parse `value` as a T that passes `valid`
+fn parse_value<T: std::str::FromStr>(
+    flag: &str,
+    value: String,
+    valid: impl Fn(&T) -> bool,
+) -> Result<T, CliError> {
+    match value.parse() {
+        Ok(parsed) if valid(&parsed) => Ok(parsed),
+        _ => Err(CliError::InvalidValue {
+            flag: flag.to_string(),
+            value,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<CliArgs, CliError> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn unknown_flag() {
+        assert_eq!(
+            parse(&["--samples", "16", "--sample"]),
+            Err(CliError::UnknownFlag("--sample".to_string()))
+        );
+    }
+
+    #[test]
+    fn missing_value() {
+        assert_eq!(
+            parse(&["--headless"]),
+            Err(CliError::MissingValue("--headless".to_string()))
+        );
+    }
+
+    #[test]
+    fn invalid_value() {
+        assert_eq!(
+            parse(&["--samples", "many"]),
+            Err(CliError::InvalidValue {
+                flag: "--samples".to_string(),
+                value: "many".to_string(),
+            })
+        );
+        // Past what the mouse wheel zooms out to
+        assert_eq!(
+            parse(&["--fov", "150"]),
+            Err(CliError::InvalidValue {
+                flag: "--fov".to_string(),
+                value: "150".to_string(),
+            })
+        );
+        assert_eq!(parse(&["--fov", "120"]).unwrap().fov, Some(FOV_MAX));
+    }
+
+    #[test]
+    fn bench_with_options() {
+        assert!(matches!(
+            parse(&["--bench", "--samples", "64"]),
+            Err(CliError::Conflict(_))
+        ));
+        assert_eq!(parse(&["--bench"]).unwrap().command, Command::Bench);
+        assert_eq!(
+            parse(&["--bench", "--workgroup-size", "16"])
+                .unwrap()
+                .workgroup_size,
+            Some(16)
+        );
+    }
+
+    #[test]
+    fn cornell_with_scene() {
+        assert!(matches!(
+            parse(&["--cornell", "--scene", "scene.ron"]),
+            Err(CliError::Conflict(_))
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use bevy::{
+    prelude::*,
+    render::gpu_readback::{Readback, ReadbackComplete},
+};
+use std::path::PathBuf;
+
+use crate::{
+    camera::CameraSettings,
+    display::DisplayedImage,
+    output::{draw_number, readback_to_rgba32f, save_png, CaptureSettings},
+    step::StepMode,
+};
+
+// Size of one glyph pixel in the saved image
+const LABEL_SCALE: u32 = 6;
+
+// Settings for rendering a contact sheet of the current view at increasing sample counts
+#[derive(Resource, Debug, Clone)]
+pub struct ContactSheetSettings {
+    pub sample_counts: Vec<u32>,
+    pub path: PathBuf,
+}
+
+impl Default for ContactSheetSettings {
+    fn default() -> Self {
+        Self {
+            sample_counts: vec![1, 16, 64, 256, 1024],
+            path: PathBuf::from("contact_sheet.png"),
+        }
+    }
+}
+
+// Progress of a contact sheet that is currently being rendered
+#[derive(Resource)]
+pub struct ContactSheet {
+    // Index into the sample counts of the tile being rendered
+    index: usize,
+    // Whether the tile is being read back with the render held
+    reading: bool,
+    tiles: Vec<Vec<f32>>,
+    // Render size when the sheet was started; every tile must match it
+    size: UVec2,
+    // Settings restored once the sheet is done
+    previous_samples_per_pixel: u32,
+    previous_sample_budget: u32,
+    previous_samples_per_frame: u32,
+    previous_step_mode: bool,
+}
+
+/// System to render the view at each configured sample count and tile the results into one image
+pub fn contact_sheet_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    (settings, capture_settings, render_size): (
+        Res<ContactSheetSettings>,
+        Res<CaptureSettings>,
+        Res<crate::RenderSize>,
+    ),
+    sheet: Option<ResMut<ContactSheet>>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut step_mode: ResMut<StepMode>,
+    displayed: Res<DisplayedImage>,
+) {
+    let Some(mut sheet) = sheet else {
+        if keyboard_input.just_pressed(KeyCode::F10) && !settings.sample_counts.is_empty() {
+            info!(
+                "Rendering a contact sheet at {:?} samples",
+                settings.sample_counts
+            );
+            commands.insert_resource(ContactSheet {
+                index: 0,
+                reading: false,
+                tiles: Vec::new(),
+                size: **render_size,
+                previous_samples_per_pixel: camera_settings.samples_per_pixel,
+                previous_sample_budget: camera_settings.sample_budget,
+                previous_samples_per_frame: camera_settings.samples_per_frame,
+                previous_step_mode: step_mode.enabled,
+            });
+            // Accumulation would otherwise stop short of the largest count
+            let max_samples = settings.sample_counts.iter().copied().max().unwrap_or(1);
+            camera_settings.samples_per_pixel = camera_settings.samples_per_pixel.max(max_samples);
+            if camera_settings.sample_budget > 0 {
+                camera_settings.sample_budget = camera_settings.sample_budget.max(max_samples);
+            }
+            // One sample per frame, so each tile can be held at exactly its count
+            camera_settings.samples_per_frame = 1;
+            camera_settings.camera_has_moved = true;
+            step_mode.enabled = false;
+        }
+        return;
+    };
+
+    if sheet.size != **render_size {
+        // Tiles of different sizes can't be put side by side
+        warn!("Render size changed, abandoning the contact sheet");
+        camera_settings.samples_per_pixel = sheet.previous_samples_per_pixel;
+        camera_settings.sample_budget = sheet.previous_sample_budget;
+        camera_settings.samples_per_frame = sheet.previous_samples_per_frame;
+        step_mode.enabled = sheet.previous_step_mode;
+        commands.remove_resource::<ContactSheet>();
+        return;
+    }
+
+    if !sheet.reading {
+        // accumulated_frames counts the samples rendered before the last dispatched frame
+        if camera_settings.accumulated_frames + 1 < settings.sample_counts[sheet.index] {
+            return;
+        }
+        // Hold the render so the tile has exactly the requested number of samples
+        step_mode.enabled = true;
+        sheet.reading = true;
+        let UVec2 {
+            x: width,
+            y: height,
+        } = sheet.size;
+        commands
+            .spawn(Readback::texture(displayed.0.clone()))
+            .observe(
+                move |trigger: Trigger<ReadbackComplete>,
+                      mut commands: Commands,
+                      sheet: Option<ResMut<ContactSheet>>| {
+                    commands.entity(trigger.entity()).despawn();
+                    if let Some(mut sheet) = sheet {
+                        let tile = readback_to_rgba32f(&trigger.event().0, width, height);
+                        sheet.tiles.push(tile);
+                    }
+                },
+            );
+        return;
+    }
+
+    if sheet.tiles.len() <= sheet.index {
+        return;
+    }
+    sheet.reading = false;
+    sheet.index += 1;
+    step_mode.enabled = false;
+    if sheet.index < settings.sample_counts.len() {
+        return;
+    }
+
+    let UVec2 {
+        x: width,
+        y: height,
+    } = sheet.size;
+    let pixels = tile_horizontally(&sheet.tiles, &settings.sample_counts, width, height);
+    let sheet_width = width * sheet.tiles.len() as u32;
+    match save_png(
+        &settings.path,
+        &pixels,
+        sheet_width,
+        height,
+        &capture_settings,
+    ) {
+        Ok(()) => info!("Saved {}", settings.path.display()),
+        Err(err) => error!("Could not save {}: {err}", settings.path.display()),
+    }
+
+    camera_settings.samples_per_pixel = sheet.previous_samples_per_pixel;
+    camera_settings.sample_budget = sheet.previous_sample_budget;
+    camera_settings.samples_per_frame = sheet.previous_samples_per_frame;
+    step_mode.enabled = sheet.previous_step_mode;
+    commands.remove_resource::<ContactSheet>();
+}
+
+// Place the tiles side by side, labelling each with its sample count
+fn tile_horizontally(tiles: &[Vec<f32>], labels: &[u32], width: u32, height: u32) -> Vec<f32> {
+    let row_len = (width * 4) as usize;
+    let mut pixels = Vec::with_capacity(row_len * tiles.len() * height as usize);
+    for y in 0..height as usize {
+        for tile in tiles {
+            pixels.extend_from_slice(&tile[y * row_len..(y + 1) * row_len]);
+        }
+    }
+
+    let sheet_size = UVec2::new(width * tiles.len() as u32, height);
+    for (i, label) in labels.iter().take(tiles.len()).enumerate() {
+        let origin = UVec2::new(i as u32 * width + 2 * LABEL_SCALE, 2 * LABEL_SCALE);
+        draw_number(&mut pixels, sheet_size, origin, *label, LABEL_SCALE);
+    }
+    pixels
+}
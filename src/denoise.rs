@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+// Settings for denoising the progressive render
+#[derive(Resource, Debug, Clone)]
+pub struct DenoiseSettings {
+    // Blend each new frame with the reprojected history while the camera moves,
+    // instead of restarting accumulation from scratch
+    pub temporal: bool,
+    // Weight of the new frame in the exponential moving average
+    pub temporal_blend: f32,
+    // History is rejected when its depth differs by more than this fraction
+    pub temporal_depth_tolerance: f32,
+    // History is rejected when the normals' dot product is below this value
+    pub temporal_normal_threshold: f32,
+    // Smooth the image on screen with an edge-aware blur guided by the primary-hit normals
+    // and depths, so low-sample previews look cleaner. Captures keep the raw samples.
+    pub spatial: bool,
+    // How much noise the blur smooths over, above 0. The filter also tightens as pixels
+    // gather samples, so a converged image is left nearly untouched.
+    pub spatial_strength: f32,
+}
+
+impl Default for DenoiseSettings {
+    fn default() -> Self {
+        Self {
+            temporal: false,
+            temporal_blend: 0.2,
+            temporal_depth_tolerance: 0.05,
+            temporal_normal_threshold: 0.9,
+            spatial: false,
+            spatial_strength: 1.0,
+        }
+    }
+}
+
+/// System to toggle temporal denoising with the T key and the spatial denoiser with
+/// Shift+T
+pub fn toggle_denoise_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut denoise_settings: ResMut<DenoiseSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyT) {
+        return;
+    }
+
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        denoise_settings.spatial = !denoise_settings.spatial;
+        info!("Spatial denoising: {}", denoise_settings.spatial);
+    } else {
+        denoise_settings.temporal = !denoise_settings.temporal;
+        info!("Temporal denoising: {}", denoise_settings.temporal);
+    }
+}
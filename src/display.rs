@@ -0,0 +1,495 @@
+use bevy::{
+    color::palettes::css::YELLOW,
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+    sprite::Material2d,
+};
+
+use crate::{camera::CameraSettings, denoise::DenoiseSettings, probe::PixelProbe, step::StepMode};
+
+// Luminance the metered pixel is mapped to in auto exposure mode
+const MIDDLE_GRAY: f32 = 0.18;
+const EXPOSURE_RANGE: (f32, f32) = (-10.0, 10.0);
+// Manual exposure change per key press, in stops
+const EXPOSURE_STEP: f32 = 1.0 / 3.0;
+// Vignette strength change per key press
+const VIGNETTE_STEP: f32 = 0.1;
+
+const POST_PROCESS_SHADER_ASSET_PATH: &str = "post_process.wgsl";
+// Embedded copy used by release builds, like the compute shader
+pub const EMBEDDED_POST_PROCESS_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(23580134928735601);
+
+// How the accumulated image is shown on screen. Only affects the display; the
+// accumulated radiance and saved captures are left untouched.
+#[derive(Resource, Debug, Clone)]
+pub struct DisplaySettings {
+    // Exposure in stops; the displayed color is scaled by 2^exposure
+    pub exposure: f32,
+    // Meter the pixel under the crosshair and expose it to middle gray
+    pub auto_exposure: bool,
+    // Rate at which auto exposure approaches its target, per second
+    pub adaptation_speed: f32,
+    pub vignette: bool,
+    // How much the corners are darkened, from 0 (not at all) to 1 (black)
+    pub vignette_strength: f32,
+    // Distance from the center where darkening starts, relative to the half-diagonal
+    pub vignette_radius: f32,
+    // Show the image without post effects while held, for before/after comparisons
+    pub bypass: bool,
+    // Burn the accumulated sample count into the upper left corner, on screen and in
+    // captures
+    pub frame_count_label: bool,
+    // Show how many samples each pixel has instead of the image, from blue (few) to red
+    // (as many as any pixel can have so far), to see where adaptive sampling stops
+    pub sample_heatmap: bool,
+    pub channel: DisplayChannel,
+    // Distance shown as black in the depth channel
+    pub depth_range: f32,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            auto_exposure: false,
+            adaptation_speed: 3.0,
+            vignette: false,
+            vignette_strength: 0.5,
+            vignette_radius: 0.5,
+            bypass: false,
+            frame_count_label: false,
+            sample_heatmap: false,
+            channel: DisplayChannel::Color,
+            depth_range: 30.0,
+        }
+    }
+}
+
+// Curve that maps the exposed HDR color to the displayable range. The numbers match the
+// `tonemap` values in post_process.wgsl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TonemapMode {
+    // Clip everything above 1
+    #[default]
+    None = 0,
+    // c / (1 + c), rolls off highlights gently but desaturates them
+    Reinhard = 1,
+    // Narkowicz's fit of the ACES filmic curve, which keeps contrast in the midtones and
+    // compresses bright emitters without blowing them out
+    AcesFilmic = 2,
+    // 1 - exp(-c), like film exposed for the given time
+    Exposure = 3,
+}
+
+impl TonemapMode {
+    fn next(self) -> Self {
+        match self {
+            TonemapMode::None => TonemapMode::Reinhard,
+            TonemapMode::Reinhard => TonemapMode::AcesFilmic,
+            TonemapMode::AcesFilmic => TonemapMode::Exposure,
+            TonemapMode::Exposure => TonemapMode::None,
+        }
+    }
+}
+
+// Tonemapping applied by the post pass after exposure. Captures are not affected.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TonemapSettings {
+    pub mode: TonemapMode,
+}
+
+// Texture currently shown on screen, the one the compute pass wrote to most recently
+#[derive(Resource, Clone)]
+pub struct DisplayedImage(pub Handle<Image>);
+
+// G-buffer of the surfaces seen in DisplayedImage, for compositing and debugging. Both
+// textures are Rgba32Float and hold the latest sample of each pixel's primary hit.
+#[derive(Resource, Clone)]
+pub struct DisplayedGBuffer {
+    // Normal (xyz) and distance from the camera (w, -1 for the sky)
+    pub normal_depth: Handle<Image>,
+    // Albedo (xyz) and 1 (w), or 0 for the sky
+    pub albedo: Handle<Image>,
+}
+
+impl DisplayedGBuffer {
+    // The G-buffer written together with the color texture `displayed`
+    pub(crate) fn new(targets: &crate::ComputeShaderImages, displayed: &Handle<Image>) -> Self {
+        // Each color texture is written together with the normal and depth texture of the
+        // same letter; there is only one albedo texture
+        let normal_depth = if *displayed == targets.texture_a {
+            &targets.gbuffer_a
+        } else {
+            &targets.gbuffer_b
+        };
+        Self {
+            normal_depth: normal_depth.clone_weak(),
+            albedo: targets.albedo.clone_weak(),
+        }
+    }
+}
+
+// What the display shows. The G-buffer channels skip exposure and post effects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisplayChannel {
+    // The rendered image
+    #[default]
+    Color = 0,
+    // Distance to the primary hit, white up close fading to black at
+    // DisplaySettings::depth_range and beyond
+    Depth = 1,
+    // Primary-hit normals mapped from -1..1 to 0..1 per axis
+    Normals = 2,
+    // Albedo of the primary hit
+    Albedo = 3,
+}
+
+impl DisplayChannel {
+    fn next(self) -> Self {
+        match self {
+            DisplayChannel::Color => DisplayChannel::Depth,
+            DisplayChannel::Depth => DisplayChannel::Normals,
+            DisplayChannel::Normals => DisplayChannel::Albedo,
+            DisplayChannel::Albedo => DisplayChannel::Color,
+        }
+    }
+}
+
+// Display parameters as laid out in post_process.wgsl
+#[derive(Debug, Clone, Copy, Default, ShaderType)]
+pub struct GpuDisplay {
+    pub exposure_scale: f32,
+    pub vignette_strength: f32,
+    pub vignette_radius: f32,
+    pub frame_count_label: f32,
+    pub tonemap: u32,
+    // Sample count shown as the hottest color of the heatmap, 0 to show the image
+    pub heatmap_samples: f32,
+    // See DenoiseSettings::spatial_strength; 0 shows the image unfiltered
+    pub denoise_strength: f32,
+    // DisplayChannel to show
+    pub channel: u32,
+    pub depth_range: f32,
+    // Sample count the frame count label shows, see CameraSettings::samples_shown
+    pub label_samples: f32,
+    pub _padding1: f32,
+    pub _padding2: f32,
+}
+
+impl GpuDisplay {
+    pub fn new(
+        settings: &DisplaySettings,
+        tonemap: &TonemapSettings,
+        denoise: &DenoiseSettings,
+    ) -> Self {
+        let frame_count_label = if settings.frame_count_label { 1.0 } else { 0.0 };
+        let channel = settings.channel as u32;
+        let depth_range = settings.depth_range.max(1e-3);
+        if settings.bypass {
+            return Self {
+                exposure_scale: 1.0,
+                frame_count_label,
+                channel,
+                depth_range,
+                ..default()
+            };
+        }
+        Self {
+            exposure_scale: settings.exposure.exp2(),
+            vignette_strength: if settings.vignette {
+                settings.vignette_strength
+            } else {
+                0.0
+            },
+            vignette_radius: settings.vignette_radius,
+            frame_count_label,
+            tonemap: tonemap.mode as u32,
+            denoise_strength: if denoise.spatial {
+                denoise.spatial_strength.max(0.0)
+            } else {
+                0.0
+            },
+            channel,
+            depth_range,
+            ..default()
+        }
+    }
+}
+
+// Draws the accumulated image to the screen, applying the denoiser, exposure and the
+// vignette
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct PostProcessMaterial {
+    #[uniform(0)]
+    pub display: GpuDisplay,
+    // Rgba32Float can't be filtered, so the shader loads texels directly
+    #[texture(1, filterable = false)]
+    pub source: Handle<Image>,
+    // Primary-hit normal (xyz) and depth (w) written along with `source`, guiding the
+    // denoiser
+    #[texture(2, filterable = false)]
+    pub gbuffer: Handle<Image>,
+    // Primary-hit albedo, for the albedo channel
+    #[texture(3, filterable = false)]
+    pub albedo: Handle<Image>,
+}
+
+impl Material2d for PostProcessMaterial {
+    fn fragment_shader() -> ShaderRef {
+        if cfg!(debug_assertions) {
+            POST_PROCESS_SHADER_ASSET_PATH.into()
+        } else {
+            EMBEDDED_POST_PROCESS_SHADER_HANDLE.into()
+        }
+    }
+}
+
+/// System to toggle auto exposure with E and adjust the exposure manually with - and =
+pub fn exposure_controls_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyE) {
+        settings.auto_exposure = !settings.auto_exposure;
+        info!("Auto exposure: {}", settings.auto_exposure);
+    }
+
+    let mut step = 0.0;
+    if keyboard_input.just_pressed(KeyCode::Minus) {
+        step -= EXPOSURE_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::Equal) {
+        step += EXPOSURE_STEP;
+    }
+    // Manual adjustments would be overridden straight away while metering
+    if step != 0.0 && !settings.auto_exposure {
+        settings.exposure = (settings.exposure + step).clamp(EXPOSURE_RANGE.0, EXPOSURE_RANGE.1);
+        info!("Exposure: {:+.2} EV", settings.exposure);
+    }
+}
+
+// Sweeps the exposure back and forth between two bounds so a good value can be picked by
+// eye; stopping the sweep keeps the exposure it was showing
+#[derive(Resource, Debug, Clone)]
+pub struct ExposureSweep {
+    pub active: bool,
+    // Lowest and highest exposure visited, in stops
+    pub range: (f32, f32),
+    // Seconds for a full sweep from the low bound to the high bound and back
+    pub period: f32,
+    // Position in the current sweep, from 0 to 1
+    phase: f32,
+}
+
+impl Default for ExposureSweep {
+    fn default() -> Self {
+        Self {
+            active: false,
+            range: (-4.0, 4.0),
+            period: 8.0,
+            phase: 0.0,
+        }
+    }
+}
+
+// Marks the text that shows the exposure during a sweep
+#[derive(Component)]
+pub struct ExposureLabel;
+
+pub fn setup_exposure_label(mut commands: Commands) {
+    commands.spawn((
+        Text::new(""),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+        Visibility::Hidden,
+        ExposureLabel,
+    ));
+}
+
+/// System to start an exposure sweep with Q and lock in the current exposure by pressing
+/// Q again
+pub fn exposure_sweep_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut sweep: ResMut<ExposureSweep>,
+    mut settings: ResMut<DisplaySettings>,
+    label: Single<(&mut Text, &mut Visibility), With<ExposureLabel>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        sweep.active = !sweep.active;
+        if sweep.active {
+            // Start from the current exposure, heading upwards
+            let (low, high) = sweep.range;
+            sweep.phase = ((settings.exposure - low) / (high - low)).clamp(0.0, 1.0) / 2.0;
+            settings.auto_exposure = false;
+        } else {
+            info!("Exposure locked at {:+.2} EV", settings.exposure);
+        }
+    }
+
+    let (mut text, mut visibility) = label.into_inner();
+    if !sweep.active {
+        if *visibility != Visibility::Hidden {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    }
+
+    sweep.phase = (sweep.phase + time.delta_secs() / sweep.period.max(0.1)).fract();
+    // Triangle wave: up during the first half, back down during the second
+    let t = 1.0 - (2.0 * sweep.phase - 1.0).abs();
+    let (low, high) = sweep.range;
+    settings.exposure = low + (high - low) * t;
+
+    text.0 = format!("EV {:+.2}  (Q to lock)", settings.exposure);
+    *visibility = Visibility::Visible;
+}
+
+/// System to ease the exposure towards the value that maps the probed pixel to middle gray
+pub fn auto_exposure_system(
+    time: Res<Time>,
+    probe: Res<PixelProbe>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    let luminance = probe.luminance();
+    // Nothing to meter yet, or the crosshair points at a black surface
+    if !settings.auto_exposure || !luminance.is_finite() || luminance <= 0.0 {
+        return;
+    }
+
+    let target = (MIDDLE_GRAY / luminance)
+        .log2()
+        .clamp(EXPOSURE_RANGE.0, EXPOSURE_RANGE.1);
+    let blend = 1.0 - (-time.delta_secs() * settings.adaptation_speed).exp();
+    settings.exposure += (target - settings.exposure) * blend;
+}
+
+/// System to toggle the vignette with V, adjust its strength with 9 and 0, and show the
+/// image without post effects while B is held
+pub fn vignette_controls_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        settings.vignette = !settings.vignette;
+        info!("Vignette: {}", settings.vignette);
+    }
+
+    let mut step = 0.0;
+    if keyboard_input.just_pressed(KeyCode::Digit9) {
+        step -= VIGNETTE_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::Digit0) {
+        step += VIGNETTE_STEP;
+    }
+    if step != 0.0 {
+        settings.vignette_strength = (settings.vignette_strength + step).clamp(0.0, 1.0);
+        info!("Vignette strength: {:.1}", settings.vignette_strength);
+    }
+
+    let bypass = keyboard_input.pressed(KeyCode::KeyB);
+    // Avoid triggering change detection every frame
+    if settings.bypass != bypass {
+        settings.bypass = bypass;
+    }
+}
+
+/// System to toggle the frame count label with F12
+pub fn toggle_frame_count_label_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F12) {
+        settings.frame_count_label = !settings.frame_count_label;
+        info!("Frame count label: {}", settings.frame_count_label);
+    }
+}
+
+/// System to toggle the sample count heatmap with F4
+pub fn toggle_sample_heatmap_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        settings.sample_heatmap = !settings.sample_heatmap;
+        info!("Sample count heatmap: {}", settings.sample_heatmap);
+    }
+}
+
+/// System to cycle the displayed channel between color, depth, normals and albedo with F7
+pub fn cycle_display_channel_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<DisplaySettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        settings.channel = settings.channel.next();
+        info!("Display channel: {:?}", settings.channel);
+    }
+}
+
+/// System to cycle the tonemapping operator with O
+pub fn cycle_tonemap_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut tonemap: ResMut<TonemapSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        tonemap.mode = tonemap.mode.next();
+        info!("Tonemapping: {:?}", tonemap.mode);
+    }
+}
+
+/// System to hand the display settings and the newest image to the post-process material
+pub fn update_post_process_system(
+    (settings, tonemap, denoise): (
+        Res<DisplaySettings>,
+        Res<TonemapSettings>,
+        Res<DenoiseSettings>,
+    ),
+    (camera_settings, step_mode): (Res<CameraSettings>, Res<StepMode>),
+    (displayed, gbuffer): (Res<DisplayedImage>, Res<DisplayedGBuffer>),
+    material: Single<&MeshMaterial2d<PostProcessMaterial>, Without<crate::views::ViewIndex>>,
+    mut materials: ResMut<Assets<PostProcessMaterial>>,
+) {
+    if !settings.is_changed()
+        && !tonemap.is_changed()
+        && !denoise.is_changed()
+        && !displayed.is_changed()
+    {
+        return;
+    }
+    let Some(material) = materials.get_mut(*material) else {
+        return;
+    };
+
+    material.display = GpuDisplay::new(&settings, &tonemap, &denoise);
+    let samples = camera_settings.samples_shown(&step_mode);
+    material.display.label_samples = samples as f32;
+    if settings.sample_heatmap {
+        material.display.heatmap_samples = samples.max(1) as f32;
+    }
+    material.source = displayed.0.clone();
+    material.gbuffer = gbuffer.normal_depth.clone();
+    material.albedo = gbuffer.albedo.clone();
+}
+
+/// System to draw a crosshair over the metered pixel while auto exposure is on
+pub fn draw_crosshair_system(
+    settings: Res<DisplaySettings>,
+    probe: Res<PixelProbe>,
+    render_size: Res<crate::RenderSize>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.auto_exposure {
+        return;
+    }
+
+    let center = crate::grid::pixel_to_sprite(probe.location.as_vec2() + 0.5, **render_size);
+    let arm = 8.0 * crate::DISPLAY_FACTOR as f32;
+    gizmos.line_2d(center - Vec2::X * arm, center + Vec2::X * arm, YELLOW);
+    gizmos.line_2d(center - Vec2::Y * arm, center + Vec2::Y * arm, YELLOW);
+}
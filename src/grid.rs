@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+
+use crate::camera::SceneCamera;
+
+// Reference grid drawn on the Y=0 plane on top of the traced image
+#[derive(Resource, Debug, Clone)]
+pub struct GridSettings {
+    pub enabled: bool,
+    // Distance between neighbouring grid lines
+    pub spacing: f32,
+    // The grid covers -extent..extent along X and Z
+    pub extent: f32,
+    pub color: Color,
+    pub axis_color: Color,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            spacing: 1.0,
+            extent: 10.0,
+            color: Color::srgba(1.0, 1.0, 1.0, 0.35),
+            axis_color: Color::srgba(1.0, 0.8, 0.2, 0.8),
+        }
+    }
+}
+
+// Segments are clipped to this distance in front of the camera before projecting
+const GRID_NEAR_DEPTH: f32 = 0.01;
+
+/// System to toggle the ground grid with G
+pub fn toggle_grid_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut grid_settings: ResMut<GridSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyG) {
+        grid_settings.enabled = !grid_settings.enabled;
+    }
+}
+
+/// System to draw the ground grid with gizmos, projected through the ray-traced camera
+pub fn draw_grid_system(
+    mut gizmos: Gizmos,
+    grid_settings: Res<GridSettings>,
+    scene_camera: Option<Res<SceneCamera>>,
+    render_size: Res<crate::RenderSize>,
+) {
+    let Some(scene_camera) = scene_camera else {
+        return;
+    };
+    if !grid_settings.enabled || grid_settings.spacing <= 0.0 {
+        return;
+    }
+
+    let extent = grid_settings.extent;
+    let lines = (extent / grid_settings.spacing).floor() as i32;
+    for i in -lines..=lines {
+        let offset = i as f32 * grid_settings.spacing;
+        let color = if i == 0 {
+            grid_settings.axis_color
+        } else {
+            grid_settings.color
+        };
+
+        let segments = [
+            (
+                Vec3::new(offset, 0.0, -extent),
+                Vec3::new(offset, 0.0, extent),
+            ),
+            (
+                Vec3::new(-extent, 0.0, offset),
+                Vec3::new(extent, 0.0, offset),
+            ),
+        ];
+        for (start, end) in segments {
+            if let Some((start, end)) = project_segment(&scene_camera, **render_size, start, end) {
+                gizmos.line_2d(start, end, color);
+            }
+        }
+    }
+}
+
+// Clip a world-space segment against the near plane and project it into sprite space
+fn project_segment(
+    camera: &SceneCamera,
+    size: UVec2,
+    start: Vec3,
+    end: Vec3,
+) -> Option<(Vec2, Vec2)> {
+    let (start_depth, end_depth) = (camera.depth_of(start), camera.depth_of(end));
+    if start_depth < GRID_NEAR_DEPTH && end_depth < GRID_NEAR_DEPTH {
+        return None;
+    }
+
+    let clip = |inside: Vec3, inside_depth: f32, outside: Vec3, outside_depth: f32| {
+        let t = (inside_depth - GRID_NEAR_DEPTH) / (inside_depth - outside_depth);
+        inside.lerp(outside, t)
+    };
+    let (start, end) = if start_depth < GRID_NEAR_DEPTH {
+        (clip(end, end_depth, start, start_depth), end)
+    } else if end_depth < GRID_NEAR_DEPTH {
+        (start, clip(start, start_depth, end, end_depth))
+    } else {
+        (start, end)
+    };
+
+    Some((
+        pixel_to_sprite(camera.project_to_pixel(start)?, size),
+        pixel_to_sprite(camera.project_to_pixel(end)?, size),
+    ))
+}
+
+// Convert pixel coordinates on a render target of `size` pixels to the 2D world space the
+// sprite is drawn in
+pub fn pixel_to_sprite(pixel: Vec2, size: UVec2) -> Vec2 {
+    let size = size.as_vec2();
+    Vec2::new(pixel.x - size.x / 2.0, size.y / 2.0 - pixel.y) * crate::DISPLAY_FACTOR as f32
+}
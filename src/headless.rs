@@ -0,0 +1,161 @@
+use bevy::{
+    prelude::*,
+    render::gpu_readback::{Readback, ReadbackComplete},
+};
+use std::path::PathBuf;
+
+use crate::{
+    camera::CameraSettings,
+    display::DisplayedImage,
+    output::{readback_to_rgba32f, save_png, CaptureSettings},
+    probe::PixelProbe,
+    step::StepMode,
+    tiles::TiledRendering,
+};
+
+// What to render without a window, see run_headless
+#[derive(Resource, Debug, Clone)]
+pub struct RenderConfig {
+    pub width: u32,
+    pub height: u32,
+    // Samples accumulated in every pixel before the image is saved
+    pub samples: u32,
+    pub output: PathBuf,
+    // Scene file rendered in place of the startup scene, in either scene format
+    pub scene: Option<PathBuf>,
+    // Side of the compute shader's workgroups, see WorkgroupSize
+    pub workgroup_size: u32,
+    // Base seed of the random numbers, so the same config renders the same image every
+    // time; see CameraSettings::deterministic_seed. None draws fresh seeds.
+    pub seed: Option<u64>,
+    // Side of the tiles rendered one per frame, for images too large to trace in one
+    // dispatch; see TiledRendering. 0 renders the whole image every frame.
+    pub tile_size: u32,
+    // Override the scene's camera, see RayTracingPluginBuilder::fov, defocus_angle and
+    // focus_distance
+    pub fov: Option<f32>,
+    pub defocus_angle: Option<f32>,
+    pub focus_distance: Option<f32>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            width: crate::SIZE.0,
+            height: crate::SIZE.1,
+            samples: 1024,
+            output: PathBuf::from("render.png"),
+            scene: None,
+            workgroup_size: *crate::WorkgroupSize::default(),
+            seed: None,
+            tile_size: 0,
+            fov: None,
+            defocus_angle: None,
+            focus_distance: None,
+        }
+    }
+}
+
+// Accumulate up to the requested sample count
+pub fn setup_headless(
+    config: Res<RenderConfig>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut tiles: ResMut<TiledRendering>,
+) {
+    camera_settings.samples_per_pixel = config.samples;
+    tiles.tile_size = config.tile_size;
+    camera_settings.deterministic_seed = config.seed;
+    // The render exits once it has its samples, and frames rendered while the shader was
+    // still compiling would count towards a budget
+    camera_settings.sample_budget = 0;
+    // The render waits for the probed pixel to reach `samples`, which a pixel that
+    // converged early never would
+    camera_settings.variance_threshold = 0.0;
+    info!(
+        "Rendering {}x{} at {} samples to {} with {}x{} workgroups",
+        config.width,
+        config.height,
+        config.samples,
+        config.output.display(),
+        config.workgroup_size,
+        config.workgroup_size
+    );
+}
+
+/// System to save the image once it has the requested number of samples and then exit
+pub fn headless_render_system(
+    mut commands: Commands,
+    (config, time): (Res<RenderConfig>, Res<Time<Real>>),
+    (probe, displayed): (Res<PixelProbe>, Res<DisplayedImage>),
+    mut step_mode: ResMut<StepMode>,
+    (render_size, tiles): (Res<crate::RenderSize>, Res<TiledRendering>),
+    // Time when the first samples came back, to time the frames from, and the frames
+    // rendered since
+    mut timing: Local<Option<(f32, u32)>>,
+    // Frames still to render once the probed pixel has its samples
+    mut frames_left: Local<Option<u32>>,
+) {
+    if probe.color.w > 0.0 {
+        match timing.as_mut() {
+            Some((_, frames)) => *frames += 1,
+            None => *timing = Some((time.elapsed_secs(), 0)),
+        }
+    }
+
+    // The probe's sample count comes back from the GPU, so unlike accumulated_frames it
+    // doesn't run ahead while the shader is still compiling. Step mode is only turned on
+    // below, once the readback has been started.
+    if step_mode.enabled || probe.color.w < config.samples as f32 {
+        return;
+    }
+    // With tiles, the pixels after the probed one may still be a pass behind. Pixels
+    // that have all their samples stop taking more, so another pass only catches them up.
+    let frames_left = frames_left.get_or_insert(tiles.count(**render_size) - 1);
+    if *frames_left > 0 {
+        *frames_left -= 1;
+        return;
+    }
+
+    // Counting from the first samples leaves out startup and shader compilation. A frame
+    // can add several samples, so the frames are counted rather than read off the probe.
+    if let Some((start, frames)) = *timing {
+        if frames > 0 {
+            let seconds = time.elapsed_secs() - start;
+            info!(
+                "Rendered {frames} frames in {seconds:.2}s, {:.3} ms per frame",
+                seconds * 1000.0 / frames as f32
+            );
+        }
+    }
+
+    // Hold the render while the image is read back
+    step_mode.enabled = true;
+    let UVec2 {
+        x: width,
+        y: height,
+    } = **render_size;
+    commands
+        .spawn(Readback::texture(displayed.0.clone()))
+        .observe(
+            move |trigger: Trigger<ReadbackComplete>,
+                  mut commands: Commands,
+                  config: Res<RenderConfig>,
+                  settings: Res<CaptureSettings>,
+                  mut exit: EventWriter<AppExit>| {
+                // A readback repeats every frame until its entity is gone, so only keep the first
+                commands.entity(trigger.entity()).despawn();
+
+                let pixels = readback_to_rgba32f(&trigger.event().0, width, height);
+                match save_png(&config.output, &pixels, width, height, &settings) {
+                    Ok(()) => {
+                        info!("Saved {}", config.output.display());
+                        exit.send(AppExit::Success);
+                    }
+                    Err(err) => {
+                        error!("Could not save {}: {err}", config.output.display());
+                        exit.send(AppExit::error());
+                    }
+                }
+            },
+        );
+}
@@ -0,0 +1,94 @@
+use bevy::{prelude::*, render::extract_resource::ExtractResource};
+
+use crate::camera::CameraSettings;
+
+// Upper bound for CameraSettings::light_samples
+const MAX_LIGHT_SAMPLES: u32 = 64;
+// Range CameraSettings::max_depth is adjusted in
+pub const MAX_DEPTH_RANGE: std::ops::RangeInclusive<u32> = 1..=100;
+
+// Which light transport the compute pass renders. Comparing the two modes shows how much
+// of the image comes from indirect bounces.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, ExtractResource)]
+pub enum IntegratorMode {
+    // Full path tracing, up to the camera's max depth
+    #[default]
+    Full,
+    // Emitters and the sky seen directly or after one bounce, with emissive spheres
+    // sampled explicitly from diffuse surfaces. Uses the DIRECT_ONLY shader def.
+    DirectOnly,
+}
+
+/// System to switch between full global illumination and direct lighting with I, and to
+/// turn next event estimation on or off with Shift+I
+pub fn toggle_integrator_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut mode: ResMut<IntegratorMode>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyI) {
+        return;
+    }
+
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        camera_settings.next_event_estimation = !camera_settings.next_event_estimation;
+        camera_settings.camera_has_moved = true;
+        info!(
+            "Next event estimation: {}",
+            camera_settings.next_event_estimation
+        );
+        return;
+    }
+
+    *mode = match *mode {
+        IntegratorMode::Full => IntegratorMode::DirectOnly,
+        IntegratorMode::DirectOnly => IntegratorMode::Full,
+    };
+    // Samples from the two modes must not be averaged together
+    camera_settings.camera_has_moved = true;
+    info!("Integrator: {:?}", *mode);
+}
+
+/// System to double the shadow rays per shading point with L, or halve them with Shift+L
+pub fn light_samples_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyL) {
+        return;
+    }
+
+    let current = camera_settings.light_samples.max(1);
+    let light_samples = if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        (current / 2).max(1)
+    } else {
+        (current * 2).min(MAX_LIGHT_SAMPLES)
+    };
+    if light_samples != camera_settings.light_samples {
+        camera_settings.light_samples = light_samples;
+        camera_settings.camera_has_moved = true;
+    }
+    info!("Light samples: {}", camera_settings.light_samples);
+}
+
+/// System to allow one more bounce per path with . (period) or one fewer with , (comma).
+/// Stepping down from a single bounce shows what each bounce adds to the image.
+pub fn max_depth_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    let mut max_depth = camera_settings.max_depth;
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        max_depth += 1;
+    }
+    if keyboard_input.just_pressed(KeyCode::Comma) {
+        max_depth = max_depth.saturating_sub(1);
+    }
+    let max_depth = max_depth.clamp(*MAX_DEPTH_RANGE.start(), *MAX_DEPTH_RANGE.end());
+
+    if max_depth != camera_settings.max_depth {
+        camera_settings.max_depth = max_depth;
+        camera_settings.camera_has_moved = true;
+        info!("Max depth: {max_depth}");
+    }
+}
@@ -1,69 +1,374 @@
 use bevy::{
+    app::{PluginGroupBuilder, ScheduleRunnerPlugin},
+    asset::load_internal_asset,
+    diagnostic::FrameTimeDiagnosticsPlugin,
+    ecs::system::SystemParam,
     prelude::*,
     render::{
         extract_resource::{ExtractResource, ExtractResourcePlugin},
         render_asset::{RenderAssetUsages, RenderAssets},
         render_graph::{self, RenderGraph, RenderLabel},
-        render_resource::{binding_types::texture_storage_2d, *},
-        renderer::{RenderContext, RenderDevice},
-        texture::GpuImage,
+        render_resource::{
+            binding_types::{storage_buffer, texture_storage_2d},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        storage::{GpuShaderStorageBuffer, ShaderStorageBuffer},
+        texture::{FallbackImage, GpuImage},
         Render, RenderApp, RenderSet,
     },
+    window::{ExitCondition, WindowResized},
+    winit::WinitPlugin,
 };
-use scene::sphere::SpheresPlugin;
-use std::borrow::Cow;
+use bytemuck::{Pod, Zeroable};
 
+pub use bench::BenchConfig;
+pub use camera::{CameraMode, CameraSettings, LensProjection};
+pub use display::{DisplayChannel, DisplayedGBuffer, DisplayedImage};
+pub use headless::RenderConfig;
+pub use plugin::{InputSet, RayTracingPlugin, RayTracingPluginBuilder};
+pub use scene::boxes::{BoxCollection, GpuBox};
+pub use scene::builder::{RayTracedScene, SceneBuilder};
+pub use scene::cornell::create_cornell_box;
+pub use scene::disk::{DiskCollection, GpuDisk};
+pub use scene::hash::scene_hash;
+pub use scene::instances::SphereInstances;
+pub use scene::loader::{
+    load_scene, load_scene_from_file, parse_ron_scene, parse_scene, write_scene, SceneLoadError,
+};
+pub use scene::mesh::{load_obj, MeshCollection};
+pub use scene::plane::{GpuPlane, PlaneCollection};
+pub use scene::quad::{GpuQuad, QuadCollection};
+pub use scene::sdf::{GpuSdfPrimitive, SdfCollection};
+pub use scene::sphere::{GpuMaterial, SphereCollection, SurfaceMaterial};
+pub use sky::SkySettings;
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+pub use sun::SunLight;
+pub use views::{RenderView, RenderViews};
+
+mod bench;
+mod bookmarks;
 mod camera;
+mod cli;
+mod contact_sheet;
+mod denoise;
+mod display;
+mod grid;
+mod headless;
+mod integrator;
+mod output;
+mod picking;
+mod plugin;
+mod probe;
+mod selection;
+mod sky;
+mod step;
+mod sun;
+mod tiles;
+mod turntable;
+#[cfg(feature = "ui")]
+mod ui;
+mod views;
 mod scene {
+    pub mod boxes;
+    pub mod builder;
+    pub mod bvh;
+    pub mod cornell;
+    pub mod disk;
+    pub mod hash;
+    pub mod instances;
+    pub mod lights;
+    pub mod loader;
+    pub mod mesh;
+    pub mod morph;
+    pub mod noise;
+    pub mod plane;
+    pub mod presets;
+    pub mod quad;
+    pub mod sdf;
     pub mod sphere;
+    pub mod watch;
 }
 
 /// This example uses a shader source file from the assets subdirectory
 const SHADER_ASSET_PATH: &str = "compute_shader.wgsl";
+// Copy of the shader compiled into the binary; release builds use it so they can run
+// without the assets folder, while debug builds load the file to get hot reloading
+const EMBEDDED_SHADER_HANDLE: Handle<Shader> = Handle::weak_from_u128(81627940515937152);
 
 const DISPLAY_FACTOR: u32 = 1;
+// Render size at startup; after that it follows the window, see RenderSize
 const SIZE: (u32, u32) = (1280 / DISPLAY_FACTOR, 720 / DISPLAY_FACTOR);
-const WORKGROUP_SIZE: u32 = 8;
+// Workgroup sizes examples/workgroup_benchmark.rs compares when not given any; see
+// WorkgroupSize. 32x32 would be 1024 threads, above the 256 per workgroup that wgpu allows
+// by default.
+pub const WORKGROUP_SIZES: [u32; 3] = [4, 8, 16];
 
 pub fn run() {
     App::new()
-        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins((windowed_plugins(), RayTracingPlugin::new()))
+        .run();
+}
+
+// Run as the command line in std::env::args asks, see cli::USAGE: with a window, headless
+// or as the benchmark, with the scene and camera overridden by the options given. Invalid
+// arguments print the usage and exit with an error.
+pub fn run_from_args() -> AppExit {
+    let args = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(cli::CliError::Help) => {
+            println!("{}", cli::USAGE);
+            return AppExit::Success;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{}", cli::USAGE);
+            return AppExit::error();
+        }
+    };
+
+    match args.command {
+        cli::Command::Bench => {
+            let defaults = BenchConfig::default();
+            return run_benchmark(BenchConfig {
+                workgroup_size: args.workgroup_size.unwrap_or(defaults.workgroup_size),
+                ..defaults
+            });
+        }
+        cli::Command::Headless(output) => {
+            let defaults = RenderConfig::default();
+            return run_headless(RenderConfig {
+                samples: args.samples.unwrap_or(defaults.samples),
+                output,
+                scene: args.scene,
+                workgroup_size: args.workgroup_size.unwrap_or(defaults.workgroup_size),
+                fov: args.fov,
+                defocus_angle: args.defocus_angle,
+                focus_distance: args.focus_distance,
+                ..defaults
+            });
+        }
+        cli::Command::Window | cli::Command::Cornell => {}
+    }
+
+    let mut app = App::new();
+    app.add_plugins(windowed_plugins());
+    let mut plugin = RayTracingPlugin::builder();
+    if args.command == cli::Command::Cornell {
+        plugin = plugin.scene(create_cornell_box());
+    }
+    // Loaded after Bevy's plugins are added so the error goes through Bevy's logger
+    if let Some(path) = &args.scene {
+        match load_scene_from_file(&path.to_string_lossy()) {
+            Ok(spheres) => plugin = plugin.scene(spheres),
+            Err(err) => {
+                error!("Could not load {}: {err}", path.display());
+                return AppExit::error();
+            }
+        }
+    }
+    if let Some(samples) = args.samples {
+        plugin = plugin.samples(samples);
+    }
+    if let Some(fov) = args.fov {
+        plugin = plugin.fov(fov);
+    }
+    if let Some(defocus_angle) = args.defocus_angle {
+        plugin = plugin.defocus_angle(defocus_angle);
+    }
+    if let Some(focus_distance) = args.focus_distance {
+        plugin = plugin.focus_distance(focus_distance);
+    }
+    if let Some(workgroup_size) = args.workgroup_size {
+        plugin = plugin.workgroup_size(workgroup_size);
+    }
+    app.add_plugins(plugin.build()).run()
+}
+
+// Run with a scene built in Rust instead of the startup scene file and default camera
+pub fn run_scene(scene: RayTracedScene) {
+    App::new()
         .add_plugins((
-            DefaultPlugins
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        resolution: (
-                            (SIZE.0 * DISPLAY_FACTOR) as f32,
-                            (SIZE.1 * DISPLAY_FACTOR) as f32,
-                        )
-                            .into(),
-                        // uncomment for unthrottled FPS
-                        // present_mode: bevy::window::PresentMode::AutoNoVsync,
-                        ..default()
-                    }),
-                    ..default()
-                })
-                .set(ImagePlugin::default_nearest()),
-            ComputeShaderComputePlugin,
-            SpheresPlugin,
+            windowed_plugins(),
+            RayTracingPlugin::builder().scene(scene).build(),
         ))
-        .add_systems(Startup, setup)
-        .add_systems(Update, switch_textures)
-        // Add camera movement systems
+        .run();
+}
+
+// Render a single image without opening a window and save it to `config.output`. Blocks
+// until the file has been written, then returns how the app exited: an error if the
+// scene file couldn't be loaded or the image couldn't be saved.
+pub fn run_headless(config: RenderConfig) -> AppExit {
+    let mut app = App::new();
+    app.add_plugins(headless_plugins());
+    let mut plugin = RayTracingPlugin::builder()
+        .resolution(config.width, config.height)
+        .workgroup_size(config.workgroup_size);
+    if let Some(fov) = config.fov {
+        plugin = plugin.fov(fov);
+    }
+    if let Some(defocus_angle) = config.defocus_angle {
+        plugin = plugin.defocus_angle(defocus_angle);
+    }
+    if let Some(focus_distance) = config.focus_distance {
+        plugin = plugin.focus_distance(focus_distance);
+    }
+
+    // Loaded after Bevy's plugins are added so the error goes through Bevy's logger
+    if let Some(path) = &config.scene {
+        match load_scene_from_file(&path.to_string_lossy()) {
+            Ok(spheres) => plugin = plugin.scene(spheres),
+            Err(err) => {
+                error!("Could not load {}: {err}", path.display());
+                return AppExit::error();
+            }
+        }
+    }
+
+    app.add_plugins(plugin.build())
+        .add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+        .insert_resource(config)
+        .add_systems(Startup, headless::setup_headless.after(setup))
         .add_systems(
             Update,
-            (camera::extract_camera, camera::camera_movement_system),
-        )
-        .run();
+            headless::headless_render_system.after(camera::extract_camera),
+        );
+    app.run()
+}
+
+// Render a fixed scene from a fixed camera without a window, time `config.frames` frames
+// of accumulation and print the rays per second along with the frame times, then exit
+pub fn run_benchmark(config: BenchConfig) -> AppExit {
+    let mut app = App::new();
+    app.add_plugins((
+        headless_plugins(),
+        RayTracingPlugin::builder()
+            .resolution(config.width, config.height)
+            .workgroup_size(config.workgroup_size)
+            .scene(bench::bench_scene())
+            .build(),
+    ));
+    // The `ui` feature already adds the frame time diagnostics
+    if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+        app.add_plugins(FrameTimeDiagnosticsPlugin);
+    }
+    app.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::ZERO))
+        .insert_resource(config)
+        .add_systems(Startup, bench::setup_benchmark.after(setup))
+        .add_systems(Update, bench::bench_system.after(camera::extract_camera));
+    app.run()
+}
+
+// Bevy's plugins with the primary window sized to the render
+fn windowed_plugins() -> PluginGroupBuilder {
+    default_plugins().set(WindowPlugin {
+        primary_window: Some(Window {
+            resolution: (
+                (SIZE.0 * DISPLAY_FACTOR) as f32,
+                (SIZE.1 * DISPLAY_FACTOR) as f32,
+            )
+                .into(),
+            // uncomment for unthrottled FPS
+            // present_mode: bevy::window::PresentMode::AutoNoVsync,
+            ..default()
+        }),
+        ..default()
+    })
+}
+
+fn headless_plugins() -> PluginGroupBuilder {
+    default_plugins()
+        .set(WindowPlugin {
+            primary_window: None,
+            // Nothing to close; the app exits once its work is done
+            exit_condition: ExitCondition::DontExit,
+            close_when_requested: false,
+        })
+        .disable::<WinitPlugin>()
 }
 
-fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+// Bevy's plugins configured the same way with or without a window
+fn default_plugins() -> PluginGroupBuilder {
+    DefaultPlugins
+        .set(ImagePlugin::default_nearest())
+        // Hot reload assets such as the compute shader in debug builds only
+        .set(AssetPlugin {
+            watch_for_changes_override: Some(cfg!(debug_assertions)),
+            ..default()
+        })
+}
+
+fn setup(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    scene: Option<Res<RayTracedScene>>,
+    render_size: Res<RenderSize>,
+) {
     // Initialize camera settings
-    commands.insert_resource(camera::CameraSettings::default());
+    let scene = scene.as_deref();
+    commands.insert_resource(scene.map(|scene| scene.camera.clone()).unwrap_or_default());
+    commands.insert_resource(denoise::DenoiseSettings::default());
+    commands.insert_resource(scene.map(|scene| scene.sky.clone()).unwrap_or_default());
+    commands.insert_resource(scene.map(|scene| scene.sun.clone()).unwrap_or_default());
+    commands.insert_resource(step::StepMode::default());
+    commands.insert_resource(tiles::TiledRendering::default());
+    commands.insert_resource(integrator::IntegratorMode::default());
+    commands.insert_resource(grid::GridSettings::default());
+    commands.insert_resource(turntable::TurntableSettings::default());
+    commands.insert_resource(contact_sheet::ContactSheetSettings::default());
+    commands.insert_resource(display::DisplaySettings::default());
+    commands.insert_resource(display::ExposureSweep::default());
+    commands.insert_resource(display::TonemapSettings::default());
+    commands.insert_resource(selection::SelectedSphere::default());
+    commands.insert_resource(output::CaptureSettings::default());
+
+    let targets = create_render_targets(&mut images, &mut buffers, **render_size);
+    commands.insert_resource(display::DisplayedImage(targets.texture_a.clone()));
+    commands.insert_resource(display::DisplayedGBuffer::new(&targets, &targets.texture_a));
+    commands.insert_resource(targets);
+}
+
+// Show the image in a sprite covering the window, through the post-processing material
+fn setup_display(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<display::PostProcessMaterial>>,
+    targets: Res<ComputeShaderImages>,
+    render_size: Res<RenderSize>,
+) {
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::from_size(render_size.as_vec2()))),
+        MeshMaterial2d(materials.add(display::PostProcessMaterial {
+            display: display::GpuDisplay::new(
+                &display::DisplaySettings::default(),
+                &display::TonemapSettings::default(),
+                &denoise::DenoiseSettings::default(),
+            ),
+            source: targets.texture_a.clone(),
+            gbuffer: targets.gbuffer_a.clone(),
+            albedo: targets.albedo.clone(),
+        })),
+        Transform::from_scale(Vec3::splat(DISPLAY_FACTOR as f32)),
+    ));
+    // Keep the UI in the main view when views are added next to it
+    commands.spawn((Camera2d, IsDefaultUiCamera));
+}
+
+// Accumulation, G-buffer and sphere ID textures and the luminance moments buffer for a
+// render of `size` pixels
+fn create_render_targets(
+    images: &mut Assets<Image>,
+    buffers: &mut Assets<ShaderStorageBuffer>,
+    size: UVec2,
+) -> ComputeShaderImages {
     let mut image = Image::new_fill(
         Extent3d {
-            width: SIZE.0,
-            height: SIZE.1,
+            width: size.x,
+            height: size.y,
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
@@ -71,33 +376,207 @@ fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
         TextureFormat::Rgba32Float,
         RenderAssetUsages::RENDER_WORLD,
     );
-    image.texture_descriptor.usage =
-        TextureUsages::COPY_DST | TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING;
+    image.texture_descriptor.usage = TextureUsages::COPY_DST
+        | TextureUsages::COPY_SRC
+        | TextureUsages::STORAGE_BINDING
+        | TextureUsages::TEXTURE_BINDING;
     let image0 = images.add(image.clone());
-    let image1 = images.add(image);
+    let image1 = images.add(image.clone());
+    // Primary-hit normal and depth, ping-ponged alongside the color textures
+    let gbuffer0 = images.add(image.clone());
+    let gbuffer1 = images.add(image.clone());
+    // Primary-hit albedo, only written by pixels that take a sample
+    let albedo = images.add(image);
 
-    commands.spawn((
-        Sprite {
-            image: image0.clone(),
-            custom_size: Some(Vec2::new(SIZE.0 as f32, SIZE.1 as f32)),
-            ..default()
+    // Index + 1 of the sphere seen through each pixel, 0 for the sky
+    let mut sphere_ids = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
         },
-        Transform::from_scale(Vec3::splat(DISPLAY_FACTOR as f32)),
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::R32Uint,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    sphere_ids.texture_descriptor.usage =
+        TextureUsages::COPY_DST | TextureUsages::COPY_SRC | TextureUsages::STORAGE_BINDING;
+    let sphere_ids = images.add(sphere_ids);
+
+    // Mean luminance and mean squared luminance of each pixel's samples, for adaptive
+    // sampling. Every pixel only touches its own entry, so one buffer is read and written
+    // in place instead of ping-ponging.
+    let moments = buffers.add(ShaderStorageBuffer::with_size(
+        (size.x * size.y) as usize * size_of::<Vec2>(),
+        RenderAssetUsages::RENDER_WORLD,
     ));
-    commands.spawn(Camera2d);
 
-    commands.insert_resource(ComputeShaderImages {
+    ComputeShaderImages {
         texture_a: image0,
         texture_b: image1,
-    });
+        gbuffer_a: gbuffer0,
+        gbuffer_b: gbuffer1,
+        albedo,
+        sphere_ids,
+        moments,
+    }
+}
+
+// Assets that render targets and the sprites showing them are made of
+#[derive(SystemParam)]
+struct RenderTargetAssets<'w> {
+    images: ResMut<'w, Assets<Image>>,
+    buffers: ResMut<'w, Assets<ShaderStorageBuffer>>,
+    meshes: ResMut<'w, Assets<Mesh>>,
+    materials: ResMut<'w, Assets<display::PostProcessMaterial>>,
+}
+
+// Recreate the render targets at the window's new size, so the image is rendered at the
+// new resolution instead of stretched, and restart accumulation. The old textures are
+// freed once nothing holds them.
+fn resize_render_targets(
+    mut resized: EventReader<WindowResized>,
+    mut render_size: ResMut<RenderSize>,
+    mut assets: RenderTargetAssets,
+    mut targets: ResMut<ComputeShaderImages>,
+    (mut displayed, mut gbuffer): (
+        ResMut<display::DisplayedImage>,
+        ResMut<display::DisplayedGBuffer>,
+    ),
+    sprite: Single<
+        &Mesh2d,
+        (
+            With<MeshMaterial2d<display::PostProcessMaterial>>,
+            Without<views::ViewIndex>,
+        ),
+    >,
+    (mut camera_settings, mut probe): (ResMut<camera::CameraSettings>, ResMut<probe::PixelProbe>),
+) {
+    let Some(event) = resized.read().last() else {
+        return;
+    };
+    // Minimized windows report a size of zero
+    let size = (Vec2::new(event.width, event.height) / DISPLAY_FACTOR as f32).as_uvec2();
+    if size.min_element() == 0 || size == **render_size {
+        return;
+    }
+
+    info!("Resizing the render targets to {}x{}", size.x, size.y);
+    *targets = create_render_targets(&mut assets.images, &mut assets.buffers, size);
+    displayed.0 = targets.texture_a.clone_weak();
+    *gbuffer = display::DisplayedGBuffer::new(&targets, &displayed.0);
+    assets
+        .meshes
+        .insert(&sprite.0, Rectangle::from_size(size.as_vec2()).into());
+    render_size.0 = size;
+    // Keep the probe inside the image
+    probe.location = size / 2;
+    camera_settings.camera_has_moved = true;
+}
+
+// Settings that Backspace restores along with the camera and the scene
+#[derive(SystemParam)]
+struct LaunchSettings<'w> {
+    sky: ResMut<'w, sky::SkySettings>,
+    sun: ResMut<'w, sun::SunLight>,
+    display: ResMut<'w, display::DisplaySettings>,
+    tonemap: ResMut<'w, display::TonemapSettings>,
+    denoise: ResMut<'w, denoise::DenoiseSettings>,
+    integrator: ResMut<'w, integrator::IntegratorMode>,
+}
+
+// Restore the camera, the scene and the sky, sun, display, denoise and integrator settings
+// to their launch state, or to the scene passed to run_scene, when Backspace is pressed
+fn reset_to_defaults(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut camera_settings: ResMut<camera::CameraSettings>,
+    (mut spheres, mut boxes, mut planes): (
+        ResMut<scene::sphere::SphereCollection>,
+        ResMut<scene::boxes::BoxCollection>,
+        ResMut<scene::plane::PlaneCollection>,
+    ),
+    (mut quads, mut disks, mut instances, mut sdfs): (
+        ResMut<scene::quad::QuadCollection>,
+        ResMut<scene::disk::DiskCollection>,
+        ResMut<scene::instances::SphereInstances>,
+        ResMut<scene::sdf::SdfCollection>,
+    ),
+    mut settings: LaunchSettings,
+    scene: Option<Res<RayTracedScene>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Backspace) {
+        return;
+    }
+
+    match scene {
+        Some(scene) => {
+            *camera_settings = scene.camera.clone();
+            *spheres = scene.spheres.clone();
+            *boxes = scene.boxes.clone();
+            *planes = scene.planes.clone();
+            *quads = scene.quads.clone();
+            *disks = scene.disks.clone();
+            *instances = scene.instances.clone();
+            *sdfs = scene.sdfs.clone();
+            *settings.sky = scene.sky.clone();
+            *settings.sun = scene.sun.clone();
+        }
+        None => {
+            *camera_settings = camera::CameraSettings::default();
+            (*spheres, *planes) = scene::sphere::load_startup_scene();
+            *boxes = scene::boxes::BoxCollection::default();
+            *quads = scene::quad::QuadCollection::default();
+            *disks = scene::disk::DiskCollection::default();
+            *instances = scene::instances::SphereInstances::default();
+            *sdfs = scene::sdf::SdfCollection::default();
+            *settings.sky = sky::SkySettings::default();
+            *settings.sun = sun::SunLight::default();
+        }
+    }
+    *settings.display = display::DisplaySettings::default();
+    *settings.tonemap = display::TonemapSettings::default();
+    *settings.denoise = denoise::DenoiseSettings::default();
+    *settings.integrator = integrator::IntegratorMode::default();
+    camera_settings.camera_has_moved = true;
 }
 
 // Switch texture to display every frame to show the one that was written to most recently.
-fn switch_textures(images: Res<ComputeShaderImages>, mut sprite: Single<&mut Sprite>) {
-    if sprite.image == images.texture_a {
-        sprite.image = images.texture_b.clone_weak();
+fn switch_textures(
+    images: Res<ComputeShaderImages>,
+    step_mode: Res<step::StepMode>,
+    shader_status: Res<ShaderStatus>,
+    mut displayed: ResMut<display::DisplayedImage>,
+    mut gbuffer: ResMut<display::DisplayedGBuffer>,
+) {
+    // Nothing new was written if the compute pass is held back by step mode, or by a
+    // shader that is being compiled again or doesn't compile; keep the last good frame on
+    // screen
+    if !step_mode.should_dispatch() || shader_status.is_paused() {
+        return;
+    }
+
+    if displayed.0 == images.texture_a {
+        displayed.0 = images.texture_b.clone_weak();
     } else {
-        sprite.image = images.texture_a.clone_weak();
+        displayed.0 = images.texture_a.clone_weak();
+    }
+    *gbuffer = display::DisplayedGBuffer::new(&images, &displayed.0);
+}
+
+// Restart accumulation when an edited shader has compiled, so samples from the old and
+// the new shader aren't averaged together. The node starts over from the init pass, which
+// clears the image.
+fn restart_after_shader_reload(
+    shader_status: Res<ShaderStatus>,
+    mut camera_settings: ResMut<camera::CameraSettings>,
+    mut seen_reloads: Local<u32>,
+) {
+    let reloads = shader_status.reloads();
+    if reloads != *seen_reloads {
+        info!("Reloaded assets/{SHADER_ASSET_PATH}");
+        camera_settings.camera_has_moved = true;
+        *seen_reloads = reloads;
     }
 }
 
@@ -108,11 +587,36 @@ struct ComputeShaderLabel;
 
 impl Plugin for ComputeShaderComputePlugin {
     fn build(&self, app: &mut App) {
+        if !cfg!(debug_assertions) {
+            load_internal_asset!(
+                app,
+                EMBEDDED_SHADER_HANDLE,
+                "../assets/compute_shader.wgsl",
+                Shader::from_wgsl
+            );
+            load_internal_asset!(
+                app,
+                display::EMBEDDED_POST_PROCESS_SHADER_HANDLE,
+                "../assets/post_process.wgsl",
+                Shader::from_wgsl
+            );
+        }
+
+        app.init_resource::<ShaderStatus>();
+
         // Extract the game of life image resource from the main world into the render world
-        // for operation on by the compute shader and display on the sprite.
+        // for operation on by the compute shader and display on screen.
         app.add_plugins((
             ExtractResourcePlugin::<ComputeShaderImages>::default(),
+            ExtractResourcePlugin::<RenderSize>::default(),
             ExtractResourcePlugin::<camera::SceneCamera>::default(),
+            ExtractResourcePlugin::<sky::SkySettings>::default(),
+            ExtractResourcePlugin::<sky::EnvironmentMap>::default(),
+            ExtractResourcePlugin::<sun::SunLight>::default(),
+            ExtractResourcePlugin::<step::StepMode>::default(),
+            ExtractResourcePlugin::<integrator::IntegratorMode>::default(),
+            ExtractResourcePlugin::<probe::PixelProbeBuffer>::default(),
+            ExtractResourcePlugin::<views::RenderViews>::default(),
         ));
         let render_app = app.sub_app_mut(RenderApp);
         render_app.add_systems(
@@ -121,6 +625,8 @@ impl Plugin for ComputeShaderComputePlugin {
                 prepare_bind_group.in_set(RenderSet::PrepareBindGroups),
                 prepare_camera_bind_group.in_set(RenderSet::PrepareBindGroups),
                 prepare_sphere_buffer.in_set(RenderSet::PrepareBindGroups),
+                prepare_sky_bind_group.in_set(RenderSet::PrepareBindGroups),
+                prepare_view_bind_groups.in_set(RenderSet::PrepareBindGroups),
             ),
         );
 
@@ -130,8 +636,13 @@ impl Plugin for ComputeShaderComputePlugin {
     }
 
     fn finish(&self, app: &mut App) {
+        let workgroup_size = *app.world().resource::<WorkgroupSize>();
+        let shader_status = app.world().resource::<ShaderStatus>().clone();
         let render_app = app.sub_app_mut(RenderApp);
-        render_app.init_resource::<ComputeShaderPipeline>();
+        render_app
+            .insert_resource(workgroup_size)
+            .insert_resource(shader_status)
+            .init_resource::<ComputeShaderPipeline>();
     }
 }
 
@@ -139,71 +650,610 @@ impl Plugin for ComputeShaderComputePlugin {
 struct ComputeShaderImages {
     texture_a: Handle<Image>,
     texture_b: Handle<Image>,
+    gbuffer_a: Handle<Image>,
+    gbuffer_b: Handle<Image>,
+    albedo: Handle<Image>,
+    sphere_ids: Handle<Image>,
+    moments: Handle<ShaderStorageBuffer>,
+}
+
+// Size of the render targets in pixels. It starts at SIZE, or at the resolution passed to
+// run_headless, and follows the window once it is resized.
+#[derive(Resource, Clone, Copy, Deref, ExtractResource)]
+struct RenderSize(UVec2);
+
+impl Default for RenderSize {
+    fn default() -> Self {
+        Self(UVec2::new(SIZE.0, SIZE.1))
+    }
+}
+
+// Side of the square workgroups the compute shader runs in, passed to it as the
+// WORKGROUP_SIZE shader def. It is read once when the pipelines are created, so it has to
+// be set before the app runs. Dispatches round up to cover sizes that aren't a multiple
+// of it; the shader skips threads past the edge of the image.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Deref)]
+pub struct WorkgroupSize(pub u32);
+
+impl Default for WorkgroupSize {
+    fn default() -> Self {
+        Self(8)
+    }
+}
+
+// Compile status of the compute shader, shared by the main and the render world and
+// updated by the node. In debug builds the shader is watched: saving it makes the
+// pipeline cache compile the pipelines again, with the same layouts, so the bind groups
+// stay valid across reloads.
+#[derive(Resource, Clone, Default)]
+pub struct ShaderStatus(Arc<Mutex<ShaderStatusState>>);
+
+#[derive(Default)]
+struct ShaderStatusState {
+    // Error from the last compile, until a fixed shader compiles
+    error: Option<String>,
+    // The pipelines are being compiled again, or failed to
+    paused: bool,
+    // Times an edited shader has compiled since startup
+    reloads: u32,
+}
+
+impl ShaderStatus {
+    pub fn error(&self) -> Option<String> {
+        self.0.lock().unwrap().error.clone()
+    }
+
+    // Whether the node has stopped dispatching until the shader compiles
+    pub fn is_paused(&self) -> bool {
+        self.0.lock().unwrap().paused
+    }
+
+    pub fn reloads(&self) -> u32 {
+        self.0.lock().unwrap().reloads
+    }
+
+    fn pause(&self, error: Option<String>) {
+        let mut state = self.0.lock().unwrap();
+        state.error = error;
+        state.paused = true;
+    }
+
+    fn reloaded(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.error = None;
+        state.paused = false;
+        state.reloads += 1;
+    }
 }
 
 #[derive(Resource)]
 struct ComputeShaderImageBindGroups([BindGroup; 2]);
+// What the node binds to render one view: its textures, one bind group per direction of
+// the ping-pong, its camera, and the frame constants to push
+struct ViewBindGroup {
+    images: [BindGroup; 2],
+    camera: BindGroup,
+    frame_constants: camera::FrameConstants,
+    // Pixels the update pass traces, see camera::SceneCamera::tile
+    tile: URect,
+}
+// Bind groups of the views rendered next to the main one, see views::RenderViews
+#[derive(Resource, Default)]
+struct ViewBindGroups(Vec<ViewBindGroup>);
+// Kept between frames, so the camera uniform is only written when the view changes
 #[derive(Resource)]
-struct CameraBindGroup(BindGroup);
+struct CameraBindGroup {
+    bind_group: BindGroup,
+    camera_buffer: Buffer,
+    // FrameConstants, for GPUs without push constants
+    frame_buffer: Buffer,
+    // What camera_buffer holds, see SceneCamera::without_frame_constants
+    uploaded: camera::SceneCamera,
+}
+// The sphere bind group and its buffers, in binding order, kept between frames
+#[derive(Resource)]
+struct SphereBindGroup {
+    bind_group: BindGroup,
+    buffers: Vec<Buffer>,
+}
+
+impl SphereBindGroup {
+    fn new(
+        render_device: &RenderDevice,
+        pipeline: &ComputeShaderPipeline,
+        buffers: Vec<Buffer>,
+    ) -> Self {
+        let entries: Vec<BindGroupEntry> = buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = render_device.create_bind_group(
+            Some("Sphere Bind Group"),
+            &pipeline.sphere_bind_group_layout,
+            &entries,
+        );
+        Self {
+            bind_group,
+            buffers,
+        }
+    }
+}
 #[derive(Resource)]
-struct SphereBindGroup(BindGroup);
+struct SkyBindGroup(BindGroup);
+
+impl CameraBindGroup {
+    fn new(
+        render_device: &RenderDevice,
+        pipeline: &ComputeShaderPipeline,
+        scene_camera: &camera::SceneCamera,
+    ) -> Self {
+        let uploaded = scene_camera.without_frame_constants();
+
+        // Create buffers with camera data
+        let camera_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("Camera Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uploaded),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let frame_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("Frame Constants Buffer"),
+            contents: bytemuck::bytes_of(&camera::FrameConstants::new(scene_camera)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        // Create bind group
+        let bind_group = render_device.create_bind_group(
+            Some("Camera Bind Group"),
+            &pipeline.camera_bind_group_layout,
+            &BindGroupEntries::sequential((
+                camera_buffer.as_entire_binding(),
+                frame_buffer.as_entire_binding(),
+            )),
+        );
+
+        Self {
+            bind_group,
+            camera_buffer,
+            frame_buffer,
+            uploaded,
+        }
+    }
+
+    // Write the camera to the buffers it was created with
+    fn update(
+        &mut self,
+        render_queue: &RenderQueue,
+        pipeline: &ComputeShaderPipeline,
+        scene_camera: &camera::SceneCamera,
+    ) {
+        let uploaded = scene_camera.without_frame_constants();
+        if self.uploaded != uploaded {
+            render_queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&uploaded));
+            self.uploaded = uploaded;
+        }
+        // With push constants the node hands them to the pass instead
+        if !pipeline.push_constants {
+            let frame_constants = camera::FrameConstants::new(scene_camera);
+            render_queue.write_buffer(&self.frame_buffer, 0, bytemuck::bytes_of(&frame_constants));
+        }
+    }
+}
 
 fn prepare_camera_bind_group(
     mut commands: Commands,
     pipeline: Res<ComputeShaderPipeline>,
     scene_camera: Res<camera::SceneCamera>,
+    camera_bind_group: Option<ResMut<CameraBindGroup>>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
 ) {
-    // Create buffer with camera data
-    let camera_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-        label: Some("Camera Uniform Buffer"),
-        contents: bytemuck::bytes_of(&*scene_camera),
+    match camera_bind_group {
+        Some(mut camera_bind_group) => {
+            camera_bind_group.update(&render_queue, &pipeline, &scene_camera);
+        }
+        None => {
+            let camera_bind_group = CameraBindGroup::new(&render_device, &pipeline, &scene_camera);
+            commands.insert_resource(camera_bind_group);
+        }
+    }
+}
+
+fn prepare_sky_bind_group(
+    mut commands: Commands,
+    pipeline: Res<ComputeShaderPipeline>,
+    (sky_settings, sun): (Res<sky::SkySettings>, Res<sun::SunLight>),
+    environment_map: Res<sky::EnvironmentMap>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    fallback_image: Res<FallbackImage>,
+    render_device: Res<RenderDevice>,
+) {
+    // Bind a placeholder until the environment map has been uploaded; the shader ignores
+    // it and draws the gradient
+    let environment = environment_map
+        .image
+        .as_ref()
+        .and_then(|image| gpu_images.get(image));
+    let sky_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("Sky Uniform Buffer"),
+        contents: bytemuck::bytes_of(&sky::GpuSky::new(&sky_settings, environment.is_some())),
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+    });
+    let sun_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("Sun Uniform Buffer"),
+        contents: bytemuck::bytes_of(&sun::GpuSun::new(&sun)),
         usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
     });
 
-    // Create bind group
     let bind_group = render_device.create_bind_group(
-        Some("Camera Bind Group"),
-        &pipeline.camera_bind_group_layout,
-        &[BindGroupEntry {
-            binding: 0,
-            resource: camera_buffer.as_entire_binding(),
-        }],
+        Some("Sky Bind Group"),
+        &pipeline.sky_bind_group_layout,
+        &BindGroupEntries::sequential((
+            sky_buffer.as_entire_binding(),
+            &environment.unwrap_or(&fallback_image.d2).texture_view,
+            &pipeline.environment_sampler,
+            sun_buffer.as_entire_binding(),
+        )),
     );
 
-    commands.insert_resource(CameraBindGroup(bind_group));
+    commands.insert_resource(SkyBindGroup(bind_group));
+}
+
+// Object count for a uniform buffer, padded to 16 bytes: a bare u32 is too small for
+// uniform buffers on some backends, such as DX12. Matches `Count` in compute_shader.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, ShaderType)]
+struct GpuCount {
+    value: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+impl GpuCount {
+    fn new(value: u32) -> Self {
+        Self {
+            value,
+            ..Self::zeroed()
+        }
+    }
+}
+
+// Whether the bytes bytemuck uploads for T are as long as the WGSL struct the bind group
+// layouts size it by. They differ when a field is added without the padding the shader's
+// alignment rules expect, which shifts every later field.
+const fn matches_shader_layout<T: ShaderSize>() -> bool {
+    std::mem::size_of::<T>() as u64 == T::SHADER_SIZE.get()
+}
+
+// Fail the build, rather than render a silently wrong image, if a type the compute shader
+// binds stops matching its WGSL layout
+const _: () = {
+    assert!(matches_shader_layout::<camera::SceneCamera>());
+    assert!(matches_shader_layout::<camera::FrameConstants>());
+    assert!(matches_shader_layout::<GpuCount>());
+    assert!(matches_shader_layout::<sky::GpuSky>());
+    assert!(matches_shader_layout::<sun::GpuSun>());
+    assert!(matches_shader_layout::<scene::sphere::GpuSphere>());
+    assert!(matches_shader_layout::<scene::sphere::GpuMaterial>());
+    assert!(matches_shader_layout::<scene::boxes::GpuBox>());
+    assert!(matches_shader_layout::<scene::mesh::GpuTriangle>());
+    assert!(matches_shader_layout::<scene::mesh::GpuTriangleNormals>());
+    assert!(matches_shader_layout::<scene::plane::GpuPlane>());
+    assert!(matches_shader_layout::<scene::quad::GpuQuad>());
+    assert!(matches_shader_layout::<scene::disk::GpuDisk>());
+    assert!(matches_shader_layout::<scene::instances::GpuInstanceGroup>());
+    assert!(matches_shader_layout::<scene::sdf::GpuSdfPrimitive>());
+    assert!(matches_shader_layout::<scene::sdf::GpuSdfBounds>());
+    assert!(matches_shader_layout::<scene::lights::GpuLight>());
+    assert!(matches_shader_layout::<scene::bvh::GpuBvhNode>());
+    assert!(matches_shader_layout::<scene::noise::GpuNoiseTable>());
+};
+
+// Scene data uploaded to the sphere bind group
+#[derive(SystemParam)]
+struct SceneGeometry<'w> {
+    spheres: Res<'w, scene::sphere::SphereCollection>,
+    boxes: Res<'w, scene::boxes::BoxCollection>,
+    mesh: Res<'w, scene::mesh::MeshCollection>,
+    planes: Res<'w, scene::plane::PlaneCollection>,
+    quads: Res<'w, scene::quad::QuadCollection>,
+    disks: Res<'w, scene::disk::DiskCollection>,
+    instances: Res<'w, scene::instances::SphereInstances>,
+    sdfs: Res<'w, scene::sdf::SdfCollection>,
+    bvh: Res<'w, scene::bvh::SphereBvh>,
+    noise: Res<'w, scene::noise::NoiseSettings>,
 }
 
 fn prepare_sphere_buffer(
     mut commands: Commands,
     pipeline: Res<ComputeShaderPipeline>,
-    spheres: Res<scene::sphere::SphereCollection>,
+    geometry: SceneGeometry,
+    sphere_bind_group: Option<ResMut<SphereBindGroup>>,
     render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
 ) {
-    // Create a buffer for the sphere data
-    let sphere_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-        label: Some("Sphere Buffer"),
-        contents: bytemuck::cast_slice(&spheres.spheres),
-        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-    });
+    let SceneGeometry {
+        spheres,
+        boxes,
+        mesh,
+        planes,
+        quads,
+        disks,
+        instances,
+        sdfs,
+        bvh,
+        noise,
+    } = geometry;
 
-    let count_buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
-        label: Some("Sphere Count Buffer"),
-        contents: bytemuck::cast_slice(&[spheres.count]),
-        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-    });
+    // Resources are only extracted again when they change, so a still scene uploads nothing
+    let any_changed = spheres.is_changed()
+        || boxes.is_changed()
+        || mesh.is_changed()
+        || planes.is_changed()
+        || quads.is_changed()
+        || disks.is_changed()
+        || instances.is_changed()
+        || sdfs.is_changed()
+        || bvh.is_changed()
+        || noise.is_changed();
+    if sphere_bind_group.is_some() && !any_changed {
+        return;
+    }
 
-    // Create a bind group for the sphere buffer
-    let sphere_bind_group = render_device.create_bind_group(
-        Some("Sphere Bind Group"),
-        &pipeline.sphere_bind_group_layout,
-        &BindGroupEntries::sequential((
-            count_buffer.as_entire_binding(),
-            sphere_buffer.as_entire_binding(),
-        )),
-    );
+    // Size the buffer for the spheres in use. Storage buffers can't be empty, so an empty
+    // scene uploads one sphere the shader never reads.
+    let count = (spheres.count as usize).min(spheres.spheres.len());
+    let placeholder = [scene::sphere::GpuSphere::zeroed()];
+    let uploaded = match count {
+        0 => &placeholder[..],
+        _ => &spheres.spheres[..count],
+    };
+
+    // Likewise for the materials the spheres refer to
+    let material_placeholder = [scene::sphere::GpuMaterial::zeroed()];
+    let materials = match spheres.materials.len() {
+        0 => &material_placeholder[..],
+        _ => &spheres.materials[..],
+    };
+
+    // Emitters for the shader to sample directly, with a placeholder as above
+    let lights = scene::lights::collect_lights(&spheres, &quads, &disks);
+    let light_placeholder = [scene::lights::GpuLight::zeroed()];
+    let uploaded_lights = match lights.len() {
+        0 => &light_placeholder[..],
+        _ => &lights[..],
+    };
+
+    // A node count of 0 makes the shader test every sphere
+    let bvh_node_count = match bvh.enabled {
+        true => bvh.nodes.len() as u32,
+        false => 0,
+    };
+    let bvh_placeholder = [scene::bvh::GpuBvhNode::zeroed()];
+    let bvh_nodes = match bvh.nodes.len() {
+        0 => &bvh_placeholder[..],
+        _ => &bvh.nodes[..],
+    };
+
+    let sphere_count = GpuCount::new(count as u32);
+    let box_count = GpuCount::new(boxes.count);
+    let triangle_count = GpuCount::new(mesh.count);
+    let plane_count = GpuCount::new(planes.count);
+    let bvh_count = GpuCount::new(bvh_node_count);
+    let noise_table = scene::noise::GpuNoiseTable::new(&noise);
+    let quad_count = GpuCount::new(quads.count);
+    let light_count = GpuCount::new(lights.len() as u32);
+    let disk_count = GpuCount::new(disks.count);
+    let instance_group_count = GpuCount::new(instances.count);
+    let sdf_bounds = scene::sdf::GpuSdfBounds::new(&sdfs);
 
-    commands.insert_resource(SphereBindGroup(sphere_bind_group));
+    // Every binding of the sphere bind group in order, with whether the resources it is
+    // made from changed. Boxes, meshes, planes, quads, disks, sphere instances, SDF
+    // primitives and lights share the group with the spheres, since every group slot is
+    // taken.
+    let uniform = BufferUsages::UNIFORM | BufferUsages::COPY_DST;
+    let storage = BufferUsages::STORAGE | BufferUsages::COPY_DST;
+    let lights_changed = spheres.is_changed() || quads.is_changed() || disks.is_changed();
+    let bindings: [(&str, BufferUsages, bool, &[u8]); 26] = [
+        (
+            "Sphere Count Buffer",
+            uniform,
+            spheres.is_changed(),
+            bytemuck::bytes_of(&sphere_count),
+        ),
+        (
+            "Sphere Buffer",
+            storage,
+            spheres.is_changed(),
+            bytemuck::cast_slice(uploaded),
+        ),
+        (
+            "Box Count Buffer",
+            uniform,
+            boxes.is_changed(),
+            bytemuck::bytes_of(&box_count),
+        ),
+        (
+            "Box Buffer",
+            storage,
+            boxes.is_changed(),
+            bytemuck::cast_slice(&boxes.boxes),
+        ),
+        (
+            "Triangle Count Buffer",
+            uniform,
+            mesh.is_changed(),
+            bytemuck::bytes_of(&triangle_count),
+        ),
+        (
+            "Triangle Buffer",
+            storage,
+            mesh.is_changed(),
+            bytemuck::cast_slice(&mesh.triangles),
+        ),
+        (
+            "Mesh Material Buffer",
+            storage,
+            mesh.is_changed(),
+            bytemuck::cast_slice(&mesh.materials),
+        ),
+        (
+            "Plane Count Buffer",
+            uniform,
+            planes.is_changed(),
+            bytemuck::bytes_of(&plane_count),
+        ),
+        (
+            "Plane Buffer",
+            storage,
+            planes.is_changed(),
+            bytemuck::cast_slice(&planes.planes),
+        ),
+        (
+            "Sphere Material Buffer",
+            storage,
+            spheres.is_changed(),
+            bytemuck::cast_slice(materials),
+        ),
+        (
+            "Sphere BVH Buffer",
+            storage,
+            bvh.is_changed(),
+            bytemuck::cast_slice(bvh_nodes),
+        ),
+        (
+            "Sphere BVH Node Count Buffer",
+            uniform,
+            bvh.is_changed(),
+            bytemuck::bytes_of(&bvh_count),
+        ),
+        (
+            "Noise Permutation Buffer",
+            uniform,
+            noise.is_changed(),
+            bytemuck::bytes_of(&noise_table),
+        ),
+        (
+            "Quad Count Buffer",
+            uniform,
+            quads.is_changed(),
+            bytemuck::bytes_of(&quad_count),
+        ),
+        (
+            "Quad Buffer",
+            storage,
+            quads.is_changed(),
+            bytemuck::cast_slice(&quads.quads),
+        ),
+        (
+            "Light Count Buffer",
+            uniform,
+            lights_changed,
+            bytemuck::bytes_of(&light_count),
+        ),
+        (
+            "Light Buffer",
+            storage,
+            lights_changed,
+            bytemuck::cast_slice(uploaded_lights),
+        ),
+        (
+            "Triangle Normal Buffer",
+            storage,
+            mesh.is_changed(),
+            bytemuck::cast_slice(&mesh.normals),
+        ),
+        (
+            "Disk Count Buffer",
+            uniform,
+            disks.is_changed(),
+            bytemuck::bytes_of(&disk_count),
+        ),
+        (
+            "Disk Buffer",
+            storage,
+            disks.is_changed(),
+            bytemuck::cast_slice(&disks.disks),
+        ),
+        (
+            "Instance Group Count Buffer",
+            uniform,
+            instances.is_changed(),
+            bytemuck::bytes_of(&instance_group_count),
+        ),
+        (
+            "Instance Group Buffer",
+            storage,
+            instances.is_changed(),
+            bytemuck::cast_slice(&instances.groups),
+        ),
+        (
+            "Instance Position Buffer",
+            storage,
+            instances.is_changed(),
+            bytemuck::cast_slice(&instances.positions),
+        ),
+        (
+            "SDF Bounds Buffer",
+            uniform,
+            sdfs.is_changed(),
+            bytemuck::bytes_of(&sdf_bounds),
+        ),
+        (
+            "SDF Primitive Buffer",
+            storage,
+            sdfs.is_changed(),
+            bytemuck::cast_slice(&sdfs.primitives),
+        ),
+        (
+            "Instance Node Buffer",
+            storage,
+            instances.is_changed(),
+            bytemuck::cast_slice(&instances.nodes),
+        ),
+    ];
+    let create_buffer = |label: &str, usage: BufferUsages, contents: &[u8]| {
+        render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some(label),
+            contents,
+            usage,
+        })
+    };
+
+    let Some(mut sphere_bind_group) = sphere_bind_group else {
+        let buffers = bindings
+            .iter()
+            .map(|&(label, usage, _, contents)| create_buffer(label, usage, contents))
+            .collect();
+        commands.insert_resource(SphereBindGroup::new(&render_device, &pipeline, buffers));
+        return;
+    };
+
+    // Write the changed buffers in place, and only make new ones for contents that have
+    // outgrown them. The shader reads no further than the counts, so a buffer left larger
+    // than its contents is fine.
+    let mut reallocated = false;
+    for (&(label, usage, changed, contents), buffer) in
+        bindings.iter().zip(&mut sphere_bind_group.buffers)
+    {
+        if !changed {
+            continue;
+        }
+        if contents.len() as u64 <= buffer.size() {
+            render_queue.write_buffer(buffer, 0, contents);
+        } else {
+            *buffer = create_buffer(label, usage, contents);
+            reallocated = true;
+        }
+    }
+    if reallocated {
+        let buffers = std::mem::take(&mut sphere_bind_group.buffers);
+        *sphere_bind_group = SphereBindGroup::new(&render_device, &pipeline, buffers);
+    }
 }
 
 fn prepare_bind_group(
@@ -211,21 +1261,137 @@ fn prepare_bind_group(
     pipeline: Res<ComputeShaderPipeline>,
     gpu_images: Res<RenderAssets<GpuImage>>,
     game_of_life_images: Res<ComputeShaderImages>,
+    storage_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    probe_buffer: Res<probe::PixelProbeBuffer>,
     render_device: Res<RenderDevice>,
 ) {
-    let view_a = gpu_images.get(&game_of_life_images.texture_a).unwrap();
-    let view_b = gpu_images.get(&game_of_life_images.texture_b).unwrap();
+    // The images and the probe buffer are uploaded asynchronously, so on the first frames
+    // (or on a slow GPU, or right after a resize) they may not exist yet. Try again next
+    // frame; the node skips its dispatch until the bind groups are there.
+    match create_image_bind_groups(
+        &render_device,
+        &pipeline,
+        &gpu_images,
+        &storage_buffers,
+        &game_of_life_images,
+        &probe_buffer,
+    ) {
+        Some(bind_groups) => {
+            commands.insert_resource(ComputeShaderImageBindGroups(bind_groups));
+        }
+        // Don't leave the node drawing into textures that were replaced
+        None => commands.remove_resource::<ComputeShaderImageBindGroups>(),
+    }
+}
+
+// The two texture bind groups of a set of render targets, one for each direction of the
+// ping-pong, or None while any of the targets is still being uploaded
+fn create_image_bind_groups(
+    render_device: &RenderDevice,
+    pipeline: &ComputeShaderPipeline,
+    gpu_images: &RenderAssets<GpuImage>,
+    storage_buffers: &RenderAssets<GpuShaderStorageBuffer>,
+    targets: &ComputeShaderImages,
+    probe_buffer: &probe::PixelProbeBuffer,
+) -> Option<[BindGroup; 2]> {
+    let view_a = gpu_images.get(&targets.texture_a)?;
+    let view_b = gpu_images.get(&targets.texture_b)?;
+    let gbuffer_a = gpu_images.get(&targets.gbuffer_a)?;
+    let gbuffer_b = gpu_images.get(&targets.gbuffer_b)?;
+    let albedo = gpu_images.get(&targets.albedo)?;
+    let sphere_ids = gpu_images.get(&targets.sphere_ids)?;
+    let probe = storage_buffers.get(&probe_buffer.0)?;
+    let moments = storage_buffers.get(&targets.moments)?;
+
     let bind_group_0 = render_device.create_bind_group(
         None,
         &pipeline.texture_bind_group_layout,
-        &BindGroupEntries::sequential((&view_a.texture_view, &view_b.texture_view)),
+        &BindGroupEntries::sequential((
+            &view_a.texture_view,
+            &view_b.texture_view,
+            &gbuffer_a.texture_view,
+            &gbuffer_b.texture_view,
+            probe.buffer.as_entire_binding(),
+            &sphere_ids.texture_view,
+            moments.buffer.as_entire_binding(),
+            &albedo.texture_view,
+        )),
     );
     let bind_group_1 = render_device.create_bind_group(
         None,
         &pipeline.texture_bind_group_layout,
-        &BindGroupEntries::sequential((&view_b.texture_view, &view_a.texture_view)),
+        &BindGroupEntries::sequential((
+            &view_b.texture_view,
+            &view_a.texture_view,
+            &gbuffer_b.texture_view,
+            &gbuffer_a.texture_view,
+            probe.buffer.as_entire_binding(),
+            &sphere_ids.texture_view,
+            moments.buffer.as_entire_binding(),
+            &albedo.texture_view,
+        )),
     );
-    commands.insert_resource(ComputeShaderImageBindGroups([bind_group_0, bind_group_1]));
+    Some([bind_group_0, bind_group_1])
+}
+
+// Bind groups of the views in views::RenderViews whose render targets have been uploaded.
+// Like the main view, each view keeps its camera buffers between frames, in `cameras` at
+// the view's index, and only writes them.
+fn prepare_view_bind_groups(
+    mut commands: Commands,
+    pipeline: Res<ComputeShaderPipeline>,
+    views: Res<views::RenderViews>,
+    (gpu_images, storage_buffers): (
+        Res<RenderAssets<GpuImage>>,
+        Res<RenderAssets<GpuShaderStorageBuffer>>,
+    ),
+    probe_buffer: Res<probe::PixelProbeBuffer>,
+    (render_device, render_queue): (Res<RenderDevice>, Res<RenderQueue>),
+    mut cameras: Local<Vec<Option<CameraBindGroup>>>,
+) {
+    cameras.resize_with(views.0.len(), || None);
+    let bind_groups = views
+        .0
+        .iter()
+        .zip(cameras.iter_mut())
+        .filter_map(|(view, camera)| {
+            let (targets, scene_camera) = view.gpu_view()?;
+            let images = create_image_bind_groups(
+                &render_device,
+                &pipeline,
+                &gpu_images,
+                &storage_buffers,
+                targets,
+                &probe_buffer,
+            )?;
+
+            let camera = camera.get_or_insert_with(|| {
+                CameraBindGroup::new(&render_device, &pipeline, scene_camera)
+            });
+            camera.update(&render_queue, &pipeline, scene_camera);
+
+            Some(ViewBindGroup {
+                images,
+                camera: camera.bind_group.clone(),
+                frame_constants: camera::FrameConstants::new(scene_camera),
+                tile: scene_camera.tile(),
+            })
+        })
+        .collect();
+    commands.insert_resource(ViewBindGroups(bind_groups));
+}
+
+// Shader defs shared by every entry point of the compute shader
+fn shader_defs(workgroup_size: u32, push_constants: bool) -> Vec<ShaderDefVal> {
+    let mut defs = vec![ShaderDefVal::UInt("WORKGROUP_SIZE".into(), workgroup_size)];
+    if push_constants {
+        defs.push("PUSH_CONSTANTS".into());
+    }
+    // Debug builds paint NaN/Inf pixels magenta so numerical bugs are easy to spot
+    if cfg!(debug_assertions) {
+        defs.push("NAN_GUARD".into());
+    }
+    defs
 }
 
 #[derive(Resource)]
@@ -233,14 +1399,45 @@ struct ComputeShaderPipeline {
     texture_bind_group_layout: BindGroupLayout,
     camera_bind_group_layout: BindGroupLayout,
     sphere_bind_group_layout: BindGroupLayout,
+    sky_bind_group_layout: BindGroupLayout,
+    environment_sampler: Sampler,
     init_pipeline: CachedComputePipelineId,
     update_pipeline: CachedComputePipelineId,
+    preview_pipeline: CachedComputePipelineId,
+    direct_pipeline: CachedComputePipelineId,
+    // Workgroup size the pipelines were compiled with, for the dispatch counts
+    workgroup_size: u32,
+    // Whether the pipelines read FrameConstants from push constants rather than a uniform
+    push_constants: bool,
 }
 
 impl FromWorld for ComputeShaderPipeline {
     fn from_world(world: &mut World) -> Self {
+        let requested_workgroup_size = **world.resource::<WorkgroupSize>();
         let render_device = world.resource::<RenderDevice>();
 
+        // Fall back to the default if the GPU can't run workgroups this large
+        let limits = render_device.limits();
+        let workgroup_size = if requested_workgroup_size > 0
+            && requested_workgroup_size
+                .checked_mul(requested_workgroup_size)
+                .is_some_and(|invocations| {
+                    invocations <= limits.max_compute_invocations_per_workgroup
+                })
+            && requested_workgroup_size
+                <= limits
+                    .max_compute_workgroup_size_x
+                    .min(limits.max_compute_workgroup_size_y)
+        {
+            requested_workgroup_size
+        } else {
+            let fallback = *WorkgroupSize::default();
+            warn!(
+                "Workgroup size {requested_workgroup_size} is not supported by this GPU, using {fallback}"
+            );
+            fallback
+        };
+
         // Texture bind group layout
         let texture_bind_group_layout = render_device.create_bind_group_layout(
             "ComputeShaderImages",
@@ -249,6 +1446,17 @@ impl FromWorld for ComputeShaderPipeline {
                 (
                     texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::ReadOnly),
                     texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::WriteOnly),
+                    // G-buffer history and output
+                    texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::ReadOnly),
+                    texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::WriteOnly),
+                    // Probed pixel for readback
+                    storage_buffer::<Vec4>(false),
+                    // Primary-hit sphere index
+                    texture_storage_2d(TextureFormat::R32Uint, StorageTextureAccess::WriteOnly),
+                    // Per-pixel luminance moments
+                    storage_buffer::<Vec2>(false),
+                    // Primary-hit albedo
+                    texture_storage_2d(TextureFormat::Rgba32Float, StorageTextureAccess::WriteOnly),
                 ),
             ),
         );
@@ -263,6 +1471,10 @@ impl FromWorld for ComputeShaderPipeline {
                     bevy::render::render_resource::binding_types::uniform_buffer::<
                         camera::SceneCamera,
                     >(false),
+                    // Uniform buffer for FrameConstants, unused with push constants
+                    bevy::render::render_resource::binding_types::uniform_buffer::<
+                        camera::FrameConstants,
+                    >(false),
                 ),
             ),
         );
@@ -274,15 +1486,143 @@ impl FromWorld for ComputeShaderPipeline {
                 ShaderStages::COMPUTE,
                 (
                     // Number of spheres as a uniform
-                    bevy::render::render_resource::binding_types::uniform_buffer::<u32>(false),
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
                     // Storage buffer for spheres
                     bevy::render::render_resource::binding_types::storage_buffer::<
                         scene::sphere::GpuSphere,
                     >(false),
+                    // Number of boxes as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffer for boxes
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::boxes::GpuBox,
+                    >(false),
+                    // Number of mesh triangles as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffers for mesh triangles and their materials
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::mesh::GpuTriangle,
+                    >(false),
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::sphere::GpuMaterial,
+                    >(false),
+                    // Number of planes as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffer for planes
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::plane::GpuPlane,
+                    >(false),
+                    // Storage buffer for the materials spheres refer to by index
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::sphere::GpuMaterial,
+                    >(false),
+                    // Bounding volume hierarchy over the spheres and its node count
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::bvh::GpuBvhNode,
+                    >(false),
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Permutation table of the noise materials
+                    bevy::render::render_resource::binding_types::uniform_buffer::<
+                        scene::noise::GpuNoiseTable,
+                    >(false),
+                    // Number of quads as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffer for quads
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::quad::GpuQuad,
+                    >(false),
+                    // Number of lights as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffer for the emitters sampled directly
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::lights::GpuLight,
+                    >(false),
+                    // Storage buffer for the corner normals of the mesh triangles
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::mesh::GpuTriangleNormals,
+                    >(false),
+                    // Number of disks as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffer for disks and rings
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::disk::GpuDisk,
+                    >(false),
+                    // Number of sphere instance groups as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<GpuCount>(false),
+                    // Storage buffer for the instance groups
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::instances::GpuInstanceGroup,
+                    >(false),
+                    // Storage buffer for the instance centers, three floats apiece
+                    bevy::render::render_resource::binding_types::storage_buffer::<f32>(false),
+                    // Number of SDF primitives and the box around them as a uniform
+                    bevy::render::render_resource::binding_types::uniform_buffer::<
+                        scene::sdf::GpuSdfBounds,
+                    >(false),
+                    // Storage buffer for the SDF primitives
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::sdf::GpuSdfPrimitive,
+                    >(false),
+                    // Storage buffer for the hierarchies over the instance groups
+                    bevy::render::render_resource::binding_types::storage_buffer::<
+                        scene::bvh::GpuBvhNode,
+                    >(false),
                 ),
             ),
         );
-        let shader = world.load_asset(SHADER_ASSET_PATH);
+
+        // Sky bind group layout
+        let sky_bind_group_layout = render_device.create_bind_group_layout(
+            "SkyLayout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    // Uniform buffer for the sky parameters
+                    bevy::render::render_resource::binding_types::uniform_buffer::<sky::GpuSky>(
+                        false,
+                    ),
+                    // Environment map. HDR images are 32-bit float, which not every GPU
+                    // can filter, so it is sampled without filtering.
+                    bevy::render::render_resource::binding_types::texture_2d(
+                        TextureSampleType::Float { filterable: false },
+                    ),
+                    bevy::render::render_resource::binding_types::sampler(
+                        SamplerBindingType::NonFiltering,
+                    ),
+                    // Uniform buffer for the sun, which shares the group with the sky
+                    bevy::render::render_resource::binding_types::uniform_buffer::<sun::GpuSun>(
+                        false,
+                    ),
+                ),
+            ),
+        );
+        // Wraps horizontally around the equirectangular seam
+        let environment_sampler = render_device.create_sampler(&SamplerDescriptor {
+            label: Some("Environment Map Sampler"),
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::ClampToEdge,
+            ..default()
+        });
+        let shader = if cfg!(debug_assertions) {
+            world.load_asset(SHADER_ASSET_PATH)
+        } else {
+            EMBEDDED_SHADER_HANDLE
+        };
+        // The per-frame camera values go in push constants where the GPU has them, so the
+        // camera uniform only changes with the view
+        let frame_constants_size = std::mem::size_of::<camera::FrameConstants>() as u32;
+        let push_constants = render_device
+            .features()
+            .contains(WgpuFeatures::PUSH_CONSTANTS)
+            && render_device.limits().max_push_constant_size >= frame_constants_size;
+        let push_constant_ranges = if push_constants {
+            vec![PushConstantRange {
+                stages: ShaderStages::COMPUTE,
+                range: 0..frame_constants_size,
+            }]
+        } else {
+            Vec::new()
+        };
         let pipeline_cache = world.resource::<PipelineCache>();
         let init_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
             label: None,
@@ -290,11 +1630,12 @@ impl FromWorld for ComputeShaderPipeline {
                 texture_bind_group_layout.clone(),
                 camera_bind_group_layout.clone(),
                 sphere_bind_group_layout.clone(),
+                sky_bind_group_layout.clone(),
             ],
 
-            push_constant_ranges: Vec::new(),
+            push_constant_ranges: push_constant_ranges.clone(),
             shader: shader.clone(),
-            shader_defs: vec![],
+            shader_defs: shader_defs(workgroup_size, push_constants),
             entry_point: Cow::from("init"),
             zero_initialize_workgroup_memory: false,
         });
@@ -304,11 +1645,52 @@ impl FromWorld for ComputeShaderPipeline {
                 texture_bind_group_layout.clone(),
                 camera_bind_group_layout.clone(),
                 sphere_bind_group_layout.clone(),
+                sky_bind_group_layout.clone(),
+            ],
+
+            push_constant_ranges: push_constant_ranges.clone(),
+            shader: shader.clone(),
+            shader_defs: shader_defs(workgroup_size, push_constants),
+            entry_point: Cow::from("update"),
+            zero_initialize_workgroup_memory: false,
+        });
+        // Cheaper variant of update used while the camera is moving
+        let preview_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: vec![
+                texture_bind_group_layout.clone(),
+                camera_bind_group_layout.clone(),
+                sphere_bind_group_layout.clone(),
+                sky_bind_group_layout.clone(),
             ],
 
-            push_constant_ranges: Vec::new(),
+            push_constant_ranges: push_constant_ranges.clone(),
+            shader: shader.clone(),
+            shader_defs: [
+                shader_defs(workgroup_size, push_constants),
+                vec!["PREVIEW".into()],
+            ]
+            .concat(),
+            entry_point: Cow::from("update"),
+            zero_initialize_workgroup_memory: false,
+        });
+        // Variant of update that leaves out indirect lighting, see IntegratorMode
+        let direct_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+            label: None,
+            layout: vec![
+                texture_bind_group_layout.clone(),
+                camera_bind_group_layout.clone(),
+                sphere_bind_group_layout.clone(),
+                sky_bind_group_layout.clone(),
+            ],
+
+            push_constant_ranges,
             shader,
-            shader_defs: vec![],
+            shader_defs: [
+                shader_defs(workgroup_size, push_constants),
+                vec!["DIRECT_ONLY".into()],
+            ]
+            .concat(),
             entry_point: Cow::from("update"),
             zero_initialize_workgroup_memory: false,
         });
@@ -317,8 +1699,14 @@ impl FromWorld for ComputeShaderPipeline {
             texture_bind_group_layout,
             camera_bind_group_layout,
             sphere_bind_group_layout,
+            sky_bind_group_layout,
+            environment_sampler,
             init_pipeline,
             update_pipeline,
+            preview_pipeline,
+            direct_pipeline,
+            workgroup_size,
+            push_constants,
         }
     }
 }
@@ -327,6 +1715,10 @@ enum ComputeShaderState {
     Loading,
     Init,
     Update(usize),
+    // The shader changed and the pipelines are being compiled again
+    Reloading,
+    // A pipeline failed to compile; nothing is dispatched until it compiles again
+    Failed,
 }
 
 struct ComputeShaderNode {
@@ -345,18 +1737,46 @@ impl render_graph::Node for ComputeShaderNode {
     fn update(&mut self, world: &mut World) {
         let pipeline = world.resource::<ComputeShaderPipeline>();
         let pipeline_cache = world.resource::<PipelineCache>();
+        let shader_status = world.resource::<ShaderStatus>();
+        // The preview and direct lighting variants are only reported when they fail: the
+        // node uses the update pipeline until they have compiled
+        let states = [
+            pipeline.init_pipeline,
+            pipeline.update_pipeline,
+            pipeline.preview_pipeline,
+            pipeline.direct_pipeline,
+        ]
+        .map(|id| pipeline_cache.get_compute_pipeline_state(id));
+
+        // Log a compile error once instead of panicking, so the shader can be fixed while
+        // the app keeps running. A shader that hasn't loaded yet is retried by the cache.
+        let error = states.iter().find_map(|state| match state {
+            CachedPipelineState::Err(
+                PipelineCacheError::ShaderNotLoaded(_)
+                | PipelineCacheError::ShaderImportNotYetAvailable,
+            ) => None,
+            CachedPipelineState::Err(err) => Some(err.to_string()),
+            _ => None,
+        });
+        if let Some(error) = error {
+            if !matches!(self.state, ComputeShaderState::Failed) {
+                error!("Compiling assets/{SHADER_ASSET_PATH}:\n{error}");
+                shader_status.pause(Some(error));
+                self.state = ComputeShaderState::Failed;
+            }
+            return;
+        }
+        let compiled = states[..2]
+            .iter()
+            .all(|state| matches!(state, CachedPipelineState::Ok(_)));
 
         // if the corresponding pipeline has loaded, transition to the next stage
         match self.state {
             ComputeShaderState::Loading => {
-                match pipeline_cache.get_compute_pipeline_state(pipeline.init_pipeline) {
-                    CachedPipelineState::Ok(_) => {
-                        self.state = ComputeShaderState::Init;
-                    }
-                    CachedPipelineState::Err(err) => {
-                        panic!("Initializing assets/{SHADER_ASSET_PATH}:\n{err}")
-                    }
-                    _ => {}
+                if let CachedPipelineState::Ok(_) =
+                    pipeline_cache.get_compute_pipeline_state(pipeline.init_pipeline)
+                {
+                    self.state = ComputeShaderState::Init;
                 }
             }
             ComputeShaderState::Init => {
@@ -366,6 +1786,21 @@ impl render_graph::Node for ComputeShaderNode {
                     self.state = ComputeShaderState::Update(1);
                 }
             }
+            // Start over once the edited or fixed shader has compiled
+            ComputeShaderState::Reloading | ComputeShaderState::Failed => {
+                if compiled {
+                    shader_status.reloaded();
+                    self.state = ComputeShaderState::Loading;
+                }
+            }
+            // The pipeline cache compiles the pipelines again when the shader file changes
+            ComputeShaderState::Update(_) if !compiled => {
+                shader_status.pause(None);
+                self.state = ComputeShaderState::Reloading;
+            }
+            // Keep the current textures when step mode holds back the dispatch
+            ComputeShaderState::Update(_)
+                if !world.resource::<step::StepMode>().should_dispatch() => {}
             ComputeShaderState::Update(0) => {
                 self.state = ComputeShaderState::Update(1);
             }
@@ -382,11 +1817,56 @@ impl render_graph::Node for ComputeShaderNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), render_graph::NodeRunError> {
-        let bind_groups = &world.resource::<ComputeShaderImageBindGroups>().0;
-        let camera_bind_group = &world.resource::<CameraBindGroup>().0;
-        let sphere_bind_group = &world.resource::<SphereBindGroup>().0;
+        let Some(bind_groups) = world.get_resource::<ComputeShaderImageBindGroups>() else {
+            return Ok(());
+        };
+        let camera_bind_group = &world.resource::<CameraBindGroup>().bind_group;
+        let sphere_bind_group = &world.resource::<SphereBindGroup>().bind_group;
+        let sky_bind_group = &world.resource::<SkyBindGroup>().0;
         let pipeline_cache = world.resource::<PipelineCache>();
         let pipeline = world.resource::<ComputeShaderPipeline>();
+        let size = **world.resource::<RenderSize>();
+        let workgroups = (size + pipeline.workgroup_size - 1) / pipeline.workgroup_size;
+
+        // The main view, then the views next to it; they share the scene and the sky
+        let main_camera = world.resource::<camera::SceneCamera>();
+        let main_view = ViewBindGroup {
+            images: bind_groups.0.clone(),
+            camera: camera_bind_group.clone(),
+            frame_constants: camera::FrameConstants::new(main_camera),
+            tile: main_camera.tile(),
+        };
+        let extra_views = world.get_resource::<ViewBindGroups>();
+        let views = std::iter::once(&main_view).chain(extra_views.iter().flat_map(|v| &v.0));
+
+        // Only the tile's workgroups are dispatched, so the rest of the main image is
+        // carried over by copying it to the textures this frame writes first. The other
+        // views always render all of it.
+        if let ComputeShaderState::Update(index) = self.state {
+            if main_view.tile.size() != size && world.resource::<step::StepMode>().should_dispatch()
+            {
+                let targets = world.resource::<ComputeShaderImages>();
+                let gpu_images = world.resource::<RenderAssets<GpuImage>>();
+                let encoder = render_context.command_encoder();
+                for (a, b) in [
+                    (&targets.texture_a, &targets.texture_b),
+                    (&targets.gbuffer_a, &targets.gbuffer_b),
+                ] {
+                    // The first bind group reads from `a` and writes to `b`
+                    let (input, output) = if index == 0 { (a, b) } else { (b, a) };
+                    let (Some(input), Some(output)) =
+                        (gpu_images.get(input), gpu_images.get(output))
+                    else {
+                        continue;
+                    };
+                    encoder.copy_texture_to_texture(
+                        input.texture.as_image_copy(),
+                        output.texture.as_image_copy(),
+                        input.texture.size(),
+                    );
+                }
+            }
+        }
 
         let mut pass = render_context
             .command_encoder()
@@ -394,26 +1874,58 @@ impl render_graph::Node for ComputeShaderNode {
 
         // select the pipeline based on the current state
         match self.state {
-            ComputeShaderState::Loading => {}
+            ComputeShaderState::Loading
+            | ComputeShaderState::Reloading
+            | ComputeShaderState::Failed => {}
             ComputeShaderState::Init => {
-                let init_pipeline = pipeline_cache
-                    .get_compute_pipeline(pipeline.init_pipeline)
-                    .unwrap();
-                pass.set_bind_group(0, &bind_groups[0], &[]);
-                pass.set_bind_group(1, camera_bind_group, &[]);
-                pass.set_bind_group(2, sphere_bind_group, &[]);
-                pass.set_pipeline(init_pipeline);
-                pass.dispatch_workgroups(SIZE.0 / WORKGROUP_SIZE, SIZE.1 / WORKGROUP_SIZE, 1);
+                // Pipelines being compiled again after a shader edit aren't available
+                let Some(init_pipeline) =
+                    pipeline_cache.get_compute_pipeline(pipeline.init_pipeline)
+                else {
+                    return Ok(());
+                };
+                for view in views {
+                    pass.set_bind_group(0, &view.images[0], &[]);
+                    pass.set_bind_group(1, &view.camera, &[]);
+                    pass.set_bind_group(2, sphere_bind_group, &[]);
+                    pass.set_bind_group(3, sky_bind_group, &[]);
+                    pass.set_pipeline(init_pipeline);
+                    pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+                }
             }
+            ComputeShaderState::Update(_)
+                if !world.resource::<step::StepMode>().should_dispatch() => {}
             ComputeShaderState::Update(index) => {
-                let update_pipeline = pipeline_cache
-                    .get_compute_pipeline(pipeline.update_pipeline)
-                    .unwrap();
-                pass.set_bind_group(0, &bind_groups[index], &[]);
-                pass.set_bind_group(1, camera_bind_group, &[]);
-                pass.set_bind_group(2, sphere_bind_group, &[]);
-                pass.set_pipeline(update_pipeline);
-                pass.dispatch_workgroups(SIZE.0 / WORKGROUP_SIZE, SIZE.1 / WORKGROUP_SIZE, 1);
+                let direct_only = *world.resource::<integrator::IntegratorMode>()
+                    == integrator::IntegratorMode::DirectOnly;
+                for view in views {
+                    // Trade quality for speed while the camera is moving, once the preview
+                    // variant has compiled. Direct lighting is cheap enough to skip it.
+                    let moving = view.frame_constants.camera_has_moved > 0.5;
+                    let update_pipeline = if direct_only {
+                        pipeline_cache.get_compute_pipeline(pipeline.direct_pipeline)
+                    } else {
+                        pipeline_cache
+                            .get_compute_pipeline(pipeline.preview_pipeline)
+                            .filter(|_| moving)
+                    }
+                    .or_else(|| pipeline_cache.get_compute_pipeline(pipeline.update_pipeline));
+                    // Pipelines being compiled again after a shader edit aren't available
+                    let Some(update_pipeline) = update_pipeline else {
+                        break;
+                    };
+                    pass.set_bind_group(0, &view.images[index], &[]);
+                    pass.set_bind_group(1, &view.camera, &[]);
+                    pass.set_bind_group(2, sphere_bind_group, &[]);
+                    pass.set_bind_group(3, sky_bind_group, &[]);
+                    pass.set_pipeline(update_pipeline);
+                    if pipeline.push_constants {
+                        pass.set_push_constants(0, bytemuck::bytes_of(&view.frame_constants));
+                    }
+                    let workgroups =
+                        (view.tile.size() + pipeline.workgroup_size - 1) / pipeline.workgroup_size;
+                    pass.dispatch_workgroups(workgroups.x, workgroups.y, 1);
+                }
             }
         }
 
@@ -1,5 +1,6 @@
-use gpu_ray_tracing::run;
+use bevy::app::AppExit;
+use gpu_ray_tracing::run_from_args;
 
-fn main() {
-    run();
+fn main() -> AppExit {
+    run_from_args()
 }
@@ -0,0 +1,320 @@
+use bevy::{
+    prelude::*,
+    render::{
+        gpu_readback::{Readback, ReadbackComplete},
+        renderer::RenderDevice,
+    },
+};
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    camera::CameraSettings,
+    display::{DisplaySettings, DisplayedImage},
+    step::StepMode,
+};
+
+// Size of one Rgba32Float pixel in bytes
+const BYTES_PER_PIXEL: u32 = 16;
+
+// 3x5 bitmap glyphs for the digits 0-9, one row per entry with the leftmost pixel in bit 2
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+// Size of one glyph pixel of the frame count label, matching post_process.wgsl
+const FRAME_COUNT_SCALE: u32 = 2;
+
+// Bit depth of saved PNGs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureFormat {
+    #[default]
+    Png8,
+    // More gradient precision, so later edits to the image don't band
+    Png16,
+}
+
+// How captured frames are encoded
+#[derive(Resource, Debug, Clone)]
+pub struct CaptureSettings {
+    pub format: CaptureFormat,
+    // Apply the sRGB transfer function; when false the PNG holds linear values
+    pub srgb: bool,
+    // Folder that stills saved with F5 go to
+    pub output_dir: PathBuf,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            format: CaptureFormat::Png8,
+            srgb: true,
+            output_dir: PathBuf::from("captures"),
+        }
+    }
+}
+
+/// System to switch captures between 8-bit and 16-bit PNGs with F11
+pub fn toggle_capture_format_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<CaptureSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F11) {
+        settings.format = match settings.format {
+            CaptureFormat::Png8 => CaptureFormat::Png16,
+            CaptureFormat::Png16 => CaptureFormat::Png8,
+        };
+        info!("Capture format: {:?}", settings.format);
+    }
+}
+
+/// System to save the image on screen as a timestamped PNG with F5, e.g. to keep a still
+/// once it has converged, or as a 32-bit float OpenEXR file for grading with F6
+pub fn save_frame_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<CaptureSettings>,
+    displayed: Res<DisplayedImage>,
+    render_size: Res<crate::RenderSize>,
+) {
+    let exr = keyboard_input.just_pressed(KeyCode::F6);
+    if !exr && !keyboard_input.just_pressed(KeyCode::F5) {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let extension = if exr { "exr" } else { "png" };
+    let path = settings
+        .output_dir
+        .join(format!("render_{timestamp}.{extension}"));
+    if exr {
+        capture_exr(&mut commands, displayed.0.clone(), **render_size, path);
+    } else {
+        capture_png(&mut commands, displayed.0.clone(), **render_size, path);
+    }
+}
+
+// Sent once a captured frame has been written to disk
+#[derive(Event, Debug, Clone)]
+pub struct CaptureSaved(pub PathBuf);
+
+// Sent instead of CaptureSaved when a captured frame couldn't be written, such as on a full
+// disk or a path that can't be created
+#[derive(Event, Debug, Clone)]
+pub struct CaptureFailed(pub PathBuf);
+
+// Read back `image`, which is `size` pixels, from the GPU and save it as a PNG at `path`,
+// encoded according to CaptureSettings. CaptureSaved is sent when the file has been written,
+// or CaptureFailed if it couldn't be.
+pub fn capture_png(commands: &mut Commands, image: Handle<Image>, size: UVec2, path: PathBuf) {
+    capture(commands, image, size, path, false);
+}
+
+// Read back `image`, which is `size` pixels, from the GPU and save the raw accumulation as
+// an OpenEXR file at `path`. CaptureSaved or CaptureFailed is sent as for capture_png.
+pub fn capture_exr(commands: &mut Commands, image: Handle<Image>, size: UVec2, path: PathBuf) {
+    capture(commands, image, size, path, true);
+}
+
+// Readback shared by the PNG and EXR captures. EXR files skip the frame count label and
+// all encoding, keeping the linear float values.
+fn capture(commands: &mut Commands, image: Handle<Image>, size: UVec2, path: PathBuf, exr: bool) {
+    let UVec2 {
+        x: width,
+        y: height,
+    } = size;
+    commands.spawn(Readback::texture(image)).observe(
+        move |trigger: Trigger<ReadbackComplete>,
+              mut commands: Commands,
+              settings: Res<CaptureSettings>,
+              display_settings: Res<DisplaySettings>,
+              camera_settings: Res<CameraSettings>,
+              step_mode: Res<StepMode>,
+              mut saved: EventWriter<CaptureSaved>,
+              mut failed: EventWriter<CaptureFailed>| {
+            // A readback repeats every frame until its entity is gone, so only keep the first
+            commands.entity(trigger.entity()).despawn();
+
+            let mut pixels = readback_to_rgba32f(&trigger.event().0, width, height);
+            let result = if exr {
+                save_exr(&path, &pixels, width, height).map_err(|err| err.to_string())
+            } else {
+                if display_settings.frame_count_label {
+                    burn_frame_count(&mut pixels, size, camera_settings.samples_shown(&step_mode));
+                }
+                save_png(&path, &pixels, width, height, &settings).map_err(|err| err.to_string())
+            };
+            match result {
+                Ok(()) => {
+                    info!("Saved {}", path.display());
+                    saved.send(CaptureSaved(path.clone()));
+                }
+                Err(err) => {
+                    error!("Could not save {}: {err}", path.display());
+                    failed.send(CaptureFailed(path.clone()));
+                }
+            }
+        },
+    );
+}
+
+// Draw `samples` into the upper left corner the same way the post pass does on screen
+pub fn burn_frame_count(pixels: &mut [f32], size: UVec2, samples: u32) {
+    let origin = UVec2::splat(2 * FRAME_COUNT_SCALE);
+    draw_number(pixels, size, origin, samples, FRAME_COUNT_SCALE);
+}
+
+// Convert the raw bytes of an Rgba32Float texture readback into tightly packed floats,
+// dropping the padding the GPU adds to the end of each row
+pub fn readback_to_rgba32f(data: &[u8], width: u32, height: u32) -> Vec<f32> {
+    let row_bytes = (width * BYTES_PER_PIXEL) as usize;
+    let padded_row_bytes = RenderDevice::align_copy_bytes_per_row(row_bytes);
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+    for row in data.chunks(padded_row_bytes).take(height as usize) {
+        pixels.extend_from_slice(bytemuck::cast_slice::<u8, f32>(&row[..row_bytes]));
+    }
+    pixels
+}
+
+// Encode a linear color channel with the sRGB transfer function
+pub fn linear_to_srgb(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// Save linear RGBA float pixels as an RGB PNG. The alpha channel holds the per-pixel
+// sample count, so it is not written.
+pub fn save_png(
+    path: &Path,
+    pixels: &[f32],
+    width: u32,
+    height: u32,
+    settings: &CaptureSettings,
+) -> image::ImageResult<()> {
+    let encode = |channel: f32| {
+        if settings.srgb {
+            linear_to_srgb(channel)
+        } else {
+            channel.clamp(0.0, 1.0)
+        }
+    };
+    let channels = pixels
+        .chunks_exact(4)
+        .flat_map(|pixel| pixel[..3].iter().map(|&channel| encode(channel)));
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    match settings.format {
+        CaptureFormat::Png8 => {
+            let values = channels
+                .map(|value| (value * 255.0).round() as u8)
+                .collect();
+            image::RgbImage::from_raw(width, height, values)
+                .expect("pixel buffer should match the image size")
+                .save(path)
+        }
+        CaptureFormat::Png16 => {
+            let values = channels
+                .map(|value| (value * 65535.0).round() as u16)
+                .collect();
+            image::ImageBuffer::<image::Rgb<u16>, Vec<u16>>::from_raw(width, height, values)
+                .expect("pixel buffer should match the image size")
+                .save(path)
+        }
+    }
+}
+
+// Save linear RGBA float pixels as a 32-bit float OpenEXR file, without tone mapping or
+// clamping. Alpha holds the per-pixel sample count, which compositors would read as
+// coverage, so the file gets an opaque alpha and the count in a `samples` channel.
+pub fn save_exr(path: &Path, pixels: &[f32], width: u32, height: u32) -> exr::error::Result<()> {
+    use exr::prelude::*;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let channels = SpecificChannels::build()
+        .with_channel("R")
+        .with_channel("G")
+        .with_channel("B")
+        .with_channel("A")
+        .with_channel("samples")
+        .with_pixel_fn(|position: Vec2<usize>| {
+            let index = (position.y() * width as usize + position.x()) * 4;
+            (
+                pixels[index],
+                pixels[index + 1],
+                pixels[index + 2],
+                1.0_f32,
+                pixels[index + 3],
+            )
+        });
+    Image::from_channels((width as usize, height as usize), channels)
+        .write()
+        .to_file(path)
+}
+
+// Draw a number in white on a black box with its upper left corner at `origin`, with each
+// glyph pixel covering `scale` x `scale` image pixels. Whatever falls outside the image,
+// which is `image_size` pixels, is cut off.
+pub fn draw_number(pixels: &mut [f32], image_size: UVec2, origin: UVec2, number: u32, scale: u32) {
+    let text = number.to_string();
+    let glyph_advance = 4 * scale;
+    let box_size = UVec2::new(text.len() as u32 * glyph_advance + scale, 7 * scale);
+
+    let mut set_pixel = |x: u32, y: u32, value: f32| {
+        if x >= image_size.x || y >= image_size.y {
+            return;
+        }
+        let index = ((y * image_size.x + x) * 4) as usize;
+        if let Some(pixel) = pixels.get_mut(index..index + 3) {
+            pixel.fill(value);
+        }
+    };
+
+    for y in 0..box_size.y {
+        for x in 0..box_size.x {
+            set_pixel(origin.x + x, origin.y + y, 0.0);
+        }
+    }
+
+    for (i, digit) in text.bytes().enumerate() {
+        let glyph = DIGITS[(digit - b'0') as usize];
+        let glyph_origin = origin + UVec2::new(i as u32 * glyph_advance, 0) + scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..scale {
+                    for dx in 0..scale {
+                        set_pixel(
+                            glyph_origin.x + column * scale + dx,
+                            glyph_origin.y + row as u32 * scale + dy,
+                            1.0,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
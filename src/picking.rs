@@ -0,0 +1,178 @@
+use bevy::{
+    prelude::*,
+    render::{
+        gpu_readback::{Readback, ReadbackComplete},
+        renderer::RenderDevice,
+    },
+    window::PrimaryWindow,
+};
+
+use crate::{
+    camera::{CameraSettings, SceneCamera},
+    scene::sphere::SphereCollection,
+    views::RenderViews,
+    ComputeShaderImages, RenderSize,
+};
+
+// Closest focus distance a click can set; nearer than this the viewport would collapse
+// onto the camera
+const MIN_FOCUS_DISTANCE: f32 = 0.1;
+
+// Farthest the cursor can move, in logical pixels, between pressing and releasing the right
+// mouse button for the click to pick rather than drag the view
+const PICK_DRAG_TOLERANCE: f32 = 4.0;
+
+// Sent when the sphere under a pixel has been read back from the sphere ID texture
+#[derive(Event, Debug, Clone)]
+pub struct SpherePicked {
+    pub pixel: UVec2,
+    // Index into SphereCollection, or None if the pixel shows the sky
+    pub sphere: Option<usize>,
+}
+
+// Cast the primary ray through the center of a pixel on the CPU and return the index of
+// the closest sphere it hits
+pub fn pick_sphere(
+    camera: &SceneCamera,
+    spheres: &SphereCollection,
+    pixel: UVec2,
+) -> Option<usize> {
+    let (origin, direction) = camera.ray_through(pixel);
+    spheres
+        .closest_hit(origin, direction)
+        .map(|(index, _)| index)
+}
+
+// Map a cursor position to a pixel of a rendered image of `size` pixels. The image is drawn
+// centered in the window at DISPLAY_FACTOR scale, or with other views next to it, centered
+// in the leftmost of `slices` slices of the window and scaled down to fit.
+pub fn cursor_to_pixel(window: &Window, cursor: Vec2, size: UVec2, slices: u32) -> Option<UVec2> {
+    let image_size = size.as_vec2();
+    let scale = crate::DISPLAY_FACTOR as f32 / slices as f32;
+    let slice = window.size() / Vec2::new(slices as f32, 1.0);
+    let offset = (slice - image_size * scale) / 2.0;
+    let pixel = ((cursor - offset) / scale).floor();
+    if pixel.x < 0.0 || pixel.y < 0.0 || pixel.x >= image_size.x || pixel.y >= image_size.y {
+        return None;
+    }
+    Some(pixel.as_uvec2())
+}
+
+/// System to read back the sphere ID under the cursor when the right mouse button is clicked.
+/// Dragging with the button held turns the view instead, so only a release close to where
+/// the button went down picks. Without exactly one primary window (e.g. headless) the
+/// system is skipped.
+pub fn pick_sphere_system(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    images: Res<ComputeShaderImages>,
+    render_size: Res<RenderSize>,
+    views: Res<RenderViews>,
+    mut pressed_at: Local<Option<Vec2>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    if mouse_input.just_pressed(MouseButton::Right) {
+        *pressed_at = window.cursor_position();
+    }
+    if !mouse_input.just_released(MouseButton::Right) {
+        return;
+    }
+    let Some(pressed_at) = pressed_at.take() else {
+        return;
+    };
+    let Some(pixel) = window
+        .cursor_position()
+        .filter(|cursor| cursor.distance(pressed_at) <= PICK_DRAG_TOLERANCE)
+        .and_then(|cursor| cursor_to_pixel(window, cursor, **render_size, views.slices()))
+    else {
+        return;
+    };
+    let width = render_size.x;
+
+    commands
+        .spawn(Readback::texture(images.sphere_ids.clone()))
+        .observe(
+            move |trigger: Trigger<ReadbackComplete>,
+                  mut commands: Commands,
+                  mut picked: EventWriter<SpherePicked>| {
+                commands.entity(trigger.entity()).despawn();
+
+                // R32Uint texels are 4 bytes; rows are padded for the copy
+                let row_bytes = RenderDevice::align_copy_bytes_per_row((width * 4) as usize);
+                let offset = pixel.y as usize * row_bytes + pixel.x as usize * 4;
+                let Some(bytes) = trigger.event().0.get(offset..offset + 4) else {
+                    return;
+                };
+                let id = u32::from_ne_bytes(bytes.try_into().unwrap());
+                picked.send(SpherePicked {
+                    pixel,
+                    sphere: id.checked_sub(1).map(|index| index as usize),
+                });
+            },
+        );
+}
+
+/// System to focus the camera on the sphere under the cursor when the left mouse button is
+/// clicked. The focus plane moves to the hit point, so with defocus blur on the sphere
+/// becomes sharp and everything else blurs. Clicks on the sky are ignored.
+pub fn focus_on_click_system(
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Option<Res<SceneCamera>>,
+    spheres: Res<SphereCollection>,
+    mut camera_settings: ResMut<CameraSettings>,
+    render_size: Res<RenderSize>,
+    views: Res<RenderViews>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Ok(window), Some(camera)) = (windows.get_single(), camera) else {
+        return;
+    };
+    let Some(pixel) = window
+        .cursor_position()
+        .and_then(|cursor| cursor_to_pixel(window, cursor, **render_size, views.slices()))
+    else {
+        return;
+    };
+
+    let (origin, direction) = camera.ray_through(pixel);
+    let Some((index, t)) = spheres.closest_hit(origin, direction) else {
+        return;
+    };
+    // The focus plane faces the camera, so use the hit's depth rather than its distance
+    let depth = camera.depth_of(origin + t * direction);
+    camera_settings.focus_distance = depth.max(MIN_FOCUS_DISTANCE);
+    camera_settings.camera_has_moved = true;
+    info!(
+        "Focused on sphere {index} at distance {}",
+        camera_settings.focus_distance
+    );
+}
+
+/// System to report picked spheres, cross-checked against a CPU ray cast
+pub fn log_picked_sphere_system(
+    mut picked: EventReader<SpherePicked>,
+    camera: Option<Res<SceneCamera>>,
+    spheres: Res<SphereCollection>,
+) {
+    for event in picked.read() {
+        match event.sphere {
+            Some(index) => info!("Sphere {index} at pixel {}", event.pixel),
+            None => info!("Sky at pixel {}", event.pixel),
+        }
+
+        let Some(camera) = camera.as_deref() else {
+            continue;
+        };
+        let expected = pick_sphere(camera, &spheres, event.pixel);
+        if expected != event.sphere {
+            // Depth of field and camera motion since the click can legitimately disagree
+            warn!("CPU ray cast found {expected:?} at pixel {}", event.pixel);
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use bevy::{prelude::*, sprite::Material2dPlugin};
+
+#[cfg(feature = "ui")]
+use crate::ui;
+use crate::{
+    bookmarks, camera, contact_sheet, denoise, display, grid, integrator, output, picking, probe,
+    reset_to_defaults, resize_render_targets, restart_after_shader_reload,
+    scene::sphere::SpheresPlugin, selection, setup, setup_display, sky, step, sun, switch_textures,
+    tiles, turntable, views, ComputeShaderComputePlugin, RayTracedScene, RenderSize, WorkgroupSize,
+};
+
+// The ray tracer as a plugin, for adding to an app of your own. Build it with
+// RayTracingPlugin::builder(), or use RayTracingPlugin::new() for the settings `run`
+// starts with.
+//
+// The host app must add Bevy's DefaultPlugins (or at least the render, asset, image,
+// input, window, gizmo and 2D sprite plugins they include) before this plugin. Everything
+// else the ray tracer uses, such as CameraSettings, SkySettings and the render targets,
+// is set up by the plugin at startup. The rendered image is in the DisplayedImage
+// resource; with `display(false)` the plugin doesn't show it, and drawing it is up to the
+// host. The render then also stays at the plugin's resolution instead of following the
+// window.
+#[derive(Clone)]
+pub struct RayTracingPlugin {
+    size: UVec2,
+    // Override the camera's CameraSettings::samples_per_pixel, field of view and defocus
+    samples_per_pixel: Option<u32>,
+    fov: Option<f32>,
+    defocus_angle: Option<f32>,
+    focus_distance: Option<f32>,
+    // Replaces the startup scene and default camera
+    scene: Option<RayTracedScene>,
+    workgroup_size: u32,
+    // Show the image in a full-window sprite with its own 2D camera
+    display: bool,
+    // Run the demo's keyboard, mouse and gamepad controls, see InputSet
+    controls: bool,
+}
+
+// Systems that read the mouse, the keyboard or a gamepad. The egui windows of the `ui`
+// feature pause them while they use that input themselves, and host apps can do the same
+// for UI of their own with run conditions on these sets, or turn them all off with
+// RayTracingPluginBuilder::controls.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputSet {
+    // Mouse buttons, motion and the wheel
+    Pointer,
+    // Keyboard shortcuts and movement keys
+    Keyboard,
+    // Gamepad sticks and triggers
+    Gamepad,
+}
+
+impl Default for RayTracingPlugin {
+    fn default() -> Self {
+        Self {
+            size: UVec2::new(crate::SIZE.0, crate::SIZE.1),
+            samples_per_pixel: None,
+            fov: None,
+            defocus_angle: None,
+            focus_distance: None,
+            scene: None,
+            workgroup_size: *WorkgroupSize::default(),
+            display: true,
+            controls: true,
+        }
+    }
+}
+
+impl RayTracingPlugin {
+    // The plugin with the settings `run` starts with, the same as default()
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn builder() -> RayTracingPluginBuilder {
+        RayTracingPluginBuilder(Self::default())
+    }
+}
+
+// Fluent builder for a RayTracingPlugin:
+//
+// RayTracingPlugin::builder()
+//     .resolution(1920, 1080)
+//     .samples(256)
+//     .scene(SceneBuilder::new().add_sphere(Vec3::ZERO, 1.0, material).build()?)
+//     .build()
+pub struct RayTracingPluginBuilder(RayTracingPlugin);
+
+impl RayTracingPluginBuilder {
+    // Size of the render in pixels
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.0.size = UVec2::new(width, height);
+        self
+    }
+
+    // Samples every pixel accumulates before it stops
+    pub fn samples(mut self, samples_per_pixel: u32) -> Self {
+        self.0.samples_per_pixel = Some(samples_per_pixel);
+        self
+    }
+
+    // Vertical field of view in degrees, which makes the camera a perspective one
+    pub fn fov(mut self, fov: f32) -> Self {
+        self.0.fov = Some(fov);
+        self
+    }
+
+    // Defocus blur angle in degrees; 0 keeps everything sharp
+    pub fn defocus_angle(mut self, defocus_angle: f32) -> Self {
+        self.0.defocus_angle = Some(defocus_angle);
+        self
+    }
+
+    // Distance to the plane in focus
+    pub fn focus_distance(mut self, focus_distance: f32) -> Self {
+        self.0.focus_distance = Some(focus_distance);
+        self
+    }
+
+    // Render this scene instead of the startup scene file, such as one from SceneBuilder
+    // or just a SphereCollection
+    pub fn scene(mut self, scene: impl Into<RayTracedScene>) -> Self {
+        self.0.scene = Some(scene.into());
+        self
+    }
+
+    // Side of the compute shader's workgroups, see WorkgroupSize
+    pub fn workgroup_size(mut self, workgroup_size: u32) -> Self {
+        self.0.workgroup_size = workgroup_size;
+        self
+    }
+
+    // Whether to show the image in a full-window sprite, true by default
+    pub fn display(mut self, display: bool) -> Self {
+        self.0.display = display;
+        self
+    }
+
+    // Whether the keyboard, mouse and gamepad control the camera and settings, true by
+    // default. Without them the host app drives CameraSettings itself, and keys such as
+    // the bookmarks, which write camera_bookmarks.json, are left free for its own use.
+    pub fn controls(mut self, controls: bool) -> Self {
+        self.0.controls = controls;
+        self
+    }
+
+    pub fn build(self) -> RayTracingPlugin {
+        self.0
+    }
+}
+
+impl Plugin for RayTracingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RenderSize(self.size))
+            .insert_resource(WorkgroupSize(self.workgroup_size))
+            .init_resource::<bookmarks::CameraBookmarks>()
+            .init_resource::<views::RenderViews>()
+            .add_plugins((
+                ComputeShaderComputePlugin,
+                SpheresPlugin,
+                Material2dPlugin::<display::PostProcessMaterial>::default(),
+            ))
+            .add_event::<output::CaptureSaved>()
+            .add_event::<output::CaptureFailed>()
+            .add_event::<picking::SpherePicked>()
+            .add_systems(
+                Startup,
+                (
+                    setup,
+                    bookmarks::load_bookmarks,
+                    probe::setup_probe,
+                    sky::setup_environment_map,
+                ),
+            )
+            .add_systems(
+                Update,
+                switch_textures
+                    .after(step::step_mode_system)
+                    .after(contact_sheet::contact_sheet_system)
+                    .after(step::sample_budget_system),
+            )
+            .add_systems(
+                Update,
+                restart_after_shader_reload.before(camera::extract_camera),
+            )
+            .add_systems(
+                Update,
+                (
+                    grid::toggle_grid_system.in_set(InputSet::Keyboard),
+                    grid::draw_grid_system.after(camera::extract_camera),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    (
+                        display::exposure_controls_system,
+                        display::exposure_sweep_system,
+                    )
+                        .in_set(InputSet::Keyboard),
+                    display::auto_exposure_system,
+                    (
+                        display::vignette_controls_system,
+                        display::toggle_frame_count_label_system,
+                        display::toggle_sample_heatmap_system,
+                        display::cycle_tonemap_system,
+                        display::cycle_display_channel_system,
+                    )
+                        .in_set(InputSet::Keyboard),
+                    display::update_post_process_system.after(switch_textures),
+                    display::draw_crosshair_system,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                Update,
+                (
+                    views::toggle_split_screen_system.in_set(InputSet::Keyboard),
+                    views::prepare_views_system
+                        .after(views::toggle_split_screen_system)
+                        .after(resize_render_targets)
+                        .before(views::update_view_cameras_system),
+                    views::layout_views_system.after(views::prepare_views_system),
+                    views::update_view_sprites_system.after(display::update_post_process_system),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    picking::pick_sphere_system.in_set(InputSet::Pointer),
+                    picking::log_picked_sphere_system,
+                    picking::focus_on_click_system.in_set(InputSet::Pointer),
+                    selection::draw_selection_system.after(camera::extract_camera),
+                ),
+            )
+            // Add camera movement systems
+            .add_systems(
+                Update,
+                (
+                    (
+                        (
+                            camera::toggle_camera_mode_system,
+                            camera::camera_movement_system,
+                            camera::reset_camera_view_system,
+                            camera::restart_accumulation_system,
+                            bookmarks::camera_bookmarks_system,
+                        )
+                            .in_set(InputSet::Keyboard),
+                        (
+                            camera::camera_mouse_controls_system.in_set(InputSet::Pointer),
+                            camera::ease_fov_system,
+                        )
+                            .chain(),
+                        camera::gamepad_camera_system.in_set(InputSet::Gamepad),
+                        (
+                            camera::toggle_edge_antialiasing_system,
+                            camera::toggle_projection_system,
+                            camera::toggle_stratified_sampling_system,
+                            camera::cycle_variance_threshold_system,
+                            denoise::toggle_denoise_system,
+                            integrator::toggle_integrator_mode_system,
+                            integrator::light_samples_system,
+                            integrator::max_depth_system,
+                        )
+                            .in_set(InputSet::Keyboard),
+                        (
+                            (
+                                sky::sky_intensity_system,
+                                sky::toggle_studio_sky_system,
+                                sky::toggle_ambient_system,
+                            )
+                                .in_set(InputSet::Keyboard),
+                            sky::reset_on_sky_change,
+                        )
+                            .chain(),
+                        (
+                            (sun::toggle_sun_system, sun::rotate_sun_system)
+                                .in_set(InputSet::Keyboard),
+                            sun::reset_on_sun_change,
+                        )
+                            .chain(),
+                        resize_render_targets,
+                        (
+                            reset_to_defaults,
+                            step::step_mode_system,
+                            tiles::toggle_tiled_rendering_system,
+                            turntable::turntable_system,
+                            contact_sheet::contact_sheet_system.after(step::step_mode_system),
+                            selection::cycle_selection_system,
+                            output::toggle_capture_format_system,
+                            output::save_frame_system,
+                        )
+                            .in_set(InputSet::Keyboard),
+                    ),
+                    step::sample_budget_system,
+                    tiles::advance_tile_system,
+                    views::update_view_cameras_system,
+                    camera::extract_camera,
+                )
+                    .chain(),
+            );
+
+        if let Some(scene) = &self.scene {
+            app.insert_resource(scene.clone());
+        }
+        let (samples_per_pixel, fov, defocus_angle, focus_distance) = (
+            self.samples_per_pixel,
+            self.fov,
+            self.defocus_angle,
+            self.focus_distance,
+        );
+        app.add_systems(
+            Startup,
+            (
+                (move |mut camera_settings: ResMut<camera::CameraSettings>| {
+                    if let Some(samples_per_pixel) = samples_per_pixel {
+                        camera_settings.samples_per_pixel = samples_per_pixel;
+                    }
+                    if let Some(fov) = fov {
+                        camera_settings.projection = camera::LensProjection::Perspective { fov };
+                    }
+                    if let Some(defocus_angle) = defocus_angle {
+                        camera_settings.defocus_angle = defocus_angle;
+                    }
+                    if let Some(focus_distance) = focus_distance {
+                        camera_settings.focus_distance = focus_distance;
+                    }
+                }),
+                camera::record_starting_view,
+            )
+                .chain()
+                .after(setup),
+        );
+        if !self.controls {
+            app.configure_sets(
+                Update,
+                (InputSet::Pointer, InputSet::Keyboard, InputSet::Gamepad).run_if(|| false),
+            );
+        }
+        if self.display {
+            app.insert_resource(ClearColor(Color::BLACK)).add_systems(
+                Startup,
+                (setup_display.after(setup), display::setup_exposure_label),
+            );
+        }
+        #[cfg(feature = "ui")]
+        app.add_plugins(ui::UiPlugin);
+    }
+}
@@ -0,0 +1,55 @@
+use bevy::{
+    prelude::*,
+    render::{
+        extract_resource::ExtractResource,
+        gpu_readback::{Readback, ReadbackComplete},
+        render_asset::RenderAssetUsages,
+        render_resource::BufferUsages,
+        storage::ShaderStorageBuffer,
+    },
+};
+
+// A single pixel of the accumulated image that is read back to the CPU every frame
+#[derive(Resource, Debug, Clone)]
+pub struct PixelProbe {
+    // Pixel on the render target, with (0, 0) at the upper left corner
+    pub location: UVec2,
+    // Accumulated color at the location; w holds the sample count
+    pub color: Vec4,
+}
+
+impl PixelProbe {
+    // Relative luminance of the probed color (Rec. 709 weights)
+    pub fn luminance(&self) -> f32 {
+        self.color.truncate().dot(Vec3::new(0.2126, 0.7152, 0.0722))
+    }
+}
+
+// Storage buffer the compute shader copies the probed pixel into
+#[derive(Resource, Clone, ExtractResource)]
+pub struct PixelProbeBuffer(pub Handle<ShaderStorageBuffer>);
+
+pub fn setup_probe(
+    mut commands: Commands,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    render_size: Res<crate::RenderSize>,
+) {
+    let mut buffer = ShaderStorageBuffer::from(Vec4::ZERO);
+    buffer.buffer_description.usage |= BufferUsages::COPY_SRC;
+    buffer.asset_usage = RenderAssetUsages::RENDER_WORLD;
+    let buffer = buffers.add(buffer);
+
+    // The readback entity is kept alive, so the probe is refreshed every frame
+    commands.spawn(Readback::buffer(buffer.clone())).observe(
+        |trigger: Trigger<ReadbackComplete>, mut probe: ResMut<PixelProbe>| {
+            probe.color = trigger.event().to_shader_type();
+        },
+    );
+
+    // Start in the center of the image
+    commands.insert_resource(PixelProbe {
+        location: **render_size / 2,
+        color: Vec4::ZERO,
+    });
+    commands.insert_resource(PixelProbeBuffer(buffer));
+}
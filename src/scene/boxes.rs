@@ -0,0 +1,68 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::sphere::GpuMaterial;
+
+// Most boxes a scene can hold: the storage buffer has to fit in wgpu's default 128 MiB
+// limit for a single binding
+pub const MAX_BOXES: usize = (128 << 20) / std::mem::size_of::<GpuBox>();
+
+// Axis-aligned box between two opposite corners. Uses the same material encoding as
+// spheres, so boxes can be diffuse, metal, glass or emissive.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, ShaderType)]
+pub struct GpuBox {
+    pub min: Vec3,
+    pub _padding0: f32,
+    pub max: Vec3,
+    pub _padding1: f32,
+    pub material: GpuMaterial,
+}
+
+impl GpuBox {
+    // A box spanning the two corners, given in any order
+    pub fn new(corner_a: Vec3, corner_b: Vec3, material: GpuMaterial) -> Self {
+        Self {
+            min: corner_a.min(corner_b),
+            _padding0: 0.0,
+            max: corner_a.max(corner_b),
+            _padding1: 0.0,
+            material,
+        }
+    }
+}
+
+// Resource to hold all boxes in the scene
+#[derive(Resource, Clone, ExtractResource)]
+pub struct BoxCollection {
+    pub boxes: Vec<GpuBox>,
+    pub count: u32,
+}
+
+impl Default for BoxCollection {
+    fn default() -> Self {
+        Self::from_boxes(Vec::new())
+    }
+}
+
+impl BoxCollection {
+    // Build a collection from a list of boxes; only these are uploaded to the GPU
+    pub fn from_boxes(boxes: Vec<GpuBox>) -> Self {
+        let mut collection = Self {
+            count: boxes.len() as u32,
+            boxes,
+        };
+
+        // Storage buffers can't be empty, so no boxes uploads one the shader never reads
+        if collection.boxes.is_empty() {
+            collection
+                .boxes
+                .push(GpuBox::new(Vec3::ZERO, Vec3::ZERO, GpuMaterial::zeroed()));
+        }
+
+        collection
+    }
+}
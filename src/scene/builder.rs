@@ -0,0 +1,349 @@
+use bevy::prelude::*;
+
+use super::{
+    boxes::{BoxCollection, GpuBox, MAX_BOXES},
+    disk::{DiskCollection, GpuDisk, MAX_DISKS},
+    instances::{SphereInstances, MAX_INSTANCE_GROUPS},
+    loader::SceneLoadError,
+    mesh::MeshCollection,
+    plane::{GpuPlane, PlaneCollection, MAX_PLANES},
+    quad::{GpuQuad, QuadCollection, MAX_QUADS},
+    sdf::{GpuSdfPrimitive, SdfCollection, MAX_SDF_PRIMITIVES},
+    sphere::{GpuMaterial, GpuSphere, SphereCollection, SurfaceMaterial, MAX_SPHERES},
+};
+use crate::{
+    camera::{CameraSettings, LensProjection},
+    sky::SkySettings,
+    sun::SunLight,
+};
+
+// A complete scene built from Rust, used in place of the startup scene and default camera.
+// Pass it to `run_scene` or RayTracingPluginBuilder::scene.
+#[derive(Resource, Clone)]
+pub struct RayTracedScene {
+    pub spheres: SphereCollection,
+    pub boxes: BoxCollection,
+    pub mesh: MeshCollection,
+    pub planes: PlaneCollection,
+    pub quads: QuadCollection,
+    pub disks: DiskCollection,
+    pub instances: SphereInstances,
+    pub sdfs: SdfCollection,
+    pub camera: CameraSettings,
+    // Sky the scene starts with; U and the bracket keys still change it
+    pub sky: SkySettings,
+    // Sun the scene starts with, off unless set; End and ; / ' still change it
+    pub sun: SunLight,
+    // Seed of the pattern on noise materials
+    pub noise_seed: u64,
+}
+
+// Just the spheres, such as a loaded scene file, seen from the default camera
+impl From<SphereCollection> for RayTracedScene {
+    fn from(spheres: SphereCollection) -> Self {
+        Self {
+            spheres,
+            boxes: BoxCollection::default(),
+            mesh: MeshCollection::default(),
+            planes: PlaneCollection::default(),
+            quads: QuadCollection::default(),
+            disks: DiskCollection::default(),
+            instances: SphereInstances::default(),
+            sdfs: SdfCollection::default(),
+            camera: CameraSettings::default(),
+            sky: SkySettings::default(),
+            sun: SunLight::default(),
+            noise_seed: 0,
+        }
+    }
+}
+
+// Fluent builder for a RayTracedScene:
+//
+// SceneBuilder::new()
+//     .add_plane(Vec3::ZERO, Vec3::Y, SurfaceMaterial::Lambertian(Vec3::splat(0.5)))
+//     .add_light(Vec3::new(0.0, 3.0, 0.0), 0.5, Vec3::ONE, 10.0)
+//     .camera(Vec3::new(13.0, 2.0, 3.0), Vec3::ZERO, 20.0)
+//     .build()
+#[derive(Default)]
+pub struct SceneBuilder {
+    spheres: Vec<(GpuSphere, GpuMaterial)>,
+    boxes: Vec<GpuBox>,
+    mesh: MeshCollection,
+    planes: Vec<GpuPlane>,
+    quads: Vec<GpuQuad>,
+    disks: Vec<GpuDisk>,
+    instances: Vec<(f32, GpuMaterial, Vec<Vec3>)>,
+    sdfs: Vec<GpuSdfPrimitive>,
+    camera: CameraSettings,
+    sky: SkySettings,
+    sun: SunLight,
+    noise_seed: u64,
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_sphere(mut self, center: Vec3, radius: f32, material: SurfaceMaterial) -> Self {
+        self.spheres
+            .push((GpuSphere::new(center, radius), material.to_gpu()));
+        self
+    }
+
+    // Add a sphere that moves from `center` to `center + velocity` over the frame
+    // interval, rendering as a motion-blurred streak
+    pub fn add_moving_sphere(
+        mut self,
+        center: Vec3,
+        radius: f32,
+        velocity: Vec3,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.spheres.push((
+            GpuSphere::new(center, radius).with_velocity(velocity),
+            material.to_gpu(),
+        ));
+        self
+    }
+
+    // Add spheres of the same radius and material at each of `centers`, stored as 12-byte
+    // instances instead of full spheres; see SphereInstances
+    pub fn add_sphere_instances(
+        mut self,
+        radius: f32,
+        material: SurfaceMaterial,
+        centers: impl IntoIterator<Item = Vec3>,
+    ) -> Self {
+        self.instances
+            .push((radius, material.to_gpu(), centers.into_iter().collect()));
+        self
+    }
+
+    // Add an axis-aligned box spanning two opposite corners
+    pub fn add_box(mut self, corner_a: Vec3, corner_b: Vec3, material: SurfaceMaterial) -> Self {
+        self.boxes
+            .push(GpuBox::new(corner_a, corner_b, material.to_gpu()));
+        self
+    }
+
+    // Add an infinite plane through `point`, facing along `normal`
+    pub fn add_plane(mut self, point: Vec3, normal: Vec3, material: SurfaceMaterial) -> Self {
+        self.planes
+            .push(GpuPlane::new(point, normal, material.to_gpu()));
+        self
+    }
+
+    // Add a parallelogram with a corner at `q` and edges `u` and `v`, facing along u × v
+    pub fn add_quad(mut self, q: Vec3, u: Vec3, v: Vec3, material: SurfaceMaterial) -> Self {
+        self.quads.push(GpuQuad::new(q, u, v, material.to_gpu()));
+        self
+    }
+
+    // Add a rectangle spanning two opposite corners that share one coordinate, such as a
+    // wall, see GpuQuad::axis_aligned
+    pub fn add_axis_aligned_quad(
+        mut self,
+        corner_a: Vec3,
+        corner_b: Vec3,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.quads
+            .push(GpuQuad::axis_aligned(corner_a, corner_b, material.to_gpu()));
+        self
+    }
+
+    // Add a disk of `radius` around `center`, facing along `normal`, such as a round light
+    pub fn add_disk(
+        mut self,
+        center: Vec3,
+        normal: Vec3,
+        radius: f32,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.disks
+            .push(GpuDisk::new(center, normal, radius, material.to_gpu()));
+        self
+    }
+
+    // Add a ring between `inner_radius` and `radius`, such as a ring light
+    pub fn add_ring(
+        mut self,
+        center: Vec3,
+        normal: Vec3,
+        inner_radius: f32,
+        radius: f32,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.disks.push(GpuDisk::ring(
+            center,
+            normal,
+            inner_radius,
+            radius,
+            material.to_gpu(),
+        ));
+        self
+    }
+
+    // Add an SDF sphere that blends into the SDF primitives added before it over `blend`,
+    // like a metaball; see GpuSdfPrimitive
+    pub fn add_sdf_sphere(
+        mut self,
+        center: Vec3,
+        radius: f32,
+        blend: f32,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.sdfs
+            .push(GpuSdfPrimitive::sphere(center, radius, material.to_gpu()).with_blend(blend));
+        self
+    }
+
+    // Add an SDF box reaching `half_size` from its center, blending in like add_sdf_sphere
+    pub fn add_sdf_box(
+        mut self,
+        center: Vec3,
+        half_size: Vec3,
+        blend: f32,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.sdfs
+            .push(GpuSdfPrimitive::cuboid(center, half_size, material.to_gpu()).with_blend(blend));
+        self
+    }
+
+    // Add an SDF torus lying flat around `center`, blending in like add_sdf_sphere
+    pub fn add_sdf_torus(
+        mut self,
+        center: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        blend: f32,
+        material: SurfaceMaterial,
+    ) -> Self {
+        self.sdfs.push(
+            GpuSdfPrimitive::torus(center, major_radius, minor_radius, material.to_gpu())
+                .with_blend(blend),
+        );
+        self
+    }
+
+    // Use a triangle mesh, such as one from load_obj
+    pub fn mesh(mut self, mesh: MeshCollection) -> Self {
+        self.mesh = mesh;
+        self
+    }
+
+    // Add a light-emitting sphere whose radiance is color * intensity
+    pub fn add_light(self, center: Vec3, radius: f32, color: Vec3, intensity: f32) -> Self {
+        self.add_sphere(center, radius, SurfaceMaterial::emissive(color, intensity))
+    }
+
+    // Place the camera; the remaining camera settings keep their defaults
+    pub fn camera(mut self, look_from: Vec3, look_at: Vec3, field_of_view: f32) -> Self {
+        self.camera.look_from = look_from;
+        self.camera.look_at = look_at;
+        self.camera.projection = LensProjection::Perspective { fov: field_of_view };
+        self
+    }
+
+    // Depth of field; an angle of 0 disables it
+    pub fn defocus(mut self, defocus_angle: f32, focus_distance: f32) -> Self {
+        self.camera.defocus_angle = defocus_angle;
+        self.camera.focus_distance = focus_distance;
+        self
+    }
+
+    // Part of the frame interval the shutter is open for, both in 0..=1; moving spheres
+    // blur over the distance they cover in it
+    pub fn shutter(mut self, open: f32, close: f32) -> Self {
+        self.camera.shutter_open = open;
+        self.camera.shutter_close = close;
+        self
+    }
+
+    // Sky shown where rays escape, in place of the default gradient
+    pub fn sky(mut self, sky: SkySettings) -> Self {
+        self.sky = sky;
+        self
+    }
+
+    // Light the scene with a sun, see SunLight::new
+    pub fn sun(mut self, sun: SunLight) -> Self {
+        self.sun = sun;
+        self
+    }
+
+    // Seed the pattern on noise materials, which otherwise uses seed 0
+    pub fn noise_seed(mut self, seed: u64) -> Self {
+        self.noise_seed = seed;
+        self
+    }
+
+    pub fn samples_per_pixel(mut self, samples_per_pixel: u32) -> Self {
+        self.camera.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    pub fn build(self) -> Result<RayTracedScene, SceneLoadError> {
+        if self.spheres.len() > MAX_SPHERES {
+            return Err(SceneLoadError::TooManySpheres {
+                count: self.spheres.len(),
+                max: MAX_SPHERES,
+            });
+        }
+        if self.planes.len() > MAX_PLANES {
+            return Err(SceneLoadError::TooManyPlanes {
+                count: self.planes.len(),
+                max: MAX_PLANES,
+            });
+        }
+        if self.boxes.len() > MAX_BOXES {
+            return Err(SceneLoadError::TooManyBoxes {
+                count: self.boxes.len(),
+                max: MAX_BOXES,
+            });
+        }
+
+        if self.quads.len() > MAX_QUADS {
+            return Err(SceneLoadError::TooManyQuads {
+                count: self.quads.len(),
+                max: MAX_QUADS,
+            });
+        }
+        if self.disks.len() > MAX_DISKS {
+            return Err(SceneLoadError::TooManyDisks {
+                count: self.disks.len(),
+                max: MAX_DISKS,
+            });
+        }
+        if self.instances.len() > MAX_INSTANCE_GROUPS {
+            return Err(SceneLoadError::TooManyInstanceGroups {
+                count: self.instances.len(),
+                max: MAX_INSTANCE_GROUPS,
+            });
+        }
+        if self.sdfs.len() > MAX_SDF_PRIMITIVES {
+            return Err(SceneLoadError::TooManySdfPrimitives {
+                count: self.sdfs.len(),
+                max: MAX_SDF_PRIMITIVES,
+            });
+        }
+
+        Ok(RayTracedScene {
+            spheres: SphereCollection::from_spheres(self.spheres),
+            boxes: BoxCollection::from_boxes(self.boxes),
+            mesh: self.mesh,
+            planes: PlaneCollection::from_planes(self.planes),
+            quads: QuadCollection::from_quads(self.quads),
+            disks: DiskCollection::from_disks(self.disks),
+            instances: SphereInstances::from_groups(self.instances),
+            sdfs: SdfCollection::from_primitives(self.sdfs),
+            camera: self.camera,
+            sky: self.sky,
+            sun: self.sun,
+            noise_seed: self.noise_seed,
+        })
+    }
+}
@@ -0,0 +1,148 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::sphere::{GpuSphere, SphereCollection};
+
+// One node of a bounding volume hierarchy over the spheres, flattened for the GPU. Inner
+// nodes have `count` 0 and their children at `left_or_primitive` and the next index.
+// Leaves hold a single sphere, with `count` 1 and the sphere index in `left_or_primitive`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuBvhNode {
+    pub aabb_min: Vec3,
+    pub left_or_primitive: u32,
+    pub aabb_max: Vec3,
+    pub count: u32,
+}
+
+// Hierarchy over the spheres in SphereCollection; node 0 is the root
+#[derive(Resource, Clone, ExtractResource)]
+pub struct SphereBvh {
+    pub nodes: Vec<GpuBvhNode>,
+    // When false the shader tests every sphere, for comparing speed
+    pub enabled: bool,
+}
+
+impl Default for SphereBvh {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            enabled: true,
+        }
+    }
+}
+
+impl SphereBvh {
+    // Build a hierarchy over the spheres in use, splitting each node at the median
+    // sphere along the axis where the sphere centers are spread widest. The tree is
+    // balanced, so its depth stays within the shader's traversal stack.
+    pub fn build(spheres: &SphereCollection) -> Self {
+        let spheres = &spheres.spheres[..spheres.count as usize];
+        let mut nodes = Vec::new();
+        if !spheres.is_empty() {
+            let mut indices: Vec<u32> = (0..spheres.len() as u32).collect();
+            nodes.push(GpuBvhNode::zeroed());
+            build_node(&mut nodes, 0, spheres, &mut indices);
+        }
+        Self { nodes, ..default() }
+    }
+}
+
+fn build_node(
+    nodes: &mut Vec<GpuBvhNode>,
+    node: usize,
+    spheres: &[GpuSphere],
+    indices: &mut [u32],
+) {
+    let (mut aabb_min, mut aabb_max) = (Vec3::INFINITY, Vec3::NEG_INFINITY);
+    let (mut center_min, mut center_max) = (Vec3::INFINITY, Vec3::NEG_INFINITY);
+    for &index in indices.iter() {
+        // Moving spheres are bounded over their whole path
+        let (sphere_min, sphere_max) = spheres[index as usize].swept_bounds();
+        aabb_min = aabb_min.min(sphere_min);
+        aabb_max = aabb_max.max(sphere_max);
+        let center = (sphere_min + sphere_max) / 2.0;
+        center_min = center_min.min(center);
+        center_max = center_max.max(center);
+    }
+
+    if let [index] = indices {
+        nodes[node] = GpuBvhNode {
+            aabb_min,
+            left_or_primitive: *index,
+            aabb_max,
+            count: 1,
+        };
+        return;
+    }
+
+    let extent = center_max - center_min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let middle = indices.len() / 2;
+    indices.select_nth_unstable_by(middle, |&a, &b| {
+        let (a, b) = (&spheres[a as usize], &spheres[b as usize]);
+        let a = a.position[axis] + a.velocity[axis] / 2.0;
+        let b = b.position[axis] + b.velocity[axis] / 2.0;
+        a.total_cmp(&b)
+    });
+
+    let left = nodes.len();
+    nodes.extend([GpuBvhNode::zeroed(); 2]);
+    nodes[node] = GpuBvhNode {
+        aabb_min,
+        left_or_primitive: left as u32,
+        aabb_max,
+        count: 0,
+    };
+    let (first, second) = indices.split_at_mut(middle);
+    build_node(nodes, left, spheres, first);
+    build_node(nodes, left + 1, spheres, second);
+}
+
+/// System to rebuild the hierarchy when spheres move, appear or disappear. Material edits
+/// don't change the tree, so they don't trigger a rebuild.
+pub fn update_sphere_bvh_system(
+    spheres: Res<SphereCollection>,
+    mut bvh: ResMut<SphereBvh>,
+    mut geometry: Local<Vec<(Vec4, Vec3)>>,
+) {
+    if !spheres.is_changed() {
+        return;
+    }
+    let current: Vec<(Vec4, Vec3)> = spheres
+        .spheres
+        .iter()
+        .take(spheres.count as usize)
+        .map(|sphere| (sphere.position.extend(sphere.radius), sphere.velocity))
+        .collect();
+    if *geometry == current && !bvh.nodes.is_empty() {
+        return;
+    }
+
+    let enabled = bvh.enabled;
+    *bvh = SphereBvh {
+        enabled,
+        ..SphereBvh::build(&spheres)
+    };
+    *geometry = current;
+}
+
+/// System to switch between the hierarchy and testing every sphere with J
+pub fn toggle_sphere_bvh_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut bvh: ResMut<SphereBvh>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyJ) {
+        bvh.enabled = !bvh.enabled;
+        info!("Sphere BVH: {}", bvh.enabled);
+    }
+}
@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+
+use super::builder::{RayTracedScene, SceneBuilder};
+use super::sphere::SurfaceMaterial;
+use crate::sky::SkySettings;
+
+// Side of the box, in the units of the original measurements
+const SIZE: f32 = 555.0;
+
+// The Cornell box: a red wall on the left, a green one on the right, a white floor,
+// ceiling and back wall, a light panel in the ceiling and a tall and a short box turned
+// towards each other. Nothing but the panel lights it, so the colors bleeding from the
+// walls onto the boxes and the soft shadows under them all come from indirect light.
+pub fn create_cornell_box() -> RayTracedScene {
+    let red = SurfaceMaterial::Lambertian(Vec3::new(0.65, 0.05, 0.05));
+    let white = SurfaceMaterial::Lambertian(Vec3::splat(0.73));
+    let green = SurfaceMaterial::Lambertian(Vec3::new(0.12, 0.45, 0.15));
+
+    // The camera looks along +z, which puts +x on the left of the image
+    let builder = SceneBuilder::new()
+        .add_axis_aligned_quad(Vec3::new(SIZE, 0.0, 0.0), Vec3::splat(SIZE), red)
+        .add_axis_aligned_quad(Vec3::ZERO, Vec3::new(0.0, SIZE, SIZE), green)
+        .add_axis_aligned_quad(Vec3::ZERO, Vec3::new(SIZE, 0.0, SIZE), white)
+        .add_axis_aligned_quad(Vec3::new(0.0, SIZE, 0.0), Vec3::splat(SIZE), white)
+        .add_axis_aligned_quad(Vec3::new(0.0, 0.0, SIZE), Vec3::splat(SIZE), white)
+        // Just below the ceiling, so the two don't fight over the same depth
+        .add_axis_aligned_quad(
+            Vec3::new(213.0, 554.0, 227.0),
+            Vec3::new(343.0, 554.0, 332.0),
+            SurfaceMaterial::emissive(Vec3::ONE, 15.0),
+        );
+    let builder = add_turned_box(
+        builder,
+        Vec3::new(165.0, 330.0, 165.0),
+        15.0,
+        Vec3::new(265.0, 0.0, 295.0),
+        white,
+    );
+    let builder = add_turned_box(
+        builder,
+        Vec3::splat(165.0),
+        -18.0,
+        Vec3::new(130.0, 0.0, 65.0),
+        white,
+    );
+
+    builder
+        .camera(
+            Vec3::new(278.0, 278.0, -800.0),
+            Vec3::new(278.0, 278.0, 0.0),
+            40.0,
+        )
+        .defocus(0.0, 800.0)
+        .sky(SkySettings {
+            top_color: Vec3::ZERO,
+            bottom_color: Vec3::ZERO,
+            ..default()
+        })
+        .build()
+        .expect("Cornell box fits in the buffers")
+}
+
+// Add a box of `size` with a corner at the origin, turned by `degrees` about the y axis
+// and then moved by `offset`. Boxes can't be rotated, so it is made of six quads.
+fn add_turned_box(
+    builder: SceneBuilder,
+    size: Vec3,
+    degrees: f32,
+    offset: Vec3,
+    material: SurfaceMaterial,
+) -> SceneBuilder {
+    let rotation = Quat::from_rotation_y(degrees.to_radians());
+    let (dx, dy, dz) = (size.x * Vec3::X, size.y * Vec3::Y, size.z * Vec3::Z);
+    let faces = [
+        (dz, dx, dy),
+        (dx + dz, -dz, dy),
+        (dx, -dx, dy),
+        (Vec3::ZERO, dz, dy),
+        (dy + dz, dx, -dz),
+        (Vec3::ZERO, dx, dz),
+    ];
+    faces.into_iter().fold(builder, |builder, (q, u, v)| {
+        builder.add_quad(rotation * q + offset, rotation * u, rotation * v, material)
+    })
+}
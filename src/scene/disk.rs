@@ -0,0 +1,81 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::sphere::GpuMaterial;
+
+// Most disks a scene can hold: the storage buffer has to fit in wgpu's default 128 MiB
+// limit for a single binding
+pub const MAX_DISKS: usize = (128 << 20) / std::mem::size_of::<GpuDisk>();
+
+// Largest inner radius of a ring, as a fraction of its outer one
+const RING_MAX_INNER: f32 = 0.999;
+
+// Flat disk around `center`, facing along `normal`, such as a round ceiling light. With an
+// inner radius above 0 it is a ring, hollow inside that radius.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuDisk {
+    pub center: Vec3,
+    pub radius: f32,
+    pub normal: Vec3,
+    pub inner_radius: f32,
+    pub material: GpuMaterial,
+}
+
+impl GpuDisk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, material: GpuMaterial) -> Self {
+        Self::ring(center, normal, 0.0, radius, material)
+    }
+
+    // Annulus between `inner_radius` and `radius`. The inner radius is clamped below the
+    // outer one, so a ring always has some area to hit.
+    pub fn ring(
+        center: Vec3,
+        normal: Vec3,
+        inner_radius: f32,
+        radius: f32,
+        material: GpuMaterial,
+    ) -> Self {
+        let radius = radius.abs();
+        Self {
+            center,
+            radius,
+            normal: normal.normalize_or(Vec3::Y),
+            inner_radius: inner_radius.clamp(0.0, radius * RING_MAX_INNER),
+            material,
+        }
+    }
+}
+
+// Resource to hold all disks in the scene
+#[derive(Resource, Clone, PartialEq, ExtractResource)]
+pub struct DiskCollection {
+    pub disks: Vec<GpuDisk>,
+    pub count: u32,
+}
+
+impl Default for DiskCollection {
+    fn default() -> Self {
+        Self::from_disks(Vec::new())
+    }
+}
+
+impl DiskCollection {
+    // Build a collection from a list of disks; only these are uploaded to the GPU
+    pub fn from_disks(disks: Vec<GpuDisk>) -> Self {
+        let mut collection = Self {
+            count: disks.len() as u32,
+            disks,
+        };
+
+        // Storage buffers can't be empty, so no disks uploads one the shader never reads
+        if collection.disks.is_empty() {
+            collection.disks.push(GpuDisk::zeroed());
+        }
+
+        collection
+    }
+}
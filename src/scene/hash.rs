@@ -0,0 +1,300 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{
+    boxes::BoxCollection,
+    builder::RayTracedScene,
+    disk::DiskCollection,
+    instances::SphereInstances,
+    mesh::MeshCollection,
+    noise::NoiseSettings,
+    plane::PlaneCollection,
+    quad::QuadCollection,
+    sdf::SdfCollection,
+    sphere::{GpuMaterial, MaterialKind, SphereCollection},
+};
+use crate::{
+    camera::{CameraSettings, LensProjection},
+    sky::SkySettings,
+    sun::SunLight,
+};
+
+// 64-bit FNV-1a, which unlike std's hashers is guaranteed to give the same value on every
+// platform and Rust version
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_f32(&mut self, value: f32) {
+        self.write(&value.to_bits().to_le_bytes());
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    fn write_vec3(&mut self, value: Vec3) {
+        for component in value.to_array() {
+            self.write_f32(component);
+        }
+    }
+
+    fn write_material(&mut self, material: &GpuMaterial) {
+        let kind = material.kind();
+        self.write_u32(kind as u32);
+        let color = material.color;
+        let parameters = match kind {
+            MaterialKind::Lambertian | MaterialKind::Emissive => {
+                color.truncate().to_array().to_vec()
+            }
+            // w holds the fuzz or the noise scale
+            MaterialKind::Metal | MaterialKind::Noise => color.to_array().to_vec(),
+            MaterialKind::Dielectric => vec![color.x],
+        };
+        for value in parameters {
+            self.write_f32(value);
+        }
+        // Only tinted glass, so clear glass hashes as it did before absorption
+        if material.absorption != Vec3::ZERO {
+            self.write_vec3(material.absorption);
+        }
+    }
+}
+
+// Stable hash of everything that determines the rendered image: the geometry, the camera,
+// the sky, the sun and the render settings. Transient state such as accumulation progress
+// or a material animation in flight is left out, as are encoding details that don't change
+// the look (e.g. which `w` below -1 marks a material as diffuse). Shapes that came after
+// the spheres are only hashed when the scene has some, so sphere-only scenes keep their
+// hash.
+pub fn scene_hash(scene: &RayTracedScene) -> u64 {
+    let RayTracedScene {
+        spheres,
+        camera,
+        sky,
+        sun,
+        ..
+    } = scene;
+    let mut hasher = Fnv1a::new();
+
+    hasher.write_u32(spheres.count);
+    for (sphere, material) in spheres.pairs() {
+        hasher.write_vec3(sphere.position);
+        hasher.write_f32(sphere.radius);
+        // Only moving spheres, so static scenes hash as they did before motion blur
+        if sphere.velocity != Vec3::ZERO {
+            hasher.write_vec3(sphere.velocity);
+        }
+        hasher.write_material(&material);
+    }
+
+    if scene.planes.count > 0 {
+        hasher.write_u32(scene.planes.count);
+        for plane in scene.planes.planes.iter().take(scene.planes.count as usize) {
+            hasher.write_vec3(plane.point);
+            hasher.write_vec3(plane.normal);
+            hasher.write_material(&plane.material);
+        }
+    }
+    if scene.boxes.count > 0 {
+        hasher.write_u32(scene.boxes.count);
+        for aabb in scene.boxes.boxes.iter().take(scene.boxes.count as usize) {
+            hasher.write_vec3(aabb.min);
+            hasher.write_vec3(aabb.max);
+            hasher.write_material(&aabb.material);
+        }
+    }
+    if scene.quads.count > 0 {
+        hasher.write_u32(scene.quads.count);
+        for quad in scene.quads.quads.iter().take(scene.quads.count as usize) {
+            hasher.write_vec3(quad.q);
+            hasher.write_vec3(quad.u);
+            hasher.write_vec3(quad.v);
+            hasher.write_material(&quad.material);
+        }
+    }
+    if scene.disks.count > 0 {
+        hasher.write_u32(scene.disks.count);
+        for disk in scene.disks.disks.iter().take(scene.disks.count as usize) {
+            hasher.write_vec3(disk.center);
+            hasher.write_f32(disk.radius);
+            hasher.write_vec3(disk.normal);
+            hasher.write_f32(disk.inner_radius);
+            hasher.write_material(&disk.material);
+        }
+    }
+    let instances = &scene.instances;
+    if instances.count > 0 {
+        hasher.write_u32(instances.count);
+        for group in instances.groups.iter().take(instances.count as usize) {
+            hasher.write_f32(group.radius);
+            hasher.write_material(&group.material);
+            hasher.write_u32(group.count);
+            let first = group.first as usize;
+            for &center in &instances.positions[first..first + group.count as usize] {
+                hasher.write_vec3(center);
+            }
+        }
+    }
+    if scene.sdfs.count > 0 {
+        hasher.write_u32(scene.sdfs.count);
+        for primitive in scene.sdfs.primitives.iter().take(scene.sdfs.count as usize) {
+            hasher.write_u32(primitive.kind);
+            hasher.write_vec3(primitive.center);
+            hasher.write_vec3(primitive.size);
+            hasher.write_f32(primitive.blend);
+            hasher.write_material(&primitive.material);
+        }
+    }
+    let mesh = &scene.mesh;
+    if mesh.count > 0 {
+        hasher.write_u32(mesh.count);
+        hasher.write_u32(mesh.smooth_normals as u32);
+        let triangles = mesh.triangles.iter().zip(&mesh.normals);
+        for (triangle, normals) in triangles.take(mesh.count as usize) {
+            for corner in [triangle.v0, triangle.v1, triangle.v2] {
+                hasher.write_vec3(corner);
+            }
+            // The corner normals only show on smooth shaded triangles
+            if mesh.smooth_normals && triangle.smooth_shading != 0 {
+                for normal in [normals.n0, normals.n1, normals.n2] {
+                    hasher.write_vec3(normal);
+                }
+            }
+            if let Some(material) = mesh.materials.get(triangle.material_index as usize) {
+                hasher.write_material(material);
+            }
+        }
+    }
+    // The noise seed only shows on noise materials, which use seed 0 unless told otherwise
+    if scene.noise_seed != 0 {
+        hasher.write(&scene.noise_seed.to_le_bytes());
+    }
+
+    for vector in [camera.look_from, camera.look_at, camera.vup] {
+        for value in vector.to_array() {
+            hasher.write_f32(value);
+        }
+    }
+    match camera.projection {
+        LensProjection::Perspective { fov } => {
+            hasher.write_u32(0);
+            hasher.write_f32(fov);
+        }
+        LensProjection::Orthographic { height } => {
+            hasher.write_u32(1);
+            hasher.write_f32(height);
+        }
+    }
+    hasher.write_f32(camera.defocus_angle);
+    hasher.write_f32(camera.focus_distance);
+    hasher.write_u32(camera.samples_per_pixel);
+    hasher.write_u32(camera.max_depth);
+    hasher.write_u32(camera.edge_antialiasing as u32);
+    hasher.write_u32(camera.stratified_sampling as u32);
+    hasher.write_u32(camera.min_bounces_before_rr);
+    hasher.write_u32(camera.light_samples);
+    hasher.write_f32(camera.variance_threshold);
+    // The shutter only matters once something moves
+    if spheres
+        .pairs()
+        .any(|(sphere, _)| sphere.velocity != Vec3::ZERO)
+    {
+        hasher.write_f32(camera.shutter_open);
+        hasher.write_f32(camera.shutter_close);
+    }
+
+    hasher.write_f32(sky.intensity);
+    for vector in [sky.top_color, sky.bottom_color] {
+        for value in vector.to_array() {
+            hasher.write_f32(value);
+        }
+    }
+    hasher.write_f32(sky.gradient_power);
+    // Likewise the ambient light, so scenes without one keep their hash
+    if sky.ambient != Vec3::ZERO {
+        for value in sky.ambient.to_array() {
+            hasher.write_f32(value);
+        }
+    }
+
+    // Only a sun that is on lights the scene
+    if sun.enabled {
+        hasher.write_vec3(sun.direction);
+        hasher.write_vec3(sun.color);
+        hasher.write_f32(sun.intensity);
+        hasher.write_f32(sun.angular_radius);
+    }
+
+    hasher.0
+}
+
+// The scene resources being rendered, gathered into a RayTracedScene for scene_hash
+#[derive(SystemParam)]
+pub struct LiveScene<'w> {
+    spheres: Res<'w, SphereCollection>,
+    boxes: Res<'w, BoxCollection>,
+    mesh: Res<'w, MeshCollection>,
+    planes: Res<'w, PlaneCollection>,
+    quads: Res<'w, QuadCollection>,
+    disks: Res<'w, DiskCollection>,
+    instances: Res<'w, SphereInstances>,
+    sdfs: Res<'w, SdfCollection>,
+    camera: Res<'w, CameraSettings>,
+    sky: Res<'w, SkySettings>,
+    sun: Res<'w, SunLight>,
+    noise: Res<'w, NoiseSettings>,
+}
+
+impl LiveScene<'_> {
+    fn to_scene(&self) -> RayTracedScene {
+        RayTracedScene {
+            spheres: self.spheres.clone(),
+            boxes: self.boxes.clone(),
+            mesh: self.mesh.clone(),
+            planes: self.planes.clone(),
+            quads: self.quads.clone(),
+            disks: self.disks.clone(),
+            instances: self.instances.clone(),
+            sdfs: self.sdfs.clone(),
+            camera: self.camera.clone(),
+            sky: self.sky.clone(),
+            sun: self.sun.clone(),
+            noise_seed: self.noise.seed,
+        }
+    }
+}
+
+/// System to log the scene hash on H, checking that the spheres survive a round trip
+/// through the scene file format unchanged
+pub fn log_scene_hash_system(keyboard_input: Res<ButtonInput<KeyCode>>, live: LiveScene) {
+    if !keyboard_input.just_pressed(KeyCode::KeyH) {
+        return;
+    }
+
+    let scene = live.to_scene();
+    let hash = scene_hash(&scene);
+    info!("Scene hash: {hash:016x}");
+
+    // Scene files only hold spheres, so only those are written and the rest of the scene
+    // is kept as it is
+    let spheres_only = RayTracedScene::from(scene.spheres.clone());
+    match super::loader::parse_scene(&super::loader::write_scene(&spheres_only)) {
+        Ok(spheres) => {
+            let reloaded = scene_hash(&RayTracedScene { spheres, ..scene });
+            if reloaded != hash {
+                warn!("Scene changed after saving and loading: {reloaded:016x}");
+            }
+        }
+        Err(err) => warn!("Saved scene could not be loaded: {err}"),
+    }
+}
@@ -0,0 +1,160 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::{bvh::GpuBvhNode, sphere::GpuMaterial};
+
+// Most instance groups a scene can hold: the storage buffer has to fit in wgpu's default
+// 128 MiB limit for a single binding
+pub const MAX_INSTANCE_GROUPS: usize = (128 << 20) / std::mem::size_of::<GpuInstanceGroup>();
+
+// Most instances a leaf of a group's hierarchy holds. Testing a few spheres in a row is
+// cheaper than the extra levels of a tree with one sphere per leaf.
+const INSTANCE_LEAF_SIZE: usize = 4;
+
+// Radius and material shared by a group of sphere instances, and where their positions
+// and their hierarchy are. Rays that miss the box around the whole group skip its
+// instances.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuInstanceGroup {
+    pub material: GpuMaterial,
+    pub aabb_min: Vec3,
+    pub radius: f32,
+    pub aabb_max: Vec3,
+    // Range of SphereInstances::positions holding the group's centers
+    pub first: u32,
+    pub count: u32,
+    // Root of the group's hierarchy in SphereInstances::nodes
+    pub root: u32,
+    pub _padding0: u32,
+    pub _padding1: u32,
+}
+
+// Resource to hold identical spheres that only differ in position, such as a particle
+// cloud. Each instance takes 12 bytes, its center, instead of a whole GpuSphere and its
+// material. Instances aren't in the sphere BVH but in a hierarchy of their group's own,
+// can't be picked or selected, and emissive ones aren't sampled as lights.
+#[derive(Resource, Clone, PartialEq, ExtractResource)]
+pub struct SphereInstances {
+    pub groups: Vec<GpuInstanceGroup>,
+    pub count: u32,
+    // Centers of every group's instances, one group after the other, in the order of
+    // their group's leaves
+    pub positions: Vec<Vec3>,
+    // Every group's hierarchy over its centers, one group after the other. Inner nodes are
+    // laid out as in SphereBvh; leaves hold up to INSTANCE_LEAF_SIZE instances, `count` of
+    // them from the position at `left_or_primitive`.
+    pub nodes: Vec<GpuBvhNode>,
+}
+
+impl Default for SphereInstances {
+    fn default() -> Self {
+        Self::from_groups(Vec::new())
+    }
+}
+
+impl SphereInstances {
+    // Build the instances from groups of (radius, material, centers); only these are
+    // uploaded to the GPU
+    pub fn from_groups(groups: Vec<(f32, GpuMaterial, Vec<Vec3>)>) -> Self {
+        let mut instances = Self {
+            groups: Vec::with_capacity(groups.len()),
+            count: groups.len() as u32,
+            positions: Vec::new(),
+            nodes: Vec::new(),
+        };
+
+        for (radius, material, mut centers) in groups {
+            let radius = radius.abs();
+            let (min, max) = centers.iter().fold(
+                (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                |(min, max), &center| (min.min(center), max.max(center)),
+            );
+            let first = instances.positions.len() as u32;
+            let root = instances.nodes.len();
+            if !centers.is_empty() {
+                instances.nodes.push(GpuBvhNode::zeroed());
+                build_node(&mut instances.nodes, root, &mut centers, first, radius);
+            }
+            instances.groups.push(GpuInstanceGroup {
+                material,
+                aabb_min: min - radius,
+                radius,
+                aabb_max: max + radius,
+                first,
+                count: centers.len() as u32,
+                root: root as u32,
+                ..GpuInstanceGroup::zeroed()
+            });
+            instances.positions.extend(centers);
+        }
+
+        // Storage buffers can't be empty, so give each empty one an entry the shader never
+        // reads
+        if instances.groups.is_empty() {
+            instances.groups.push(GpuInstanceGroup::zeroed());
+        }
+        if instances.positions.is_empty() {
+            instances.positions.push(Vec3::ZERO);
+        }
+        if instances.nodes.is_empty() {
+            instances.nodes.push(GpuBvhNode::zeroed());
+        }
+
+        instances
+    }
+}
+
+// Build the hierarchy over `centers`, the instances from position `first` on, splitting
+// each node at the median center along the axis where the centers are spread widest as
+// SphereBvh does. The centers are reordered so every leaf's instances are next to each
+// other.
+fn build_node(
+    nodes: &mut Vec<GpuBvhNode>,
+    node: usize,
+    centers: &mut [Vec3],
+    first: u32,
+    radius: f32,
+) {
+    let (center_min, center_max) = centers.iter().fold(
+        (Vec3::INFINITY, Vec3::NEG_INFINITY),
+        |(min, max), &center| (min.min(center), max.max(center)),
+    );
+    let (aabb_min, aabb_max) = (center_min - radius, center_max + radius);
+
+    if centers.len() <= INSTANCE_LEAF_SIZE {
+        nodes[node] = GpuBvhNode {
+            aabb_min,
+            left_or_primitive: first,
+            aabb_max,
+            count: centers.len() as u32,
+        };
+        return;
+    }
+
+    let extent = center_max - center_min;
+    let axis = if extent.x >= extent.y && extent.x >= extent.z {
+        0
+    } else if extent.y >= extent.z {
+        1
+    } else {
+        2
+    };
+    let middle = centers.len() / 2;
+    centers.select_nth_unstable_by(middle, |a, b| a[axis].total_cmp(&b[axis]));
+
+    let left = nodes.len();
+    nodes.extend([GpuBvhNode::zeroed(); 2]);
+    nodes[node] = GpuBvhNode {
+        aabb_min,
+        left_or_primitive: left as u32,
+        aabb_max,
+        count: 0,
+    };
+    let (before, after) = centers.split_at_mut(middle);
+    build_node(nodes, left, before, first, radius);
+    build_node(nodes, left + 1, after, first + middle as u32, radius);
+}
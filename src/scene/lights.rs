@@ -0,0 +1,65 @@
+use bevy::render::render_resource::ShaderType;
+use bytemuck::{Pod, Zeroable};
+
+use super::{
+    disk::DiskCollection,
+    quad::QuadCollection,
+    sphere::{MaterialKind, SphereCollection},
+};
+
+// Values of GpuLight::kind
+pub const LIGHT_SPHERE: u32 = 0;
+pub const LIGHT_QUAD: u32 = 1;
+pub const LIGHT_DISK: u32 = 2;
+
+// An emissive sphere, quad or disk that diffuse surfaces sample directly, see
+// CameraSettings::next_event_estimation
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Pod, Zeroable, ShaderType)]
+pub struct GpuLight {
+    // LIGHT_SPHERE, LIGHT_QUAD or LIGHT_DISK
+    pub kind: u32,
+    // Index into the spheres, the quads or the disks
+    pub index: u32,
+}
+
+// Every emissive sphere, quad and disk in the scene. Spheres count by their own material,
+// not the one a material animation is heading towards.
+pub fn collect_lights(
+    spheres: &SphereCollection,
+    quads: &QuadCollection,
+    disks: &DiskCollection,
+) -> Vec<GpuLight> {
+    let sphere_lights = spheres
+        .pairs()
+        .enumerate()
+        .filter(|(_, (_, material))| material.kind() == MaterialKind::Emissive)
+        .map(|(index, _)| GpuLight {
+            kind: LIGHT_SPHERE,
+            index: index as u32,
+        });
+    let quad_lights = quads
+        .quads
+        .iter()
+        .take(quads.count as usize)
+        .enumerate()
+        .filter(|(_, quad)| quad.material.kind() == MaterialKind::Emissive)
+        .map(|(index, _)| GpuLight {
+            kind: LIGHT_QUAD,
+            index: index as u32,
+        });
+    let disk_lights = disks
+        .disks
+        .iter()
+        .take(disks.count as usize)
+        .enumerate()
+        .filter(|(_, disk)| disk.material.kind() == MaterialKind::Emissive)
+        .map(|(index, _)| GpuLight {
+            kind: LIGHT_DISK,
+            index: index as u32,
+        });
+    sphere_lights
+        .chain(quad_lights)
+        .chain(disk_lights)
+        .collect()
+}
@@ -0,0 +1,514 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::{fmt, fs, io, path::Path};
+
+use super::builder::RayTracedScene;
+use super::sphere::{
+    GpuMaterial, GpuSphere, MaterialKind, SphereCollection, SurfaceMaterial, CANDLE_KELVIN,
+    DAYLIGHT_KELVIN, MAX_SPHERES, NOISE_MATERIAL_W, TUNGSTEN_KELVIN,
+};
+
+// Scene file loaded at startup, if present
+pub const SCENE_FILE_PATH: &str = "assets/scene.txt";
+// RON scene file loaded at startup, if present. Takes precedence over SCENE_FILE_PATH.
+pub const RON_SCENE_FILE_PATH: &str = "assets/scene.ron";
+
+// Everything that can go wrong while loading a scene file
+#[derive(Debug)]
+pub enum SceneLoadError {
+    // The file could not be read
+    Io(io::Error),
+    // A line is malformed; `line` is 1-based
+    Parse { line: usize, msg: String },
+    // A RON scene file doesn't match the expected structure
+    Ron(ron::error::SpannedError),
+    // A sphere uses a material name the loader doesn't know
+    UnknownMaterial { line: usize, name: String },
+    // The file describes more spheres than the GPU buffer can hold
+    TooManySpheres { count: usize, max: usize },
+    // A built scene has more boxes than the GPU buffer can hold
+    TooManyBoxes { count: usize, max: usize },
+    // A mesh has more triangles than the GPU buffer can hold
+    TooManyTriangles { count: usize, max: usize },
+    // A built scene has more planes than the GPU buffer can hold
+    TooManyPlanes { count: usize, max: usize },
+    // A built scene has more quads than the GPU buffer can hold
+    TooManyQuads { count: usize, max: usize },
+    // A built scene has more disks than the GPU buffer can hold
+    TooManyDisks { count: usize, max: usize },
+    // A built scene has more sphere instance groups than the GPU buffer can hold
+    TooManyInstanceGroups { count: usize, max: usize },
+    // A built scene has more SDF primitives than the GPU buffer can hold
+    TooManySdfPrimitives { count: usize, max: usize },
+}
+
+impl fmt::Display for SceneLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneLoadError::Io(err) => write!(f, "could not read scene file: {err}"),
+            SceneLoadError::Parse { line, msg } => write!(f, "line {line}: {msg}"),
+            SceneLoadError::Ron(err) => write!(
+                f,
+                "line {}, column {}: {}",
+                err.position.line, err.position.col, err.code
+            ),
+            SceneLoadError::UnknownMaterial { line, name } => {
+                write!(f, "line {line}: unknown material `{name}`")
+            }
+            SceneLoadError::TooManySpheres { count, max } => {
+                write!(f, "scene has {count} spheres but at most {max} are supported")
+            }
+            SceneLoadError::TooManyBoxes { count, max } => {
+                write!(f, "scene has {count} boxes but at most {max} are supported")
+            }
+            SceneLoadError::TooManyTriangles { count, max } => {
+                write!(
+                    f,
+                    "mesh has {count} triangles but at most {max} are supported"
+                )
+            }
+            SceneLoadError::TooManyPlanes { count, max } => {
+                write!(
+                    f,
+                    "scene has {count} planes but at most {max} are supported"
+                )
+            }
+            SceneLoadError::TooManyQuads { count, max } => {
+                write!(f, "scene has {count} quads but at most {max} are supported")
+            }
+            SceneLoadError::TooManyDisks { count, max } => {
+                write!(f, "scene has {count} disks but at most {max} are supported")
+            }
+            SceneLoadError::TooManyInstanceGroups { count, max } => {
+                write!(
+                    f,
+                    "scene has {count} sphere instance groups but at most {max} are supported"
+                )
+            }
+            SceneLoadError::TooManySdfPrimitives { count, max } => {
+                write!(
+                    f,
+                    "scene has {count} SDF primitives but at most {max} are supported"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for SceneLoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneLoadError::Io(err) => Some(err),
+            SceneLoadError::Ron(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SceneLoadError {
+    fn from(err: io::Error) -> Self {
+        SceneLoadError::Io(err)
+    }
+}
+
+// Load a scene file with one sphere per line:
+//
+//   sphere <x> <y> <z> <radius> diffuse <r> <g> <b>
+//   sphere <x> <y> <z> <radius> metal <r> <g> <b> <fuzz>
+//   sphere <x> <y> <z> <radius> glass <refractive index>
+//   sphere <x> <y> <z> <radius> glass <refractive index> <absorption r> <g> <b>
+//   sphere <x> <y> <z> <radius> emissive <r> <g> <b>
+//   sphere <x> <y> <z> <radius> blackbody <kelvin|candle|tungsten|daylight> <intensity>
+//
+// Glass with absorption is tinted: light crossing it is attenuated by that much per unit
+// of distance in each channel.
+//
+// Any sphere can end in `velocity <x> <y> <z>` to move that far over the frame interval,
+// which renders as motion blur.
+//
+// Blank lines and lines starting with `#` are ignored. Files ending in `.ron` are read
+// with load_scene instead.
+pub fn load_scene_from_file(path: &str) -> Result<SphereCollection, SceneLoadError> {
+    if Path::new(path).extension().is_some_and(|ext| ext == "ron") {
+        return load_scene(path);
+    }
+    let source = fs::read_to_string(path)?;
+    parse_scene(&source)
+}
+
+// One sphere in a RON scene file
+#[derive(Debug, Deserialize)]
+struct SphereDescription {
+    position: (f32, f32, f32),
+    radius: f32,
+    material: MaterialDescription,
+    // Distance moved over the frame interval; static if left out
+    #[serde(default)]
+    velocity: (f32, f32, f32),
+}
+
+// Material of a sphere in a RON scene file, tagged by kind
+#[derive(Debug, Deserialize)]
+enum MaterialDescription {
+    Diffuse {
+        albedo: (f32, f32, f32),
+    },
+    Metal {
+        albedo: (f32, f32, f32),
+        fuzz: f32,
+    },
+    Glass {
+        ior: f32,
+        // Absorption per unit of distance inside; clear if left out
+        #[serde(default)]
+        absorption: (f32, f32, f32),
+    },
+    Emissive {
+        color: (f32, f32, f32),
+    },
+    Noise {
+        base: (f32, f32, f32),
+        scale: f32,
+    },
+}
+
+impl MaterialDescription {
+    fn to_material(&self) -> SurfaceMaterial {
+        match *self {
+            MaterialDescription::Diffuse { albedo } => SurfaceMaterial::Lambertian(albedo.into()),
+            MaterialDescription::Metal { albedo, fuzz } => SurfaceMaterial::Metal {
+                albedo: albedo.into(),
+                fuzz,
+            },
+            MaterialDescription::Glass { ior, absorption } => {
+                SurfaceMaterial::AbsorbingDielectric {
+                    refractive_index: ior,
+                    absorption: absorption.into(),
+                }
+            }
+            MaterialDescription::Emissive { color } => SurfaceMaterial::Emissive(color.into()),
+            MaterialDescription::Noise { base, scale } => SurfaceMaterial::Noise {
+                base: base.into(),
+                scale,
+            },
+        }
+    }
+}
+
+// Load a RON scene file holding a list of spheres:
+//
+//   [
+//       (position: (0.0, 1.0, 0.0), radius: 1.0, material: Glass(ior: 1.5)),
+//       (position: (4.0, 1.0, 0.0), radius: 1.0, material: Metal(albedo: (0.7, 0.6, 0.5), fuzz: 0.0)),
+//   ]
+//
+// Materials are `Diffuse(albedo)`, `Metal(albedo, fuzz)`, `Glass(ior)` with an optional
+// `absorption`, `Emissive(color)` and `Noise(base, scale)`.
+pub fn load_scene(path: &str) -> Result<SphereCollection, SceneLoadError> {
+    let source = fs::read_to_string(path)?;
+    parse_ron_scene(&source)
+}
+
+// Parse the contents of a RON scene file
+pub fn parse_ron_scene(source: &str) -> Result<SphereCollection, SceneLoadError> {
+    let descriptions: Vec<SphereDescription> =
+        ron::from_str(source).map_err(SceneLoadError::Ron)?;
+    if descriptions.len() > MAX_SPHERES {
+        return Err(SceneLoadError::TooManySpheres {
+            count: descriptions.len(),
+            max: MAX_SPHERES,
+        });
+    }
+
+    let spheres = descriptions
+        .iter()
+        .map(|sphere| {
+            (
+                GpuSphere::new(sphere.position.into(), sphere.radius)
+                    .with_velocity(sphere.velocity.into()),
+                sphere.material.to_material().to_gpu(),
+            )
+        })
+        .collect();
+    Ok(SphereCollection::from_spheres(spheres))
+}
+
+// Parse the contents of a scene file
+pub fn parse_scene(source: &str) -> Result<SphereCollection, SceneLoadError> {
+    let mut spheres = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        match tokens[0] {
+            "sphere" => spheres.push(parse_sphere(&tokens[1..], line)?),
+            other => {
+                return Err(SceneLoadError::Parse {
+                    line,
+                    msg: format!("unknown object `{other}`"),
+                })
+            }
+        }
+    }
+
+    if spheres.len() > MAX_SPHERES {
+        return Err(SceneLoadError::TooManySpheres {
+            count: spheres.len(),
+            max: MAX_SPHERES,
+        });
+    }
+
+    Ok(SphereCollection::from_spheres(spheres))
+}
+
+// Write the spheres of a scene in the scene file format. Numbers are printed with enough
+// digits to parse back to the same value, so parse_scene reproduces the spheres.
+// Blackbody lights are written as their emissive color.
+//
+// The format only holds spheres: sphere instances, SDF primitives and every other shape
+// are skipped, with a warning naming what was left out.
+pub fn write_scene(scene: &RayTracedScene) -> String {
+    let skipped: Vec<&str> = [
+        ("sphere instances", scene.instances.count),
+        ("SDF primitives", scene.sdfs.count),
+        ("boxes", scene.boxes.count),
+        ("triangles", scene.mesh.count),
+        ("planes", scene.planes.count),
+        ("quads", scene.quads.count),
+        ("disks", scene.disks.count),
+    ]
+    .into_iter()
+    .filter(|&(_, count)| count > 0)
+    .map(|(name, _)| name)
+    .collect();
+    if !skipped.is_empty() {
+        warn!(
+            "Scene files only hold spheres, so the {} were not written",
+            skipped.join(", ")
+        );
+    }
+
+    let mut source = String::new();
+    for (sphere, material) in scene.spheres.pairs() {
+        let (p, c) = (sphere.position, material.color);
+        let material = match material.kind() {
+            MaterialKind::Lambertian => format!("diffuse {} {} {}", c.x, c.y, c.z),
+            MaterialKind::Metal => format!("metal {} {} {} {}", c.x, c.y, c.z, c.w),
+            MaterialKind::Dielectric if material.absorption == Vec3::ZERO => {
+                format!("glass {}", c.x)
+            }
+            MaterialKind::Dielectric => {
+                let a = material.absorption;
+                format!("glass {} {} {} {}", c.x, a.x, a.y, a.z)
+            }
+            MaterialKind::Emissive => format!("emissive {} {} {}", c.x, c.y, c.z),
+            MaterialKind::Noise => {
+                let scale = NOISE_MATERIAL_W - c.w;
+                format!("noise {} {} {} {scale}", c.x, c.y, c.z)
+            }
+        };
+        source += &format!(
+            "sphere {} {} {} {} {material}",
+            p.x, p.y, p.z, sphere.radius
+        );
+        let v = sphere.velocity;
+        if v != Vec3::ZERO {
+            source += &format!(" velocity {} {} {}", v.x, v.y, v.z);
+        }
+        source += "\n";
+    }
+    source
+}
+
+fn parse_sphere(tokens: &[&str], line: usize) -> Result<(GpuSphere, GpuMaterial), SceneLoadError> {
+    if tokens.len() < 5 {
+        return Err(SceneLoadError::Parse {
+            line,
+            msg: "expected `sphere <x> <y> <z> <radius> <material> ...`".to_string(),
+        });
+    }
+
+    let position = Vec3::new(
+        parse_number(tokens[0], line)?,
+        parse_number(tokens[1], line)?,
+        parse_number(tokens[2], line)?,
+    );
+    let radius = parse_number(tokens[3], line)?;
+    let (params, velocity) = match &tokens[5..] {
+        [params @ .., "velocity", x, y, z] => (
+            params,
+            Vec3::new(
+                parse_number(x, line)?,
+                parse_number(y, line)?,
+                parse_number(z, line)?,
+            ),
+        ),
+        params => (params, Vec3::ZERO),
+    };
+    let material = parse_material(tokens[4], params, line)?;
+
+    Ok((
+        GpuSphere::new(position, radius).with_velocity(velocity),
+        material,
+    ))
+}
+
+fn parse_material(
+    name: &str,
+    params: &[&str],
+    line: usize,
+) -> Result<GpuMaterial, SceneLoadError> {
+    let expected = match name {
+        "diffuse" => 3,
+        "metal" => 4,
+        "glass" if params.len() == 4 => 4,
+        "glass" => 1,
+        "emissive" => 3,
+        "blackbody" => 2,
+        "noise" => 4,
+        _ => {
+            return Err(SceneLoadError::UnknownMaterial {
+                line,
+                name: name.to_string(),
+            })
+        }
+    };
+    if params.len() != expected {
+        return Err(SceneLoadError::Parse {
+            line,
+            msg: format!(
+                "material `{name}` takes {expected} parameters, found {}",
+                params.len()
+            ),
+        });
+    }
+
+    if name == "blackbody" {
+        let kelvin = match params[0] {
+            "candle" => CANDLE_KELVIN,
+            "tungsten" => TUNGSTEN_KELVIN,
+            "daylight" => DAYLIGHT_KELVIN,
+            token => parse_number(token, line)?,
+        };
+        return Ok(SurfaceMaterial::blackbody(kelvin, parse_number(params[1], line)?).to_gpu());
+    }
+
+    let values = params
+        .iter()
+        .map(|token| parse_number(token, line))
+        .collect::<Result<Vec<f32>, _>>()?;
+
+    let material = match name {
+        "diffuse" => SurfaceMaterial::Lambertian(Vec3::from_slice(&values)),
+        "metal" => SurfaceMaterial::Metal {
+            albedo: Vec3::from_slice(&values),
+            fuzz: values[3],
+        },
+        "emissive" => SurfaceMaterial::Emissive(Vec3::from_slice(&values)),
+        "noise" => SurfaceMaterial::Noise {
+            base: Vec3::from_slice(&values),
+            scale: values[3],
+        },
+        _ => SurfaceMaterial::AbsorbingDielectric {
+            refractive_index: values[0],
+            absorption: values.get(1..4).map_or(Vec3::ZERO, Vec3::from_slice),
+        },
+    };
+    Ok(material.to_gpu())
+}
+
+pub(super) fn parse_number(token: &str, line: usize) -> Result<f32, SceneLoadError> {
+    token.parse().map_err(|_| SceneLoadError::Parse {
+        line,
+        msg: format!("`{token}` is not a number"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> SceneLoadError {
+        match parse_scene(source) {
+            Ok(_) => panic!("`{source}` should not parse"),
+            Err(err) => err,
+        }
+    }
+
+    #[test]
+    fn unknown_keyword() {
+        let err = parse_error("# comment\ncube 0 0 0 1 diffuse 0.5 0.5 0.5");
+        assert!(
+            matches!(&err, SceneLoadError::Parse { line: 2, msg } if msg.contains("`cube`")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unknown_material() {
+        let err = parse_error("sphere 0 0 0 1 velvet 0.5 0.5 0.5");
+        assert!(
+            matches!(&err, SceneLoadError::UnknownMaterial { line: 1, name } if name == "velvet"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn missing_field() {
+        let err = parse_error("sphere 0 0 0 1");
+        assert!(
+            matches!(err, SceneLoadError::Parse { line: 1, .. }),
+            "{err}"
+        );
+
+        // Metal without its fuzz
+        let err = parse_error("sphere 0 0 0 1 metal 0.5 0.5 0.5");
+        assert!(
+            matches!(&err, SceneLoadError::Parse { line: 1, msg } if msg.contains("takes 4")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn bad_number() {
+        let err = parse_error("sphere 0 zero 0 1 diffuse 0.5 0.5 0.5");
+        assert!(
+            matches!(&err, SceneLoadError::Parse { line: 1, msg } if msg.contains("`zero`")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn malformed_ron() {
+        let err = match parse_ron_scene("[(position: (0.0, 1.0), radius: 1.0)]") {
+            Ok(_) => panic!("malformed RON should not parse"),
+            Err(err) => err,
+        };
+        assert!(matches!(err, SceneLoadError::Ron(_)), "{err}");
+    }
+
+    #[test]
+    fn write_scene_keeps_only_spheres() {
+        let scene = super::super::builder::SceneBuilder::new()
+            .add_sphere(
+                Vec3::new(1.0, 0.5, -2.0),
+                0.5,
+                SurfaceMaterial::Metal {
+                    albedo: Vec3::ONE,
+                    fuzz: 0.1,
+                },
+            )
+            .add_sdf_sphere(Vec3::ZERO, 1.0, 0.5, SurfaceMaterial::Lambertian(Vec3::ONE))
+            .build()
+            .unwrap();
+        let spheres = parse_scene(&write_scene(&scene)).unwrap();
+        assert_eq!(spheres.count, 1);
+        assert_eq!(spheres.spheres[0].position, Vec3::new(1.0, 0.5, -2.0));
+        assert_eq!(spheres.spheres[0].radius, 0.5);
+    }
+}
@@ -0,0 +1,318 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+use std::fs;
+
+use super::{
+    loader::{parse_number, SceneLoadError},
+    sphere::{GpuMaterial, SurfaceMaterial},
+};
+
+// Optional mesh loaded at startup alongside the scene file
+pub const MESH_FILE_PATH: &str = "assets/mesh.obj";
+// Number of triangles to send to the GPU. Every ray tests every triangle, so large meshes
+// are slow.
+pub const MAX_TRIANGLES: usize = 16384;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, ShaderType)]
+pub struct GpuTriangle {
+    pub v0: Vec3,
+    // 1 to shade with the corner normals in MeshCollection::normals, 0 for the face normal
+    pub smooth_shading: u32,
+    pub v1: Vec3,
+    pub _padding1: f32,
+    pub v2: Vec3,
+    // Index into MeshCollection::materials
+    pub material_index: u32,
+}
+
+impl GpuTriangle {
+    // Normal of the triangle's plane, facing the side its corners wind counterclockwise on
+    pub fn face_normal(&self) -> Vec3 {
+        (self.v1 - self.v0)
+            .cross(self.v2 - self.v0)
+            .normalize_or_zero()
+    }
+}
+
+// Normals at the corners of one triangle, in a buffer parallel to the triangles
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, ShaderType)]
+pub struct GpuTriangleNormals {
+    pub n0: Vec3,
+    pub _padding0: f32,
+    pub n1: Vec3,
+    pub _padding1: f32,
+    pub n2: Vec3,
+    pub _padding2: f32,
+}
+
+impl GpuTriangleNormals {
+    pub fn new(n0: Vec3, n1: Vec3, n2: Vec3) -> Self {
+        Self {
+            n0,
+            _padding0: 0.0,
+            n1,
+            _padding1: 0.0,
+            n2,
+            _padding2: 0.0,
+        }
+    }
+}
+
+// Resource to hold all triangles in the scene and the materials they use
+#[derive(Resource, Clone, ExtractResource)]
+pub struct MeshCollection {
+    pub triangles: Vec<GpuTriangle>,
+    // Corner normals of each triangle, the face normal at every corner where the OBJ has
+    // none
+    pub normals: Vec<GpuTriangleNormals>,
+    pub materials: Vec<GpuMaterial>,
+    pub count: u32,
+    // Interpolate the corner normals across each triangle rather than shading it flat,
+    // see with_smooth_normals
+    pub smooth_normals: bool,
+}
+
+impl Default for MeshCollection {
+    fn default() -> Self {
+        Self::from_triangles(Vec::new(), Vec::new(), Vec::new())
+    }
+}
+
+impl MeshCollection {
+    // Build a smooth shaded collection, keeping at least one triangle and material so the
+    // GPU buffers are never empty. Triangles past the end of `normals` get their face
+    // normal at every corner.
+    pub fn from_triangles(
+        triangles: Vec<GpuTriangle>,
+        mut normals: Vec<GpuTriangleNormals>,
+        materials: Vec<GpuMaterial>,
+    ) -> Self {
+        normals.truncate(triangles.len());
+        for triangle in &triangles[normals.len()..] {
+            let normal = triangle.face_normal();
+            normals.push(GpuTriangleNormals::new(normal, normal, normal));
+        }
+        let mut collection = Self {
+            count: triangles.len() as u32,
+            triangles,
+            normals,
+            materials,
+            smooth_normals: true,
+        };
+        if collection.triangles.is_empty() {
+            collection.triangles.push(GpuTriangle::zeroed());
+            collection.normals.push(GpuTriangleNormals::zeroed());
+        }
+        if collection.materials.is_empty() {
+            collection.materials.push(default_material());
+        }
+        collection.with_smooth_normals(true)
+    }
+
+    // Shade with normals interpolated from the corners, which hides the facets of a
+    // curved surface, or with each triangle's face normal, which keeps hard edges
+    pub fn with_smooth_normals(mut self, smooth: bool) -> Self {
+        self.smooth_normals = smooth;
+        for triangle in &mut self.triangles {
+            triangle.smooth_shading = smooth as u32;
+        }
+        self
+    }
+}
+
+// Material given to every face, since .mtl files aren't read
+fn default_material() -> GpuMaterial {
+    SurfaceMaterial::Lambertian(Vec3::splat(0.7)).to_gpu()
+}
+
+// Load a triangle mesh from a Wavefront OBJ file. Vertex positions, vertex normals and
+// faces are used; polygons are split into triangle fans. Faces without normals on every
+// corner are shaded flat. Every face shares one gray diffuse material, since `usemtl`
+// names a material in an .mtl file that isn't read. Errors are logged and give an empty
+// mesh.
+pub fn load_obj(path: &str) -> MeshCollection {
+    let result = fs::read_to_string(path)
+        .map_err(SceneLoadError::from)
+        .and_then(|source| parse_obj(&source));
+    match result {
+        Ok(mesh) => {
+            info!("Loaded {} triangles from {path}", mesh.count);
+            mesh
+        }
+        Err(err) => {
+            error!("Could not load {path}: {err}");
+            MeshCollection::default()
+        }
+    }
+}
+
+// Parse the contents of an OBJ file
+pub fn parse_obj(source: &str) -> Result<MeshCollection, SceneLoadError> {
+    let mut vertices: Vec<Vec3> = Vec::new();
+    let mut vertex_normals: Vec<Vec3> = Vec::new();
+    let mut triangles = Vec::new();
+    let mut normals = Vec::new();
+
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        match tokens.first() {
+            Some(&"v") => {
+                if tokens.len() < 4 {
+                    return Err(SceneLoadError::Parse {
+                        line,
+                        msg: "expected `v <x> <y> <z>`".to_string(),
+                    });
+                }
+                let mut position = [0.0; 3];
+                for (value, token) in position.iter_mut().zip(&tokens[1..4]) {
+                    *value = parse_number(token, line)?;
+                }
+                vertices.push(Vec3::from_array(position));
+            }
+            Some(&"vn") => {
+                if tokens.len() < 4 {
+                    return Err(SceneLoadError::Parse {
+                        line,
+                        msg: "expected `vn <x> <y> <z>`".to_string(),
+                    });
+                }
+                let mut normal = [0.0; 3];
+                for (value, token) in normal.iter_mut().zip(&tokens[1..4]) {
+                    *value = parse_number(token, line)?;
+                }
+                vertex_normals.push(Vec3::from_array(normal).normalize_or_zero());
+            }
+            Some(&"f") => {
+                let corners = tokens[1..]
+                    .iter()
+                    .map(|token| parse_vertex_index(token, vertices.len(), line))
+                    .collect::<Result<Vec<usize>, _>>()?;
+                // Normal indices, if every corner has one
+                let corner_normals = tokens[1..]
+                    .iter()
+                    .map(|token| parse_normal_index(token, vertex_normals.len(), line))
+                    .collect::<Result<Vec<Option<usize>>, _>>()?
+                    .into_iter()
+                    .collect::<Option<Vec<usize>>>();
+                if corners.len() < 3 {
+                    return Err(SceneLoadError::Parse {
+                        line,
+                        msg: "a face needs at least 3 vertices".to_string(),
+                    });
+                }
+                for i in 1..corners.len() - 1 {
+                    let triangle = GpuTriangle {
+                        v0: vertices[corners[0]],
+                        smooth_shading: 0,
+                        v1: vertices[corners[i]],
+                        _padding1: 0.0,
+                        v2: vertices[corners[i + 1]],
+                        material_index: 0,
+                    };
+                    let triangle_normals = match &corner_normals {
+                        Some(indices) => GpuTriangleNormals::new(
+                            vertex_normals[indices[0]],
+                            vertex_normals[indices[i]],
+                            vertex_normals[indices[i + 1]],
+                        ),
+                        None => {
+                            let normal = triangle.face_normal();
+                            GpuTriangleNormals::new(normal, normal, normal)
+                        }
+                    };
+                    triangles.push(triangle);
+                    normals.push(triangle_normals);
+                }
+            }
+            // Texture coordinates, groups, materials and comments aren't needed
+            _ => {}
+        }
+    }
+
+    if triangles.len() > MAX_TRIANGLES {
+        return Err(SceneLoadError::TooManyTriangles {
+            count: triangles.len(),
+            max: MAX_TRIANGLES,
+        });
+    }
+
+    Ok(MeshCollection::from_triangles(
+        triangles,
+        normals,
+        vec![default_material()],
+    ))
+}
+
+// Resolve the position index of a face corner such as `3`, `3/1` or `3/1/2`
+fn parse_vertex_index(
+    token: &str,
+    vertex_count: usize,
+    line: usize,
+) -> Result<usize, SceneLoadError> {
+    let position = token.split('/').next().unwrap_or(token);
+    resolve_index(position, token, vertex_count, "vertex", line)
+}
+
+// Resolve the normal index of a face corner such as `3//2` or `3/1/2`, if it has one
+fn parse_normal_index(
+    token: &str,
+    normal_count: usize,
+    line: usize,
+) -> Result<Option<usize>, SceneLoadError> {
+    match token.split('/').nth(2) {
+        Some(normal) if !normal.is_empty() => {
+            resolve_index(normal, token, normal_count, "normal", line).map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+// Turn one index of a face corner into a position in its list. Indices are 1-based;
+// negative ones count back from the last entry.
+fn resolve_index(
+    text: &str,
+    token: &str,
+    count: usize,
+    kind: &str,
+    line: usize,
+) -> Result<usize, SceneLoadError> {
+    let index: i64 = text.parse().map_err(|_| SceneLoadError::Parse {
+        line,
+        msg: format!("`{token}` is not a {kind} index"),
+    })?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(SceneLoadError::Parse {
+            line,
+            msg: format!("{kind} index {index} is out of range"),
+        });
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usemtl_shares_the_default_material() {
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 3 2\n";
+        let mesh = parse_obj(source).unwrap();
+        assert_eq!(mesh.count, 2);
+        assert_eq!(mesh.materials, vec![default_material()]);
+        assert!(mesh
+            .triangles
+            .iter()
+            .all(|triangle| triangle.material_index == 0));
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+
+use super::sphere::{GpuMaterial, SphereCollection, SurfaceMaterial};
+use crate::camera::CameraSettings;
+
+// A material a sphere should have at a point in time, in seconds from the start
+#[derive(Debug, Clone, Copy)]
+pub struct MaterialKeyframe {
+    pub time: f32,
+    pub material: GpuMaterial,
+}
+
+// Keyframed material animation of a single sphere, toggled with K
+#[derive(Resource, Debug, Clone)]
+pub struct MaterialAnimation {
+    // Index into SphereCollection of the animated sphere; defaults to the last one
+    pub sphere: Option<usize>,
+    // Keyframes sorted by time; the animation loops after the last one
+    pub keyframes: Vec<MaterialKeyframe>,
+    pub playing: bool,
+    pub elapsed: f32,
+}
+
+impl Default for MaterialAnimation {
+    fn default() -> Self {
+        let diffuse = SurfaceMaterial::Lambertian(Vec3::new(0.7, 0.3, 0.2)).to_gpu();
+        let metal = SurfaceMaterial::Metal {
+            albedo: Vec3::new(0.8, 0.7, 0.5),
+            fuzz: 0.05,
+        }
+        .to_gpu();
+        let glass = SurfaceMaterial::Dielectric(1.5).to_gpu();
+        Self {
+            sphere: None,
+            keyframes: vec![
+                MaterialKeyframe {
+                    time: 0.0,
+                    material: diffuse,
+                },
+                MaterialKeyframe {
+                    time: 2.0,
+                    material: metal,
+                },
+                MaterialKeyframe {
+                    time: 4.0,
+                    material: glass,
+                },
+                MaterialKeyframe {
+                    time: 6.0,
+                    material: diffuse,
+                },
+            ],
+            playing: false,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl MaterialAnimation {
+    // Material pair and cross-fade weight at `time`. Materials of the same kind are
+    // interpolated directly; otherwise the shader blends between them per sample.
+    pub fn sample(&self, time: f32) -> Option<(GpuMaterial, GpuMaterial, f32)> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        let duration = last.time - first.time;
+        let time = if duration > 0.0 {
+            first.time + (time - first.time).rem_euclid(duration)
+        } else {
+            first.time
+        };
+
+        let next = self
+            .keyframes
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap_or(self.keyframes.len() - 1);
+        let (from, to) = (self.keyframes[next.saturating_sub(1)], self.keyframes[next]);
+        let span = to.time - from.time;
+        let t = if span > 0.0 {
+            ((time - from.time) / span).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        // Smooth the start and end of each transition
+        let t = t * t * (3.0 - 2.0 * t);
+
+        if from.material.kind() == to.material.kind() {
+            let material = GpuMaterial {
+                color: from.material.color.lerp(to.material.color, t),
+                absorption: from.material.absorption.lerp(to.material.absorption, t),
+                _padding0: 0.0,
+            };
+            Some((material, material, 0.0))
+        } else {
+            Some((from.material, to.material, t))
+        }
+    }
+}
+
+// Sphere being animated. The animation appends two entries to SphereCollection::materials
+// starting at `slot`, so other spheres sharing its material are left alone.
+pub struct AnimatedSphere {
+    index: usize,
+    material_index: u32,
+    slot: usize,
+}
+
+impl AnimatedSphere {
+    // True if `spheres` is still the collection the animation started in
+    fn matches(&self, spheres: &SphereCollection) -> bool {
+        spheres.materials.len() == self.slot + 2 && self.index < spheres.count as usize
+    }
+}
+
+/// System to toggle the material animation with K and apply it to its sphere every frame
+pub fn animate_material(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut animation: ResMut<MaterialAnimation>,
+    mut spheres: ResMut<SphereCollection>,
+    mut camera_settings: ResMut<CameraSettings>,
+    mut animated: Local<Option<AnimatedSphere>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyK) {
+        animation.playing = !animation.playing;
+        animation.elapsed = 0.0;
+        info!("Material animation: {}", animation.playing);
+
+        // Put the sphere back the way it was once the animation stops
+        if !animation.playing {
+            if let Some(previous) = animated
+                .take()
+                .filter(|previous| previous.matches(&spheres))
+            {
+                let sphere = &mut spheres.spheres[previous.index];
+                sphere.material_index = previous.material_index;
+                sphere.morph_target_index = previous.material_index;
+                sphere.morph = 0.0;
+                spheres.materials.truncate(previous.slot);
+                camera_settings.camera_has_moved = true;
+            }
+        }
+    }
+
+    if !animation.playing {
+        return;
+    }
+    let Some(index) = animation
+        .sphere
+        .or((spheres.count as usize).checked_sub(1))
+        .filter(|&index| index < spheres.count as usize)
+    else {
+        return;
+    };
+    let Some((material, morph_target, morph)) = animation.sample(animation.elapsed) else {
+        return;
+    };
+
+    // Start over if the scene was switched or the animated sphere changed
+    if animated
+        .as_ref()
+        .is_some_and(|previous| previous.index != index || !previous.matches(&spheres))
+    {
+        *animated = None;
+    }
+    let slot = animated
+        .get_or_insert_with(|| {
+            let slot = spheres.materials.len();
+            spheres.materials.extend([material, morph_target]);
+            AnimatedSphere {
+                index,
+                material_index: spheres.spheres[index].material_index,
+                slot,
+            }
+        })
+        .slot;
+
+    spheres.materials[slot] = material;
+    spheres.materials[slot + 1] = morph_target;
+    let sphere = &mut spheres.spheres[index];
+    sphere.material_index = slot as u32;
+    sphere.morph_target_index = slot as u32 + 1;
+    sphere.morph = morph;
+
+    animation.elapsed += time.delta_secs();
+    // The sphere changes every frame, so accumulation restarts with it
+    camera_settings.camera_has_moved = true;
+}
@@ -0,0 +1,34 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+// Seed of the permutation table behind noise materials. A seed always gives the same
+// pattern, so renders using them can be reproduced.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq, ExtractResource)]
+pub struct NoiseSettings {
+    pub seed: u64,
+}
+
+// The numbers 0 to 255 shuffled by the seed, for the Perlin noise in compute_shader.wgsl.
+// They are packed four to a vector because uniform arrays have a 16-byte stride.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, ShaderType)]
+pub struct GpuNoiseTable {
+    pub permutation: [UVec4; 64],
+}
+
+impl GpuNoiseTable {
+    pub fn new(settings: &NoiseSettings) -> Self {
+        let mut values: Vec<u32> = (0..256).collect();
+        values.shuffle(&mut StdRng::seed_from_u64(settings.seed));
+
+        let mut permutation = [UVec4::ZERO; 64];
+        for (entry, chunk) in permutation.iter_mut().zip(values.chunks_exact(4)) {
+            *entry = UVec4::from_slice(chunk);
+        }
+        Self { permutation }
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::sphere::GpuMaterial;
+
+// Most planes a scene can hold: the storage buffer has to fit in wgpu's default 128 MiB
+// limit for a single binding
+pub const MAX_PLANES: usize = (128 << 20) / std::mem::size_of::<GpuPlane>();
+
+// Infinite plane through `point`, facing along `normal`. Intersected exactly, so it stays
+// flat far from the origin where a huge sphere would show its curvature and precision loss.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuPlane {
+    pub point: Vec3,
+    pub _padding0: f32,
+    pub normal: Vec3,
+    pub _padding1: f32,
+    pub material: GpuMaterial,
+}
+
+impl GpuPlane {
+    pub fn new(point: Vec3, normal: Vec3, material: GpuMaterial) -> Self {
+        Self {
+            point,
+            _padding0: 0.0,
+            normal: normal.normalize_or(Vec3::Y),
+            _padding1: 0.0,
+            material,
+        }
+    }
+}
+
+// Resource to hold all planes in the scene
+#[derive(Resource, Clone, PartialEq, ExtractResource)]
+pub struct PlaneCollection {
+    pub planes: Vec<GpuPlane>,
+    pub count: u32,
+}
+
+impl Default for PlaneCollection {
+    fn default() -> Self {
+        Self::from_planes(Vec::new())
+    }
+}
+
+impl PlaneCollection {
+    // Build a collection from a list of planes; only these are uploaded to the GPU
+    pub fn from_planes(planes: Vec<GpuPlane>) -> Self {
+        let mut collection = Self {
+            count: planes.len() as u32,
+            planes,
+        };
+
+        // Storage buffers can't be empty, so no planes uploads one the shader never reads
+        if collection.planes.is_empty() {
+            collection
+                .planes
+                .push(GpuPlane::new(Vec3::ZERO, Vec3::Y, GpuMaterial::zeroed()));
+        }
+
+        collection
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use std::fs;
+
+use super::{
+    loader::load_scene_from_file,
+    plane::PlaneCollection,
+    sphere::{load_startup_scene, SphereCollection},
+};
+use crate::camera::CameraSettings;
+
+// Folder scanned for scene files to offer as presets
+const PRESET_DIRECTORY: &str = "assets/scenes";
+
+// One scene that can be switched to
+pub struct ScenePreset {
+    pub name: String,
+    pub spheres: SphereCollection,
+    pub planes: PlaneCollection,
+}
+
+// Scenes that can be cycled through at runtime
+#[derive(Resource)]
+pub struct ScenePresets {
+    pub presets: Vec<ScenePreset>,
+    pub current: usize,
+    // Restart accumulation on every switch. When false, switching between presets with
+    // identical geometry (only materials differ) keeps accumulating.
+    pub reset_on_switch: bool,
+}
+
+impl ScenePresets {
+    // The startup scene followed by every scene file in PRESET_DIRECTORY, sorted by name
+    pub fn load() -> Self {
+        let (spheres, planes) = load_startup_scene();
+        let mut presets = vec![ScenePreset {
+            name: "startup".to_string(),
+            spheres,
+            planes,
+        }];
+
+        let mut paths: Vec<_> = fs::read_dir(PRESET_DIRECTORY)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| Some(entry.ok()?.path()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        paths.sort();
+        for path in paths {
+            let name = path.display().to_string();
+            match load_scene_from_file(&name) {
+                Ok(spheres) => presets.push(ScenePreset {
+                    name,
+                    spheres,
+                    planes: PlaneCollection::default(),
+                }),
+                Err(err) => warn!("Skipping scene preset {name}: {err}"),
+            }
+        }
+
+        Self {
+            presets,
+            current: 0,
+            reset_on_switch: true,
+        }
+    }
+}
+
+// True if both collections hold the same spheres at the same places, ignoring materials
+pub fn same_geometry(a: &SphereCollection, b: &SphereCollection) -> bool {
+    a.count == b.count
+        && a.spheres
+            .iter()
+            .zip(&b.spheres)
+            .take(a.count as usize)
+            .all(|(a, b)| a.position == b.position && a.radius == b.radius)
+}
+
+/// System to cycle scene presets with F2 and toggle reset-on-switch with F3
+pub fn cycle_scene_preset_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut presets: ResMut<ScenePresets>,
+    mut spheres: ResMut<SphereCollection>,
+    mut planes: ResMut<PlaneCollection>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F3) {
+        presets.reset_on_switch = !presets.reset_on_switch;
+        info!(
+            "Reset accumulation on preset switch: {}",
+            presets.reset_on_switch
+        );
+    }
+
+    if !keyboard_input.just_pressed(KeyCode::F2) || presets.presets.is_empty() {
+        return;
+    }
+
+    presets.current = (presets.current + 1) % presets.presets.len();
+    let next = &presets.presets[presets.current];
+    info!("Scene preset: {}", next.name);
+
+    if presets.reset_on_switch || !same_geometry(&spheres, &next.spheres) || *planes != next.planes
+    {
+        camera_settings.camera_has_moved = true;
+    }
+    *spheres = next.spheres.clone();
+    *planes = next.planes.clone();
+}
@@ -0,0 +1,86 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::sphere::GpuMaterial;
+
+// Most quads a scene can hold: the storage buffer has to fit in wgpu's default 128 MiB
+// limit for a single binding
+pub const MAX_QUADS: usize = (128 << 20) / std::mem::size_of::<GpuQuad>();
+
+// Parallelogram with a corner at `q` and the edges `u` and `v` leaving it. It faces along
+// u × v, which matters for telling the inside of a glass panel from the outside.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuQuad {
+    pub q: Vec3,
+    pub _padding0: f32,
+    pub u: Vec3,
+    pub _padding1: f32,
+    pub v: Vec3,
+    pub _padding2: f32,
+    pub material: GpuMaterial,
+}
+
+impl GpuQuad {
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: GpuMaterial) -> Self {
+        Self {
+            q,
+            _padding0: 0.0,
+            u,
+            _padding1: 0.0,
+            v,
+            _padding2: 0.0,
+            material,
+        }
+    }
+
+    // Rectangle spanning two opposite corners that share one coordinate, such as a wall or
+    // a ceiling light. It faces towards +x, +y or +z along the axis it is flat in; if the
+    // corners differ in all three, the axis they differ least in is flattened.
+    pub fn axis_aligned(corner_a: Vec3, corner_b: Vec3, material: GpuMaterial) -> Self {
+        let min = corner_a.min(corner_b);
+        let size = corner_a.max(corner_b) - min;
+        // Edges in cyclic order, so u × v points along the flat axis
+        let (u, v) = if size.x <= size.y && size.x <= size.z {
+            (Vec3::Y * size.y, Vec3::Z * size.z)
+        } else if size.y <= size.z {
+            (Vec3::Z * size.z, Vec3::X * size.x)
+        } else {
+            (Vec3::X * size.x, Vec3::Y * size.y)
+        };
+        Self::new(min, u, v, material)
+    }
+}
+
+// Resource to hold all quads in the scene
+#[derive(Resource, Clone, PartialEq, ExtractResource)]
+pub struct QuadCollection {
+    pub quads: Vec<GpuQuad>,
+    pub count: u32,
+}
+
+impl Default for QuadCollection {
+    fn default() -> Self {
+        Self::from_quads(Vec::new())
+    }
+}
+
+impl QuadCollection {
+    // Build a collection from a list of quads; only these are uploaded to the GPU
+    pub fn from_quads(quads: Vec<GpuQuad>) -> Self {
+        let mut collection = Self {
+            count: quads.len() as u32,
+            quads,
+        };
+
+        // Storage buffers can't be empty, so no quads uploads one the shader never reads
+        if collection.quads.is_empty() {
+            collection.quads.push(GpuQuad::zeroed());
+        }
+
+        collection
+    }
+}
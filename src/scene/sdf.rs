@@ -0,0 +1,165 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use super::sphere::GpuMaterial;
+
+// Most SDF primitives a scene can hold. Every ray marching step evaluates all of them, so
+// the limit is kept low.
+pub const MAX_SDF_PRIMITIVES: usize = 16;
+
+// Shapes GpuSdfPrimitive::kind can hold. Keep in sync with the SDF_ constants in
+// compute_shader.wgsl.
+pub const SDF_SPHERE: u32 = 0;
+pub const SDF_BOX: u32 = 1;
+pub const SDF_TORUS: u32 = 2;
+
+// Shape given by a signed distance function instead of an analytic intersection. All
+// primitives of an SdfCollection make up one surface that the shader ray marches, each
+// joined to the ones before it by a smooth minimum over `blend`, so nearby spheres melt
+// together like metaballs. A blend of 0 is a plain union with a sharp crease.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuSdfPrimitive {
+    pub center: Vec3,
+    // SDF_SPHERE, SDF_BOX or SDF_TORUS
+    pub kind: u32,
+    // Radius in x for a sphere, half extents for a box, and the major and minor radius in x
+    // and y for a torus lying flat in the XZ plane
+    pub size: Vec3,
+    // Distance over which the primitive blends into the ones before it
+    pub blend: f32,
+    // The surface takes the material of the nearest primitive
+    pub material: GpuMaterial,
+}
+
+impl GpuSdfPrimitive {
+    pub fn sphere(center: Vec3, radius: f32, material: GpuMaterial) -> Self {
+        Self {
+            center,
+            kind: SDF_SPHERE,
+            size: Vec3::new(radius.abs(), 0.0, 0.0),
+            blend: 0.0,
+            material,
+        }
+    }
+
+    // Axis-aligned box reaching `half_size` from its center along each axis
+    pub fn cuboid(center: Vec3, half_size: Vec3, material: GpuMaterial) -> Self {
+        Self {
+            center,
+            kind: SDF_BOX,
+            size: half_size.abs(),
+            blend: 0.0,
+            material,
+        }
+    }
+
+    // Ring around the vertical axis through `center`, with a tube of `minor_radius`
+    pub fn torus(
+        center: Vec3,
+        major_radius: f32,
+        minor_radius: f32,
+        material: GpuMaterial,
+    ) -> Self {
+        Self {
+            center,
+            kind: SDF_TORUS,
+            size: Vec3::new(major_radius.abs(), minor_radius.abs(), 0.0),
+            blend: 0.0,
+            material,
+        }
+    }
+
+    // Blend into the primitives before this one over `blend`
+    pub fn with_blend(mut self, blend: f32) -> Self {
+        self.blend = blend.max(0.0);
+        self
+    }
+
+    // Half extents of the box around the primitive, before blending
+    fn extent(&self) -> Vec3 {
+        match self.kind {
+            SDF_BOX => self.size,
+            SDF_TORUS => {
+                let outer = self.size.x + self.size.y;
+                Vec3::new(outer, self.size.y, outer)
+            }
+            _ => Vec3::splat(self.size.x),
+        }
+    }
+}
+
+// Number of SDF primitives and the box around all of them, which rays that miss it don't
+// march through. The shader pads the box by the distance it counts as a hit.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
+pub struct GpuSdfBounds {
+    pub aabb_min: Vec3,
+    pub count: u32,
+    pub aabb_max: Vec3,
+    pub _padding: u32,
+}
+
+impl GpuSdfBounds {
+    pub fn new(sdfs: &SdfCollection) -> Self {
+        Self {
+            aabb_min: sdfs.aabb_min,
+            count: sdfs.count,
+            aabb_max: sdfs.aabb_max,
+            _padding: 0,
+        }
+    }
+}
+
+// Resource to hold the SDF primitives in the scene. It is empty by default, and rays only
+// march through the primitives when there are some, so scenes without them render at the
+// speed of the analytic shapes alone.
+#[derive(Resource, Clone, PartialEq, ExtractResource)]
+pub struct SdfCollection {
+    pub primitives: Vec<GpuSdfPrimitive>,
+    pub count: u32,
+    // Box around the primitives in use, with their blending
+    pub aabb_min: Vec3,
+    pub aabb_max: Vec3,
+}
+
+impl Default for SdfCollection {
+    fn default() -> Self {
+        Self::from_primitives(Vec::new())
+    }
+}
+
+impl SdfCollection {
+    // Build a collection from a list of primitives; only these are uploaded to the GPU
+    pub fn from_primitives(primitives: Vec<GpuSdfPrimitive>) -> Self {
+        // Blending lies at most blend / 4 outside the primitives, see smooth_min in the
+        // shader
+        let (aabb_min, aabb_max) = primitives.iter().fold(
+            (Vec3::INFINITY, Vec3::NEG_INFINITY),
+            |(min, max), primitive| {
+                let extent = primitive.extent() + primitive.blend * 0.25;
+                (
+                    min.min(primitive.center - extent),
+                    max.max(primitive.center + extent),
+                )
+            },
+        );
+        let mut collection = Self {
+            count: primitives.len() as u32,
+            primitives,
+            aabb_min,
+            aabb_max,
+        };
+
+        // Storage buffers can't be empty, so an empty collection uploads one primitive the
+        // shader never reads
+        if collection.primitives.is_empty() {
+            collection.primitives.push(GpuSdfPrimitive::zeroed());
+        }
+
+        collection
+    }
+}
@@ -6,15 +6,210 @@ use bevy::{
     },
 };
 use bytemuck::{Pod, Zeroable};
+use std::collections::HashMap;
 
-// Number of spheres to send to the GPU
-pub const MAX_SPHERES: usize = 100;
+use super::{
+    loader::{load_scene_from_file, SceneLoadError, RON_SCENE_FILE_PATH, SCENE_FILE_PATH},
+    plane::{GpuPlane, PlaneCollection},
+};
+
+// Most spheres a scene can hold: the storage buffer has to fit in wgpu's default
+// 128 MiB limit for a single binding
+pub const MAX_SPHERES: usize = (128 << 20) / std::mem::size_of::<GpuSphere>();
 
 // GPU-compatible sphere and material definitions
+//
+// A material is a single vec4 whose `w` selects the kind; this must match `Material` in
+// compute_shader.wgsl, `SurfaceMaterial::to_gpu` and `GpuMaterial::kind`:
+//
+//   w < -3.0          noise, diffuse with xyz as the albedo modulated by a marble pattern
+//                     of Perlin noise; the pattern's scale is NOISE_MATERIAL_W - w
+//   -3.0 <= w < -1.0  lambertian, xyz is the albedo (constructors use -2.0)
+//   -1.0 <= w <= 1.0  metal, xyz is the albedo and w the fuzz
+//   1.0 < w <= 2.5    dielectric, x is the refractive index (constructors use 2.0); light
+//                     crossing the inside is attenuated by `absorption`
+//   w > 2.5           emissive, xyz is the radiance, i.e. color * intensity, and the path
+//                     ends on hitting it (constructors use EMISSIVE_MATERIAL_W)
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable, ShaderType)]
+#[derive(Copy, Clone, Debug, PartialEq, Pod, Zeroable, ShaderType)]
 pub struct GpuMaterial {
     pub color: Vec4,
+    // Beer-Lambert absorption of dielectrics per unit of distance: light crossing a length
+    // d of glass is scaled by exp(-absorption * d). Zero for clear glass and other kinds.
+    pub absorption: Vec3,
+    pub _padding0: f32,
+}
+
+// Value of `color.w` that marks a material as emissive; `color.xyz` is then its radiance
+pub const EMISSIVE_MATERIAL_W: f32 = 3.0;
+
+// Values of `color.w` below this mark a noise material
+pub const NOISE_MATERIAL_W: f32 = -3.0;
+
+// Material families encoded in `GpuMaterial::color.w`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialKind {
+    Lambertian,
+    Metal,
+    Dielectric,
+    Emissive,
+    Noise,
+}
+
+impl GpuMaterial {
+    pub fn kind(&self) -> MaterialKind {
+        match self.color.w {
+            w if w < NOISE_MATERIAL_W => MaterialKind::Noise,
+            w if w < -1.0 => MaterialKind::Lambertian,
+            w if w <= 1.0 => MaterialKind::Metal,
+            w if w <= 2.5 => MaterialKind::Dielectric,
+            _ => MaterialKind::Emissive,
+        }
+    }
+
+    // The material this packs, the inverse of SurfaceMaterial::to_gpu
+    pub fn to_material(&self) -> SurfaceMaterial {
+        let color = self.color;
+        match self.kind() {
+            MaterialKind::Lambertian => SurfaceMaterial::Lambertian(color.truncate()),
+            MaterialKind::Metal => SurfaceMaterial::Metal {
+                albedo: color.truncate(),
+                fuzz: color.w,
+            },
+            MaterialKind::Dielectric if self.absorption == Vec3::ZERO => {
+                SurfaceMaterial::Dielectric(color.x)
+            }
+            MaterialKind::Dielectric => SurfaceMaterial::AbsorbingDielectric {
+                refractive_index: color.x,
+                absorption: self.absorption,
+            },
+            MaterialKind::Emissive => SurfaceMaterial::Emissive(color.truncate()),
+            MaterialKind::Noise => SurfaceMaterial::Noise {
+                base: color.truncate(),
+                scale: NOISE_MATERIAL_W - color.w,
+            },
+        }
+    }
+}
+
+// A material as scenes describe it, packed into a GpuMaterial with `to_gpu`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SurfaceMaterial {
+    // Diffuse with the given albedo
+    Lambertian(Vec3),
+    // Reflective; fuzz in 0..=1 roughens the reflection
+    Metal {
+        albedo: Vec3,
+        fuzz: f32,
+    },
+    // Clear and refractive with the given index of refraction
+    Dielectric(f32),
+    // Refractive and tinted, absorbing light inside by `absorption` per unit of distance
+    // and channel; thick parts look darker than thin ones
+    AbsorbingDielectric {
+        refractive_index: f32,
+        absorption: Vec3,
+    },
+    // Light-emitting with the given radiance, i.e. color * intensity
+    Emissive(Vec3),
+    // Diffuse with marble-like veins of 3D Perlin noise darkening the base albedo. Higher
+    // scales give narrower veins; a scale of 0 is plain diffuse.
+    Noise {
+        base: Vec3,
+        scale: f32,
+    },
+}
+
+impl SurfaceMaterial {
+    // A light-emitting material with the given color scaled by intensity
+    pub fn emissive(color: Vec3, intensity: f32) -> Self {
+        SurfaceMaterial::Emissive(color * intensity)
+    }
+
+    // A light-emitting material whose color is that of a black body at `kelvin`
+    pub fn blackbody(kelvin: f32, intensity: f32) -> Self {
+        SurfaceMaterial::emissive(kelvin_to_rgb(kelvin), intensity)
+    }
+
+    // Glass that tints light passing through it to `color` after `distance` units, e.g.
+    // the diameter of a sphere
+    pub fn tinted_glass(refractive_index: f32, color: Vec3, distance: f32) -> Self {
+        let transmitted = color.clamp(Vec3::splat(1e-4), Vec3::ONE);
+        SurfaceMaterial::AbsorbingDielectric {
+            refractive_index,
+            absorption: -Vec3::new(transmitted.x.ln(), transmitted.y.ln(), transmitted.z.ln())
+                / distance.max(1e-4),
+        }
+    }
+
+    pub fn to_gpu(&self) -> GpuMaterial {
+        let (color, absorption) = match *self {
+            SurfaceMaterial::Lambertian(albedo) => (albedo.extend(-2.0), Vec3::ZERO),
+            SurfaceMaterial::Metal { albedo, fuzz } => {
+                (albedo.extend(fuzz.clamp(0.0, 1.0)), Vec3::ZERO)
+            }
+            SurfaceMaterial::Dielectric(refractive_index) => {
+                (Vec4::new(refractive_index, 0.0, 0.0, 2.0), Vec3::ZERO)
+            }
+            SurfaceMaterial::AbsorbingDielectric {
+                refractive_index,
+                absorption,
+            } => (
+                Vec4::new(refractive_index, 0.0, 0.0, 2.0),
+                absorption.max(Vec3::ZERO),
+            ),
+            SurfaceMaterial::Emissive(radiance) => {
+                (radiance.extend(EMISSIVE_MATERIAL_W), Vec3::ZERO)
+            }
+            SurfaceMaterial::Noise { base, scale } => {
+                (base.extend(NOISE_MATERIAL_W - scale.max(0.0)), Vec3::ZERO)
+            }
+        };
+        GpuMaterial {
+            color,
+            absorption,
+            _padding0: 0.0,
+        }
+    }
+}
+
+// Color temperature presets, in Kelvin
+pub const CANDLE_KELVIN: f32 = 1800.0;
+pub const TUNGSTEN_KELVIN: f32 = 3200.0;
+pub const DAYLIGHT_KELVIN: f32 = 5600.0;
+
+// Linear sRGB color of a black body at the given temperature, normalized so the
+// brightest channel is 1. Uses the Kim et al. cubic fit of the Planckian locus,
+// valid from 1667K to 25000K; temperatures outside that range are clamped.
+pub fn kelvin_to_rgb(kelvin: f32) -> Vec3 {
+    let t = kelvin.clamp(1667.0, 25000.0) as f64;
+    let (t2, t3) = (t * t, t * t * t);
+
+    // CIE 1931 chromaticity of the black body
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+    let y = if t <= 2222.0 {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+
+    // xyY (Y = 1) to XYZ to linear sRGB
+    let (big_x, big_y, big_z) = (x / y, 1.0, (1.0 - x - y) / y);
+    let rgb = Vec3::new(
+        (3.2404542 * big_x - 1.5371385 * big_y - 0.4985314 * big_z) as f32,
+        (-0.9692660 * big_x + 1.8760108 * big_y + 0.0415560 * big_z) as f32,
+        (0.0556434 * big_x - 0.2040259 * big_y + 1.0572252 * big_z) as f32,
+    )
+    .max(Vec3::ZERO);
+
+    rgb / rgb.max_element()
 }
 
 #[repr(C)]
@@ -22,37 +217,164 @@ pub struct GpuMaterial {
 pub struct GpuSphere {
     pub position: Vec3,
     pub radius: f32,
-    pub material: GpuMaterial,
+    // Index into SphereCollection::materials
+    pub material_index: u32,
+    // Each sample uses the material at morph_target_index instead with probability
+    // `morph`, which cross-fades between materials the shader can't interpolate
+    pub morph_target_index: u32,
+    pub morph: f32,
+    pub _padding0: f32,
+    // Distance the center travels over the frame interval: at shutter time t in 0..=1 the
+    // center is at position + velocity * t, so moving spheres blur along their path.
+    // Zero for static spheres.
+    pub velocity: Vec3,
+    pub _padding1: f32,
 }
 
-// Resource to hold all spheres in the scene
-#[derive(Resource, Clone, ExtractResource)]
+impl GpuSphere {
+    // A sphere using the first material; SphereCollection::from_spheres sets the index
+    pub fn new(position: Vec3, radius: f32) -> Self {
+        Self {
+            position,
+            radius,
+            ..Self::zeroed()
+        }
+    }
+
+    // The same sphere moving by `velocity` over the frame interval
+    pub fn with_velocity(self, velocity: Vec3) -> Self {
+        Self { velocity, ..self }
+    }
+
+    // Corners of a box holding the sphere at every point of the frame interval
+    pub fn swept_bounds(&self) -> (Vec3, Vec3) {
+        let end = self.position + self.velocity;
+        (
+            self.position.min(end) - self.radius,
+            self.position.max(end) + self.radius,
+        )
+    }
+
+    // Distance along a ray with unit `direction` to where it enters the sphere, or leaves
+    // it for origins inside. Hits closer than 0.001 are ignored, like in the shader, and
+    // moving spheres are tested where they are at time 0.
+    pub fn ray_hit(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        let oc = self.position - origin;
+        let h = oc.dot(direction);
+        let discriminant = h * h - oc.length_squared() + self.radius * self.radius;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        [h - root, h + root].into_iter().find(|&t| t > 0.001)
+    }
+}
+
+// Resource to hold all spheres in the scene and the materials they use. Spheres with
+// identical materials share one entry, so editing an entry changes all of them.
+#[derive(Resource, Clone, Default, ExtractResource)]
 pub struct SphereCollection {
     pub spheres: Vec<GpuSphere>,
+    pub materials: Vec<GpuMaterial>,
     pub count: u32,
 }
 
-impl Default for SphereCollection {
-    fn default() -> Self {
+impl SphereCollection {
+    // Build a collection from spheres and their materials, storing each distinct material
+    // once; only these are uploaded to the GPU
+    pub fn from_spheres(spheres: Vec<(GpuSphere, GpuMaterial)>) -> Self {
+        let mut materials = Vec::new();
+        let mut indices = HashMap::new();
+        let spheres: Vec<GpuSphere> = spheres
+            .into_iter()
+            .map(|(sphere, material)| {
+                let key = (
+                    material.color.to_array().map(f32::to_bits),
+                    material.absorption.to_array().map(f32::to_bits),
+                );
+                let index = *indices.entry(key).or_insert_with(|| {
+                    materials.push(material);
+                    materials.len() as u32 - 1
+                });
+                GpuSphere {
+                    material_index: index,
+                    morph_target_index: index,
+                    morph: 0.0,
+                    ..sphere
+                }
+            })
+            .collect();
         Self {
-            spheres: Vec::with_capacity(MAX_SPHERES),
-            count: 0,
+            count: spheres.len() as u32,
+            spheres,
+            materials,
         }
     }
+
+    // Material of the sphere at `index`
+    pub fn material(&self, index: usize) -> GpuMaterial {
+        self.materials[self.spheres[index].material_index as usize]
+    }
+
+    // Give the sphere at `index` a new material. Its entry is overwritten unless other
+    // spheres share it, in which case the sphere gets an entry of its own.
+    pub fn set_material(&mut self, index: usize, material: GpuMaterial) {
+        let current = self.spheres[index].material_index;
+        let shared = self
+            .spheres
+            .iter()
+            .take(self.count as usize)
+            .enumerate()
+            .any(|(other, sphere)| {
+                other != index
+                    && (sphere.material_index == current || sphere.morph_target_index == current)
+            });
+        let slot = if shared {
+            self.materials.push(material);
+            self.materials.len() as u32 - 1
+        } else {
+            self.materials[current as usize] = material;
+            current
+        };
+
+        let sphere = &mut self.spheres[index];
+        sphere.material_index = slot;
+        sphere.morph_target_index = slot;
+        sphere.morph = 0.0;
+    }
+
+    // The spheres in use with their materials, the inverse of from_spheres
+    pub fn pairs(&self) -> impl Iterator<Item = (GpuSphere, GpuMaterial)> + '_ {
+        (0..self.count as usize).map(|index| (self.spheres[index], self.material(index)))
+    }
+
+    // Index of and distance to the closest sphere hit by a ray with unit `direction`
+    pub fn closest_hit(&self, origin: Vec3, direction: Vec3) -> Option<(usize, f32)> {
+        self.spheres
+            .iter()
+            .take(self.count as usize)
+            .enumerate()
+            .filter_map(|(index, sphere)| Some((index, sphere.ray_hit(origin, direction)?)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
 }
 
-// Helper function to create a basic collection of spheres
-pub fn create_default_spheres() -> SphereCollection {
-    let mut collection = SphereCollection::default();
+// The default scene with an infinite plane as the ground
+pub fn create_default_scene() -> (SphereCollection, PlaneCollection) {
+    let ground = GpuPlane::new(
+        Vec3::ZERO,
+        Vec3::Y,
+        SurfaceMaterial::Lambertian(Vec3::splat(0.5)).to_gpu(),
+    );
+    (
+        SphereCollection::from_spheres(default_scene_spheres()),
+        PlaneCollection::from_planes(vec![ground]),
+    )
+}
 
-    // Add a ground sphere
-    collection.spheres.push(GpuSphere {
-        position: Vec3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: GpuMaterial {
-            color: Vec4::new(0.5, 0.5, 0.5, -2.0), // Ground material (diffuse)
-        },
-    });
+// Spheres of the default scene, without the ground
+fn default_scene_spheres() -> Vec<(GpuSphere, GpuMaterial)> {
+    let mut spheres = Vec::new();
 
     // Add random smaller spheres
     // let mut rng = rand::thread_rng();
@@ -74,13 +396,10 @@ pub fn create_default_spheres() -> SphereCollection {
                         rand::random::<f32>() * rand::random::<f32>(),
                         rand::random::<f32>() * rand::random::<f32>(),
                     );
-                    collection.spheres.push(GpuSphere {
-                        position: center,
-                        radius: 0.2,
-                        material: GpuMaterial {
-                            color: Vec4::new(albedo.x, albedo.y, albedo.z, -2.0), // Diffuse
-                        },
-                    });
+                    spheres.push((
+                        GpuSphere::new(center, 0.2),
+                        SurfaceMaterial::Lambertian(albedo).to_gpu(),
+                    ));
                 } else if choose_mat < 0.95 {
                     // Metal material
                     let albedo = Vec3::new(
@@ -89,83 +408,254 @@ pub fn create_default_spheres() -> SphereCollection {
                         0.5 * (1.0 + rand::random::<f32>()),
                     );
                     let fuzz = 0.5 * rand::random::<f32>();
-                    collection.spheres.push(GpuSphere {
-                        position: center,
-                        radius: 0.2,
-                        material: GpuMaterial {
-                            color: Vec4::new(albedo.x, albedo.y, albedo.z, fuzz), // Metal with fuzz
-                        },
-                    });
+                    spheres.push((
+                        GpuSphere::new(center, 0.2),
+                        SurfaceMaterial::Metal { albedo, fuzz }.to_gpu(),
+                    ));
                 } else {
                     // Glass material
-                    collection.spheres.push(GpuSphere {
-                        position: center,
-                        radius: 0.2,
-                        material: GpuMaterial {
-                            color: Vec4::new(1.5, 0.0, 0.0, 2.0), // Glass (refractive index 1.5)
-                        },
-                    });
+                    spheres.push((
+                        GpuSphere::new(center, 0.2),
+                        SurfaceMaterial::Dielectric(1.5).to_gpu(),
+                    ));
                 }
             }
         }
     }
 
     // Add a few special spheres
-    collection.spheres.push(GpuSphere {
-        position: Vec3::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: GpuMaterial {
-            color: Vec4::new(1.5, 0.0, 0.0, 2.0), // Glass
-        },
-    });
-
-    collection.spheres.push(GpuSphere {
-        position: Vec3::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: GpuMaterial {
-            color: Vec4::new(0.4, 0.2, 0.1, -2.0), // Diffuse
-        },
-    });
-
-    collection.spheres.push(GpuSphere {
-        position: Vec3::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: GpuMaterial {
-            color: Vec4::new(0.7, 0.6, 0.5, 0.0), // Metal
-        },
-    });
-
-    // Set the actual count
-    collection.count = collection.spheres.len() as u32;
-
-    // Fill remaining slots with dummy spheres if needed
-    while collection.spheres.len() < MAX_SPHERES {
-        collection.spheres.push(GpuSphere {
-            position: Vec3::ZERO,
-            radius: 0.0,
-            material: GpuMaterial {
-                color: Vec4::ZERO,
-            },
-        });
+    spheres.push((
+        GpuSphere::new(Vec3::new(0.0, 1.0, 0.0), 1.0),
+        SurfaceMaterial::Noise {
+            base: Vec3::new(0.9, 0.9, 0.85),
+            scale: 4.0,
+        }
+        .to_gpu(),
+    ));
+
+    spheres.push((
+        GpuSphere::new(Vec3::new(-4.0, 1.0, 0.0), 1.0),
+        SurfaceMaterial::Lambertian(Vec3::new(0.4, 0.2, 0.1)).to_gpu(),
+    ));
+
+    spheres.push((
+        GpuSphere::new(Vec3::new(4.0, 1.0, 0.0), 1.0),
+        SurfaceMaterial::Metal {
+            albedo: Vec3::new(0.7, 0.6, 0.5),
+            fuzz: 0.0,
+        }
+        .to_gpu(),
+    ));
+
+    spheres
+}
+
+// Recolor the diffuse and metal spheres by sampling random pixels of a palette image.
+// Glass and emissive spheres keep their color channels, since those hold the refractive
+// index and the emitted radiance. Every sphere gets its own color, so the material table
+// is rebuilt rather than edited.
+pub fn recolor_from_palette(collection: &mut SphereCollection, palette: &Image) {
+    let (width, height) = (palette.width(), palette.height());
+    if width == 0 || height == 0 {
+        return;
     }
 
-    collection
+    let mut spheres: Vec<_> = collection.pairs().collect();
+    for (_, material) in spheres.iter_mut() {
+        let color = &mut material.color;
+        if color.w > 1.0 {
+            continue;
+        }
+
+        let x = rand::random_range(0..width);
+        let y = rand::random_range(0..height);
+        if let Ok(sample) = palette.get_color_at(x, y) {
+            let sample = sample.to_linear();
+            *color = Vec4::new(sample.red, sample.green, sample.blue, color.w);
+        }
+    }
+    *collection = SphereCollection::from_spheres(spheres);
 }
 
+// Spheres this many times larger than the median radius (e.g. a ground sphere) are
+// left out when measuring the scene's centroid and extent
+const RECENTER_OUTLIER_FACTOR: f32 = 10.0;
+
+// Translate all spheres so the scene's centroid sits at the origin. With `target_radius`,
+// the scene is also scaled uniformly so its bounding sphere has that radius.
+pub fn recenter_spheres(collection: &mut SphereCollection, target_radius: Option<f32>) {
+    let count = collection.count as usize;
+    let spheres = &mut collection.spheres[..count];
+    if spheres.is_empty() {
+        return;
+    }
+
+    let mut radii: Vec<f32> = spheres.iter().map(|sphere| sphere.radius).collect();
+    radii.sort_by(f32::total_cmp);
+    let max_radius = radii[radii.len() / 2] * RECENTER_OUTLIER_FACTOR;
+    let measured = || spheres.iter().filter(|sphere| sphere.radius <= max_radius);
+
+    let centroid =
+        measured().map(|sphere| sphere.position).sum::<Vec3>() / measured().count() as f32;
+    let extent = measured()
+        .map(|sphere| (sphere.position - centroid).length() + sphere.radius)
+        .fold(0.0, f32::max);
+    let scale = match target_radius {
+        Some(radius) if extent > 0.0 => radius / extent,
+        _ => 1.0,
+    };
+
+    for sphere in spheres.iter_mut() {
+        sphere.position = (sphere.position - centroid) * scale;
+        sphere.radius *= scale;
+    }
+}
+
+// Palette image used by the P key, relative to the assets folder
+const PALETTE_ASSET_PATH: &str = "palettes/sunset.png";
+
+// Palette that has been requested but not applied yet because it is still loading
+#[derive(Resource)]
+struct PendingPalette(Handle<Image>);
+
 // Plugin to handle sphere setup and extraction
 pub struct SpheresPlugin;
 
 impl Plugin for SpheresPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<SphereCollection>()
-            .add_systems(Startup, setup_spheres)
-            .add_plugins(ExtractResourcePlugin::<SphereCollection>::default());
+            .init_resource::<super::boxes::BoxCollection>()
+            .init_resource::<super::mesh::MeshCollection>()
+            .init_resource::<PlaneCollection>()
+            .init_resource::<super::quad::QuadCollection>()
+            .init_resource::<super::disk::DiskCollection>()
+            .init_resource::<super::instances::SphereInstances>()
+            .init_resource::<super::sdf::SdfCollection>()
+            .init_resource::<super::bvh::SphereBvh>()
+            .init_resource::<super::morph::MaterialAnimation>()
+            .init_resource::<super::noise::NoiseSettings>()
+            .init_asset::<super::watch::SceneFile>()
+            .init_asset_loader::<super::watch::SceneFileLoader>()
+            .add_systems(Startup, (setup_spheres, super::watch::watch_scene_file))
+            .add_systems(
+                Update,
+                (
+                    request_palette,
+                    apply_pending_palette,
+                    recenter_scene_system,
+                    super::presets::cycle_scene_preset_system,
+                    super::morph::animate_material,
+                    super::hash::log_scene_hash_system,
+                    super::watch::reload_scene_file_system,
+                    super::bvh::toggle_sphere_bvh_system,
+                ),
+            )
+            .add_systems(PostUpdate, super::bvh::update_sphere_bvh_system)
+            .add_plugins((
+                ExtractResourcePlugin::<SphereCollection>::default(),
+                ExtractResourcePlugin::<super::boxes::BoxCollection>::default(),
+                ExtractResourcePlugin::<super::mesh::MeshCollection>::default(),
+                ExtractResourcePlugin::<PlaneCollection>::default(),
+                ExtractResourcePlugin::<super::quad::QuadCollection>::default(),
+                ExtractResourcePlugin::<super::disk::DiskCollection>::default(),
+                ExtractResourcePlugin::<super::instances::SphereInstances>::default(),
+                ExtractResourcePlugin::<super::sdf::SdfCollection>::default(),
+                ExtractResourcePlugin::<super::bvh::SphereBvh>::default(),
+                ExtractResourcePlugin::<super::noise::NoiseSettings>::default(),
+            ));
+    }
+}
+
+// Load the scene file if there is one (RON first, then the text format), falling back to
+// the default scene
+pub fn load_startup_scene() -> (SphereCollection, PlaneCollection) {
+    let path = if std::path::Path::new(RON_SCENE_FILE_PATH).exists() {
+        RON_SCENE_FILE_PATH
+    } else {
+        SCENE_FILE_PATH
+    };
+    match load_scene_from_file(path) {
+        Ok(spheres) => (spheres, PlaneCollection::default()),
+        Err(SceneLoadError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+            create_default_scene()
+        }
+        Err(err) => {
+            error!("Falling back to the default scene: {err}");
+            create_default_scene()
+        }
     }
 }
 
-// Initialize the sphere collection at startup
-fn setup_spheres(mut commands: Commands) {
-    let spheres = create_default_spheres();
-    commands.insert_resource(spheres);
+// Initialize the sphere collection and the scene presets at startup
+fn setup_spheres(mut commands: Commands, scene: Option<Res<super::builder::RayTracedScene>>) {
+    let mut presets = super::presets::ScenePresets::load();
+    // A scene built in Rust replaces the startup scene
+    if let Some(scene) = scene {
+        presets.presets[0] = super::presets::ScenePreset {
+            name: "custom".to_string(),
+            spheres: scene.spheres.clone(),
+            planes: scene.planes.clone(),
+        };
+        commands.insert_resource(scene.boxes.clone());
+        commands.insert_resource(scene.quads.clone());
+        commands.insert_resource(scene.disks.clone());
+        commands.insert_resource(scene.instances.clone());
+        commands.insert_resource(scene.sdfs.clone());
+        commands.insert_resource(scene.mesh.clone());
+        commands.insert_resource(super::noise::NoiseSettings {
+            seed: scene.noise_seed,
+        });
+    } else if std::path::Path::new(super::mesh::MESH_FILE_PATH).exists() {
+        commands.insert_resource(super::mesh::load_obj(super::mesh::MESH_FILE_PATH));
+    }
+    commands.insert_resource(presets.presets[0].spheres.clone());
+    commands.insert_resource(presets.presets[0].planes.clone());
+    commands.insert_resource(presets);
 }
   
+
+// Start loading the palette image when P is pressed
+fn request_palette(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    asset_server: Res<AssetServer>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        commands.insert_resource(PendingPalette(asset_server.load(PALETTE_ASSET_PATH)));
+    }
+}
+
+// Recolor the scene once the requested palette is available and restart accumulation
+fn apply_pending_palette(
+    mut commands: Commands,
+    pending: Option<Res<PendingPalette>>,
+    images: Res<Assets<Image>>,
+    mut spheres: ResMut<SphereCollection>,
+    mut camera_settings: ResMut<crate::camera::CameraSettings>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+    let Some(palette) = images.get(&pending.0) else {
+        return;
+    };
+
+    recolor_from_palette(&mut spheres, palette);
+    camera_settings.camera_has_moved = true;
+    commands.remove_resource::<PendingPalette>();
+}
+
+// Recenter the scene on C; Shift+C also scales it to unit size
+fn recenter_scene_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut spheres: ResMut<SphereCollection>,
+    mut camera_settings: ResMut<crate::camera::CameraSettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+
+    let scale_to_unit = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    recenter_spheres(&mut spheres, scale_to_unit.then_some(1.0));
+    camera_settings.camera_has_moved = true;
+}
@@ -0,0 +1,129 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, LoadContext},
+    prelude::*,
+};
+use std::io;
+
+use super::{
+    loader::{parse_ron_scene, SceneLoadError, RON_SCENE_FILE_PATH},
+    presets::ScenePresets,
+    sphere::SphereCollection,
+};
+use crate::camera::CameraSettings;
+
+// RON_SCENE_FILE_PATH relative to the assets folder, as the asset server sees it
+const RON_SCENE_ASSET_PATH: &str = "scene.ron";
+
+// Editors often write a file several times per save, so wait until it has been quiet for
+// this long before swapping the scene in
+const RELOAD_DEBOUNCE_SECONDS: f32 = 0.25;
+
+// Spheres from a RON scene file, loaded through the asset server so edits are picked up
+#[derive(Asset, TypePath)]
+pub struct SceneFile {
+    pub spheres: SphereCollection,
+}
+
+// Asset loader for RON scene files, see loader::load_scene for the format
+#[derive(Default)]
+pub struct SceneFileLoader;
+
+impl AssetLoader for SceneFileLoader {
+    type Asset = SceneFile;
+    type Settings = ();
+    type Error = SceneLoadError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<SceneFile, SceneLoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let source = String::from_utf8(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(SceneFile {
+            spheres: parse_ron_scene(&source)?,
+        })
+    }
+
+    // Only `.scene.ron`, so the host app's own RON assets keep their loaders. The watched
+    // scene.ron still comes here, since a typed load picks the one loader for SceneFile
+    // before looking at the extension.
+    fn extensions(&self) -> &[&str] {
+        &["scene.ron"]
+    }
+}
+
+// The watched scene file and the reload waiting for the file to settle, if any
+#[derive(Resource)]
+pub struct SceneFileWatch {
+    handle: Handle<SceneFile>,
+    pending: Option<Timer>,
+}
+
+// Start watching the RON scene file if it was there at startup. Without it the startup
+// scene comes from the text file or the default scene and nothing is watched. A scene
+// built in Rust is never replaced.
+pub fn watch_scene_file(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    scene: Option<Res<super::builder::RayTracedScene>>,
+) {
+    if scene.is_some() || !std::path::Path::new(RON_SCENE_FILE_PATH).exists() {
+        return;
+    }
+    commands.insert_resource(SceneFileWatch {
+        handle: asset_server.load(RON_SCENE_ASSET_PATH),
+        pending: None,
+    });
+}
+
+/// System to replace the startup preset when the RON scene file is saved, showing it right
+/// away if that preset is on screen. Files that fail to parse are logged by the asset
+/// server and leave the current scene alone.
+pub fn reload_scene_file_system(
+    mut events: EventReader<AssetEvent<SceneFile>>,
+    time: Res<Time>,
+    watch: Option<ResMut<SceneFileWatch>>,
+    scene_files: Res<Assets<SceneFile>>,
+    mut presets: ResMut<ScenePresets>,
+    mut spheres: ResMut<SphereCollection>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    let Some(mut watch) = watch else {
+        return;
+    };
+
+    // The first load matches the scene read at startup, so only modifications count
+    for event in events.read() {
+        if event.is_modified(&watch.handle) {
+            watch.pending = Some(Timer::from_seconds(
+                RELOAD_DEBOUNCE_SECONDS,
+                TimerMode::Once,
+            ));
+        }
+    }
+
+    let Some(timer) = watch.pending.as_mut() else {
+        return;
+    };
+    if !timer.tick(time.delta()).finished() {
+        return;
+    }
+    watch.pending = None;
+    let Some(scene_file) = scene_files.get(&watch.handle) else {
+        return;
+    };
+
+    info!(
+        "Reloaded {RON_SCENE_FILE_PATH} with {} spheres",
+        scene_file.spheres.count
+    );
+    presets.presets[0].spheres = scene_file.spheres.clone();
+    if presets.current == 0 {
+        *spheres = scene_file.spheres.clone();
+        camera_settings.camera_has_moved = true;
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::{color::palettes::css::LIME, prelude::*};
+
+use crate::{
+    camera::{CameraSettings, SceneCamera},
+    scene::sphere::SphereCollection,
+};
+
+// Time taken to fly the camera to a newly selected sphere
+const FRAME_TRANSITION_SECONDS: f32 = 0.75;
+
+// Camera move towards a framed sphere that is in progress
+struct FrameTransition {
+    from: (Vec3, Vec3),
+    to: (Vec3, Vec3),
+    elapsed: f32,
+}
+
+// Sphere picked with Tab/Shift+Tab, which the camera frames in turn
+#[derive(Resource)]
+pub struct SelectedSphere {
+    pub index: Option<usize>,
+    // Outline the selected sphere on screen
+    pub highlight: bool,
+    transition: Option<FrameTransition>,
+}
+
+impl Default for SelectedSphere {
+    fn default() -> Self {
+        Self {
+            index: None,
+            highlight: true,
+            transition: None,
+        }
+    }
+}
+
+/// System to cycle the selected sphere with Tab (Shift+Tab goes backwards) and smoothly
+/// move the camera so it frames the selection. Escape clears the selection.
+pub fn cycle_selection_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    spheres: Res<SphereCollection>,
+    mut selected: ResMut<SelectedSphere>,
+    mut camera_settings: ResMut<CameraSettings>,
+    render_size: Res<crate::RenderSize>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        selected.index = None;
+    }
+
+    let count = spheres.count as usize;
+    if keyboard_input.just_pressed(KeyCode::Tab) && count > 0 {
+        let backwards = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        let index = match (selected.index, backwards) {
+            (Some(index), false) => (index + 1) % count,
+            (Some(index), true) => (index + count - 1) % count,
+            (None, false) => 0,
+            (None, true) => count - 1,
+        };
+        let sphere = &spheres.spheres[index];
+        info!(
+            "Selected sphere {index} at {} (radius {})",
+            sphere.position, sphere.radius
+        );
+
+        let look_from = camera_settings.frame_sphere(sphere.position, sphere.radius, **render_size);
+        selected.index = Some(index);
+        selected.transition = Some(FrameTransition {
+            from: (camera_settings.look_from, camera_settings.look_at),
+            to: (look_from, sphere.position),
+            elapsed: 0.0,
+        });
+    }
+
+    let Some(transition) = selected.transition.as_mut() else {
+        return;
+    };
+    transition.elapsed += time.delta_secs();
+    let t = (transition.elapsed / FRAME_TRANSITION_SECONDS).min(1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+    camera_settings.look_from = transition.from.0.lerp(transition.to.0, t);
+    camera_settings.look_at = transition.from.1.lerp(transition.to.1, t);
+    camera_settings.focus_distance = camera_settings.look_from.distance(camera_settings.look_at);
+    camera_settings.camera_has_moved = true;
+    if t >= 1.0 {
+        selected.transition = None;
+    }
+}
+
+/// System to outline the selected sphere on screen
+pub fn draw_selection_system(
+    mut gizmos: Gizmos,
+    selected: Res<SelectedSphere>,
+    spheres: Res<SphereCollection>,
+    scene_camera: Option<Res<SceneCamera>>,
+    render_size: Res<crate::RenderSize>,
+) {
+    let (Some(index), Some(scene_camera)) = (selected.index, scene_camera) else {
+        return;
+    };
+    if !selected.highlight || index >= spheres.count as usize {
+        return;
+    }
+
+    let sphere = &spheres.spheres[index];
+    let edge = sphere.position + scene_camera.pixel_delta_u.normalize() * sphere.radius;
+    let (Some(center), Some(edge)) = (
+        scene_camera.project_to_pixel(sphere.position),
+        scene_camera.project_to_pixel(edge),
+    ) else {
+        return;
+    };
+
+    let radius = center.distance(edge) * crate::DISPLAY_FACTOR as f32;
+    gizmos.circle_2d(
+        Isometry2d::from_translation(crate::grid::pixel_to_sprite(center, **render_size)),
+        radius,
+        LIME,
+    );
+}
@@ -0,0 +1,192 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::camera::CameraSettings;
+
+// Sky shading used when a ray escapes the scene: a gradient from bottom_color straight
+// down to top_color straight up
+#[derive(Resource, Debug, Clone, ExtractResource)]
+pub struct SkySettings {
+    // Radiance multiplier applied on top of the sky color, without changing its hue
+    pub intensity: f32,
+    pub top_color: Vec3,
+    pub bottom_color: Vec3,
+    // Shapes the gradient: above 1 keeps the bottom color further up the sky, below 1
+    // brings the top color down towards the horizon
+    pub gradient_power: f32,
+    // Fill light added to the first surface each ray hits, times its albedo, so fully
+    // shadowed areas aren't black in quick previews. It isn't physically based; zero, the
+    // default, leaves it out.
+    pub ambient: Vec3,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            top_color: Vec3::new(0.5, 0.7, 1.0),
+            bottom_color: Vec3::ONE,
+            gradient_power: 1.0,
+            ambient: Vec3::ZERO,
+        }
+    }
+}
+
+impl SkySettings {
+    // A dark backdrop with a faint light from above, for scenes lit by their own emitters
+    pub fn studio() -> Self {
+        Self {
+            intensity: 1.0,
+            top_color: Vec3::splat(0.08),
+            bottom_color: Vec3::splat(0.01),
+            gradient_power: 2.0,
+            ambient: Vec3::ZERO,
+        }
+    }
+}
+
+// Equirectangular HDR image, relative to the assets folder, loaded at startup if present
+const ENVIRONMENT_MAP_PATH: &str = "environment.hdr";
+
+// Environment map shown instead of the sky gradient when a ray escapes the scene. The
+// gradient is used while the image is missing or still loading.
+#[derive(Resource, Debug, Clone, Default, ExtractResource)]
+pub struct EnvironmentMap {
+    pub image: Option<Handle<Image>>,
+}
+
+// Load assets/environment.hdr as the environment map, if there is one
+pub fn setup_environment_map(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let path = format!("assets/{ENVIRONMENT_MAP_PATH}");
+    let image = std::path::Path::new(&path)
+        .exists()
+        .then(|| asset_server.load(ENVIRONMENT_MAP_PATH));
+    commands.insert_resource(EnvironmentMap { image });
+}
+
+// GPU-compatible sky representation that matches shader's expectations
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, ShaderType, Pod, Zeroable)]
+pub struct GpuSky {
+    pub top_color: Vec3,
+    pub intensity: f32,
+    pub bottom_color: Vec3,
+    pub gradient_power: f32,
+    pub ambient: Vec3,
+    // 1 if the environment map is bound and should replace the gradient
+    pub environment_map: u32,
+}
+
+impl GpuSky {
+    pub fn new(settings: &SkySettings, environment_map: bool) -> Self {
+        Self {
+            top_color: settings.top_color,
+            intensity: settings.intensity,
+            bottom_color: settings.bottom_color,
+            gradient_power: settings.gradient_power,
+            ambient: settings.ambient,
+            environment_map: environment_map as u32,
+        }
+    }
+}
+
+// Sky intensity constants
+const SKY_INTENSITY_STEP: f32 = 1.25; // Multiplier per key press
+const SKY_INTENSITY_MIN: f32 = 0.01;
+const SKY_INTENSITY_MAX: f32 = 100.0;
+
+// Ambient light Shift+U switches on when the sky has none of its own
+const DEFAULT_AMBIENT: Vec3 = Vec3::splat(0.1);
+
+/// System to dim/brighten the sky with [ and ]
+pub fn sky_intensity_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut sky_settings: ResMut<SkySettings>,
+) {
+    let mut intensity = sky_settings.intensity;
+    if keyboard_input.just_pressed(KeyCode::BracketLeft) {
+        intensity /= SKY_INTENSITY_STEP;
+    }
+    if keyboard_input.just_pressed(KeyCode::BracketRight) {
+        intensity *= SKY_INTENSITY_STEP;
+    }
+    let intensity = intensity.clamp(SKY_INTENSITY_MIN, SKY_INTENSITY_MAX);
+
+    if intensity != sky_settings.intensity {
+        sky_settings.intensity = intensity;
+        info!("Sky intensity: {intensity:.3}");
+    }
+}
+
+/// System to switch between the default sky and the studio backdrop with U, keeping the
+/// intensity and the ambient light
+pub fn toggle_studio_sky_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut sky_settings: ResMut<SkySettings>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyU)
+        || keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    {
+        return;
+    }
+
+    let studio = SkySettings::studio();
+    let is_studio = sky_settings.top_color == studio.top_color
+        && sky_settings.bottom_color == studio.bottom_color
+        && sky_settings.gradient_power == studio.gradient_power;
+    let next = if is_studio {
+        SkySettings::default()
+    } else {
+        studio
+    };
+    *sky_settings = SkySettings {
+        intensity: sky_settings.intensity,
+        ambient: sky_settings.ambient,
+        ..next
+    };
+    info!("Studio sky: {}", !is_studio);
+}
+
+/// System to turn the ambient fill light off with Shift+U, or back on at the last level it
+/// had
+pub fn toggle_ambient_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut sky_settings: ResMut<SkySettings>,
+    mut last_ambient: Local<Option<Vec3>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyU)
+        || !keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight])
+    {
+        return;
+    }
+
+    if sky_settings.ambient == Vec3::ZERO {
+        sky_settings.ambient = last_ambient.unwrap_or(DEFAULT_AMBIENT);
+        info!("Ambient light: {}", sky_settings.ambient);
+    } else {
+        *last_ambient = Some(sky_settings.ambient);
+        sky_settings.ambient = Vec3::ZERO;
+        info!("Ambient light: off");
+    }
+}
+
+/// System to restart accumulation whenever the sky changes, including when the
+/// environment map finishes loading and replaces the gradient
+pub fn reset_on_sky_change(
+    sky_settings: Res<SkySettings>,
+    environment_map: Res<EnvironmentMap>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    let environment_loaded = image_events.read().any(|event| {
+        environment_map.image.as_ref().is_some_and(|image| {
+            event.is_loaded_with_dependencies(image) || event.is_modified(image)
+        })
+    });
+    if (sky_settings.is_changed() && !sky_settings.is_added()) || environment_loaded {
+        camera_settings.camera_has_moved = true;
+    }
+}
@@ -0,0 +1,65 @@
+use bevy::{prelude::*, render::extract_resource::ExtractResource};
+
+use crate::camera::CameraSettings;
+
+// Single-step mode for debugging accumulation: while enabled, the compute pass only
+// runs on frames where a step was requested
+#[derive(Resource, Debug, Clone, Default, ExtractResource)]
+pub struct StepMode {
+    pub enabled: bool,
+    // One-shot request, only true for the frame the step key was pressed
+    pub step_requested: bool,
+    // Set by sample_budget_system while a still camera has used up its sample budget;
+    // the compute pass stays idle until the camera moves
+    pub budget_reached: bool,
+}
+
+impl StepMode {
+    // Whether the compute pass should run this frame
+    pub fn should_dispatch(&self) -> bool {
+        !self.budget_reached && (!self.enabled || self.step_requested)
+    }
+}
+
+/// System to stop rendering once the image has CameraSettings::sample_budget samples,
+/// or samples_per_pixel if that is fewer, and to start again when the camera moves
+pub fn sample_budget_system(camera_settings: Res<CameraSettings>, mut step_mode: ResMut<StepMode>) {
+    let budget = camera_settings.sample_budget;
+    // Accumulation stops at samples_per_pixel, so a larger budget would never be reached
+    let samples = budget.min(camera_settings.samples_per_pixel);
+    let budget_reached = budget > 0
+        && camera_settings.accumulated_frames >= samples
+        && !camera_settings.camera_has_moved;
+    if step_mode.budget_reached != budget_reached {
+        step_mode.budget_reached = budget_reached;
+        if budget_reached {
+            info!("Converged at {samples} samples, paused until the camera moves");
+        }
+    }
+}
+
+/// System to toggle step mode with M and request a single step with N. Leaving step mode
+/// carries on from the samples already accumulated.
+pub fn step_mode_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    camera_settings: Res<CameraSettings>,
+    mut step_mode: ResMut<StepMode>,
+) {
+    if keyboard_input.just_pressed(KeyCode::KeyM) {
+        step_mode.enabled = !step_mode.enabled;
+        info!(
+            "Step mode: {} at {} samples",
+            step_mode.enabled, camera_settings.accumulated_frames
+        );
+    }
+
+    let step_requested = keyboard_input.just_pressed(KeyCode::KeyN);
+    if step_requested && step_mode.enabled {
+        // Step mode takes one sample per frame, see CameraSettings::samples_this_frame
+        info!("Step to sample {}", camera_settings.accumulated_frames + 1);
+    }
+    // Avoid triggering change detection every frame
+    if step_mode.step_requested != step_requested {
+        step_mode.step_requested = step_requested;
+    }
+}
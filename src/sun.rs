@@ -0,0 +1,134 @@
+use bevy::{
+    prelude::*,
+    render::{extract_resource::ExtractResource, render_resource::ShaderType},
+};
+use bytemuck::{Pod, Zeroable};
+
+use crate::camera::CameraSettings;
+
+// Angular radius of the real sun's disk, in degrees
+pub const SUN_ANGULAR_RADIUS: f32 = 0.27;
+
+// Radians per second the sun turns while ; or ' is held
+const SUN_TURN_SPEED: f32 = 0.5;
+// Elevation the sun is kept within, in radians, short of straight up or down where the
+// azimuth is lost
+const SUN_MAX_ELEVATION: f32 = 1.5;
+
+// Directional light infinitely far away, for outdoor scenes. Diffuse surfaces trace a
+// shadow ray towards a random point on its disk, so shadows are sharp near the object
+// casting them and soften with distance, by how large the disk looks. It only lights
+// surfaces this way: it isn't drawn in the sky or seen in reflections.
+#[derive(Resource, Debug, Clone, ExtractResource)]
+pub struct SunLight {
+    // Off by default, so scenes are lit by their emitters and the sky alone
+    pub enabled: bool,
+    // Direction towards the sun
+    pub direction: Vec3,
+    pub color: Vec3,
+    // Irradiance on a surface facing the sun, as a multiple of `color`. It doesn't change
+    // with angular_radius, which only softens the shadows.
+    pub intensity: f32,
+    // Angular radius of the sun's disk in degrees; 0 casts hard shadows
+    pub angular_radius: f32,
+}
+
+impl Default for SunLight {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            direction: Vec3::new(0.5, 1.0, 0.3).normalize(),
+            color: Vec3::ONE,
+            intensity: 3.0,
+            angular_radius: SUN_ANGULAR_RADIUS,
+        }
+    }
+}
+
+impl SunLight {
+    // A sun that is on, shining from `direction`
+    pub fn new(direction: Vec3, color: Vec3, intensity: f32, angular_radius: f32) -> Self {
+        Self {
+            enabled: true,
+            direction: direction.normalize_or(Vec3::Y),
+            color,
+            intensity,
+            angular_radius,
+        }
+    }
+}
+
+// GPU-compatible sun that matches the shader's expectations
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, ShaderType, Pod, Zeroable)]
+pub struct GpuSun {
+    pub direction: Vec3,
+    // Cosine of the angular radius, the edge of the cone directions are sampled in
+    pub cos_angular_radius: f32,
+    // color * intensity, or zero when the sun is off
+    pub irradiance: Vec3,
+    // 1 if the shader should trace rays towards the sun
+    pub enabled: u32,
+}
+
+impl GpuSun {
+    pub fn new(sun: &SunLight) -> Self {
+        Self {
+            direction: sun.direction.normalize_or(Vec3::Y),
+            cos_angular_radius: sun.angular_radius.clamp(0.0, 90.0).to_radians().cos(),
+            irradiance: sun.color * sun.intensity,
+            enabled: sun.enabled as u32,
+        }
+    }
+}
+
+/// System to turn the sun on or off with End
+pub fn toggle_sun_system(keyboard_input: Res<ButtonInput<KeyCode>>, mut sun: ResMut<SunLight>) {
+    if !keyboard_input.just_pressed(KeyCode::End) {
+        return;
+    }
+
+    sun.enabled = !sun.enabled;
+    info!("Sun: {}", sun.enabled);
+}
+
+/// System to turn the sun around the vertical axis while ; or ' is held, or to lower or
+/// raise it with Shift held
+pub fn rotate_sun_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    mut sun: ResMut<SunLight>,
+) {
+    let mut turn = 0.0;
+    if keyboard_input.pressed(KeyCode::Semicolon) {
+        turn -= SUN_TURN_SPEED * time.delta_secs();
+    }
+    if keyboard_input.pressed(KeyCode::Quote) {
+        turn += SUN_TURN_SPEED * time.delta_secs();
+    }
+    // Turning a sun that is off would only restart accumulation
+    if turn == 0.0 || !sun.enabled {
+        return;
+    }
+
+    let direction = sun.direction.normalize_or(Vec3::Y);
+    let mut azimuth = direction.z.atan2(direction.x);
+    let mut elevation = direction.y.clamp(-1.0, 1.0).asin();
+    if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        elevation = (elevation + turn).clamp(-SUN_MAX_ELEVATION, SUN_MAX_ELEVATION);
+    } else {
+        azimuth += turn;
+    }
+    sun.direction = Vec3::new(
+        elevation.cos() * azimuth.cos(),
+        elevation.sin(),
+        elevation.cos() * azimuth.sin(),
+    );
+}
+
+/// System to restart accumulation whenever the sun changes
+pub fn reset_on_sun_change(sun: Res<SunLight>, mut camera_settings: ResMut<CameraSettings>) {
+    if sun.is_changed() && !sun.is_added() {
+        camera_settings.camera_has_moved = true;
+    }
+}
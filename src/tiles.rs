@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+use crate::{camera::CameraSettings, step::StepMode};
+
+// Tile side F8 switches to
+const DEFAULT_TILE_SIZE: u32 = 256;
+
+// Tiled rendering for large images: each frame traces rays for one tile only, and the
+// rest of the image is carried over, so no single dispatch runs long enough to stall the
+// desktop or trip the driver's timeout. A pass over every tile adds one frame of samples.
+// Frames that reset the image, such as the previews of a moving camera, still render all
+// of it, see extract_camera.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct TiledRendering {
+    // Side of a tile in pixels; 0 renders the whole image every frame
+    pub tile_size: u32,
+    // Tile the coming frame renders, in rows from the top left
+    pub tile: u32,
+}
+
+impl TiledRendering {
+    // Tiles across and down a render target of `size` pixels
+    pub fn grid(&self, size: UVec2) -> UVec2 {
+        if self.tile_size == 0 {
+            return UVec2::ONE;
+        }
+        (size.max(UVec2::ONE) + self.tile_size - 1) / self.tile_size
+    }
+
+    // Number of tiles in a pass over the image
+    pub fn count(&self, size: UVec2) -> u32 {
+        let grid = self.grid(size);
+        grid.x * grid.y
+    }
+
+    // Corners of the current tile in pixels, clamped to the render target
+    pub fn bounds(&self, size: UVec2) -> (UVec2, UVec2) {
+        if self.tile_size == 0 {
+            return (UVec2::ZERO, size);
+        }
+        let grid = self.grid(size);
+        let tile = self.tile % (grid.x * grid.y);
+        let min = UVec2::new(tile % grid.x, tile / grid.x) * self.tile_size;
+        (min, (min + self.tile_size).min(size))
+    }
+}
+
+/// System to toggle tiled rendering with F8
+pub fn toggle_tiled_rendering_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    render_size: Res<crate::RenderSize>,
+    mut tiles: ResMut<TiledRendering>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F8) {
+        return;
+    }
+    tiles.tile_size = if tiles.tile_size == 0 {
+        DEFAULT_TILE_SIZE
+    } else {
+        0
+    };
+    // Start the next pass from the first tile
+    tiles.tile = tiles.count(**render_size).saturating_sub(1);
+    match tiles.tile_size {
+        0 => info!("Tiled rendering: off"),
+        size => info!("Tiled rendering: {size}x{size} tiles"),
+    }
+}
+
+/// System to move on to the next tile on every frame that runs the compute pass. A reset
+/// renders the whole image, so the pass after it starts over from the first tile.
+pub fn advance_tile_system(
+    camera_settings: Res<CameraSettings>,
+    step_mode: Res<StepMode>,
+    render_size: Res<crate::RenderSize>,
+    mut tiles: ResMut<TiledRendering>,
+) {
+    if tiles.tile_size == 0 || !step_mode.should_dispatch() {
+        return;
+    }
+    let tile = if camera_settings.camera_has_moved {
+        tiles.count(**render_size) - 1
+    } else {
+        (tiles.tile + 1) % tiles.count(**render_size)
+    };
+    // Avoid triggering change detection every frame
+    if tiles.tile != tile {
+        tiles.tile = tile;
+    }
+}
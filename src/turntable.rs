@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+use std::{f32::consts::TAU, path::PathBuf};
+
+use crate::{
+    camera::CameraSettings,
+    display::DisplayedImage,
+    output::{capture_png, CaptureFailed, CaptureSaved},
+};
+
+// Settings for rendering a turntable image sequence
+#[derive(Resource, Debug, Clone)]
+pub struct TurntableSettings {
+    // Number of frames in a full revolution
+    pub steps: u32,
+    // Frames accumulated before each capture (capped by samples_per_pixel)
+    pub samples: u32,
+    pub output_dir: PathBuf,
+}
+
+impl Default for TurntableSettings {
+    fn default() -> Self {
+        Self {
+            steps: 36,
+            samples: 256,
+            output_dir: PathBuf::from("turntable"),
+        }
+    }
+}
+
+enum TurntablePhase {
+    Accumulating,
+    // Waiting for the frame at this path to be written
+    Saving(PathBuf),
+}
+
+// Progress of a turntable render that is currently running
+#[derive(Resource)]
+pub struct Turntable {
+    step: u32,
+    // Camera position relative to look_at at the first step
+    start_offset: Vec3,
+    phase: TurntablePhase,
+}
+
+/// System to orbit the camera around look_at in equal steps, converging and saving a frame at each
+pub fn turntable_system(
+    mut commands: Commands,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    settings: Res<TurntableSettings>,
+    turntable: Option<ResMut<Turntable>>,
+    mut camera_settings: ResMut<CameraSettings>,
+    (mut saved, mut failed): (EventReader<CaptureSaved>, EventReader<CaptureFailed>),
+    (displayed, render_size): (Res<DisplayedImage>, Res<crate::RenderSize>),
+) {
+    let Some(mut turntable) = turntable else {
+        if keyboard_input.just_pressed(KeyCode::F9) && settings.steps > 0 {
+            info!("Rendering a {}-frame turntable", settings.steps);
+            commands.insert_resource(Turntable {
+                step: 0,
+                start_offset: camera_settings.look_from - camera_settings.look_at,
+                phase: TurntablePhase::Accumulating,
+            });
+            camera_settings.camera_has_moved = true;
+        }
+        return;
+    };
+
+    match turntable.phase {
+        TurntablePhase::Accumulating => {
+            let mut target = settings.samples.min(camera_settings.samples_per_pixel);
+            // Accumulation pauses at the sample budget
+            if camera_settings.sample_budget > 0 {
+                target = target.min(camera_settings.sample_budget);
+            }
+            if camera_settings.accumulated_frames >= target {
+                let path = settings
+                    .output_dir
+                    .join(format!("frame_{:04}.png", turntable.step));
+                capture_png(
+                    &mut commands,
+                    displayed.0.clone(),
+                    **render_size,
+                    path.clone(),
+                );
+                turntable.phase = TurntablePhase::Saving(path);
+            }
+        }
+        TurntablePhase::Saving(ref path) => {
+            // The rest of the frames would most likely fail the same way, so stop instead of
+            // waiting for a frame that never arrives
+            if failed
+                .read()
+                .any(|CaptureFailed(failed_path)| failed_path == path)
+            {
+                warn!("Turntable stopped at frame {}", turntable.step);
+                commands.remove_resource::<Turntable>();
+                return;
+            }
+            if !saved
+                .read()
+                .any(|CaptureSaved(saved_path)| saved_path == path)
+            {
+                return;
+            }
+
+            turntable.step += 1;
+            if turntable.step == settings.steps {
+                info!("Turntable finished");
+                commands.remove_resource::<Turntable>();
+                return;
+            }
+
+            let angle = TAU * turntable.step as f32 / settings.steps as f32;
+            camera_settings.look_from =
+                camera_settings.look_at + Quat::from_rotation_y(angle) * turntable.start_offset;
+            camera_settings.camera_has_moved = true;
+            turntable.phase = TurntablePhase::Accumulating;
+        }
+    }
+}
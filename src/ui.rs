@@ -0,0 +1,327 @@
+use bevy::{
+    diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_egui::{egui, EguiContexts, EguiPlugin};
+
+use crate::{
+    camera::CameraSettings,
+    integrator::MAX_DEPTH_RANGE,
+    plugin::InputSet,
+    scene::sphere::{MaterialKind, SphereCollection, SurfaceMaterial},
+    selection::SelectedSphere,
+    ShaderStatus,
+};
+
+// Material kinds offered by the editor, in the order of its dropdown
+const MATERIAL_KINDS: [MaterialKind; 5] = [
+    MaterialKind::Lambertian,
+    MaterialKind::Metal,
+    MaterialKind::Dielectric,
+    MaterialKind::Emissive,
+    MaterialKind::Noise,
+];
+
+// Values the stats overlay offers for CameraSettings::samples_per_frame
+const SAMPLES_PER_FRAME_RANGE: std::ops::RangeInclusive<u32> = 1..=16;
+
+// Egui windows with render statistics and a material editor, for tuning the render and
+// the scene without recompiling. Only built with the `ui` feature.
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((EguiPlugin, FrameTimeDiagnosticsPlugin))
+            .init_resource::<EguiWantsInput>()
+            .configure_sets(
+                Update,
+                (
+                    InputSet::Pointer.run_if(|wants: Res<EguiWantsInput>| !wants.pointer),
+                    InputSet::Keyboard.run_if(|wants: Res<EguiWantsInput>| !wants.keyboard),
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    egui_wants_input_system
+                        .before(InputSet::Pointer)
+                        .before(InputSet::Keyboard),
+                    stats_overlay_system,
+                    material_editor_system,
+                    shader_error_system,
+                ),
+            );
+    }
+}
+
+// Whether egui uses the mouse or the keyboard itself, for the run conditions on InputSet.
+// It takes the mouse while the pointer is over one of its windows or dragging a widget, and
+// the keyboard while a text field has focus.
+#[derive(Resource, Default)]
+struct EguiWantsInput {
+    pointer: bool,
+    keyboard: bool,
+}
+
+/// System to record whether egui wants the mouse or the keyboard before the systems that
+/// read them run
+fn egui_wants_input_system(mut contexts: EguiContexts, mut wants: ResMut<EguiWantsInput>) {
+    let (pointer, keyboard) = match contexts.try_ctx_mut() {
+        Some(ctx) => (ctx.wants_pointer_input(), ctx.wants_keyboard_input()),
+        None => (false, false),
+    };
+    wants.pointer = pointer;
+    wants.keyboard = keyboard;
+}
+
+/// System to show the error from compiling the compute shader at the bottom of the window
+/// until the fixed shader compiles
+fn shader_error_system(mut contexts: EguiContexts, shader_status: Res<ShaderStatus>) {
+    let Some(error) = shader_status.error() else {
+        return;
+    };
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    egui::Window::new("Shader error")
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -12.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label("The compute shader failed to compile; showing the last good frame.");
+            egui::ScrollArea::vertical()
+                .max_height(240.0)
+                .show(ui, |ui| {
+                    ui.label(egui::RichText::new(error).monospace());
+                });
+        });
+}
+
+/// System to show the frame rate, accumulation progress, sample settings, sphere count and
+/// camera position in the upper right corner, with a slider for the max depth
+fn stats_overlay_system(
+    mut contexts: EguiContexts,
+    diagnostics: Res<DiagnosticsStore>,
+    mut camera_settings: ResMut<CameraSettings>,
+    spheres: Res<SphereCollection>,
+) {
+    // Headless renders have no window to draw into
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+    let fps = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.smoothed());
+    let mut max_depth = camera_settings.max_depth;
+    let mut samples_per_frame = camera_settings.samples_per_frame;
+
+    egui::Window::new("Render stats")
+        .anchor(egui::Align2::RIGHT_TOP, [-12.0, 12.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            match fps {
+                Some(fps) => ui.label(format!("FPS: {fps:.1}")),
+                None => ui.label("FPS: -"),
+            };
+            ui.label(format!(
+                "Accumulated samples: {}",
+                camera_settings.accumulated_frames
+            ));
+            ui.label(format!(
+                "Samples per pixel: {}",
+                camera_settings.samples_per_pixel
+            ));
+            ui.add(
+                egui::Slider::new(&mut samples_per_frame, SAMPLES_PER_FRAME_RANGE)
+                    .text("Samples per frame"),
+            );
+            ui.add(egui::Slider::new(&mut max_depth, MAX_DEPTH_RANGE).text("Max depth"));
+            ui.label(format!("Spheres: {}", spheres.count));
+            let position = camera_settings.look_from;
+            ui.label(format!(
+                "Camera: ({:.2}, {:.2}, {:.2})",
+                position.x, position.y, position.z
+            ));
+        });
+
+    if max_depth != camera_settings.max_depth {
+        camera_settings.max_depth = max_depth;
+        camera_settings.camera_has_moved = true;
+    }
+    // Only changes how fast the image converges, so the accumulation is kept
+    if samples_per_frame != camera_settings.samples_per_frame {
+        camera_settings.samples_per_frame = samples_per_frame;
+    }
+}
+
+/// System to list the spheres and edit the material of the selected one. Picking a sphere
+/// in the list selects it like Tab does, so it gets outlined on screen.
+fn material_editor_system(
+    mut contexts: EguiContexts,
+    mut spheres: ResMut<SphereCollection>,
+    mut selected: ResMut<SelectedSphere>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    let Some(ctx) = contexts.try_ctx_mut() else {
+        return;
+    };
+
+    let count = spheres.count as usize;
+    let mut picked = None;
+    let mut edited = None;
+    egui::Window::new("Materials")
+        .anchor(egui::Align2::LEFT_TOP, [12.0, 12.0])
+        .resizable(false)
+        .show(ctx, |ui| {
+            let row_height = ui.text_style_height(&egui::TextStyle::Body);
+            egui::ScrollArea::vertical().max_height(240.0).show_rows(
+                ui,
+                row_height,
+                count,
+                |ui, rows| {
+                    for index in rows {
+                        let kind = spheres.material(index).kind();
+                        let label = format!("Sphere {index}: {kind:?}");
+                        if ui
+                            .selectable_label(selected.index == Some(index), label)
+                            .clicked()
+                        {
+                            picked = Some(index);
+                        }
+                    }
+                },
+            );
+
+            let Some(index) = selected.index.filter(|&index| index < count) else {
+                ui.label("Select a sphere to edit its material");
+                return;
+            };
+            ui.separator();
+            let mut material = spheres.material(index).to_material();
+            if material_controls(ui, &mut material) {
+                edited = Some((index, material));
+            }
+        });
+
+    if let Some(index) = picked {
+        info!("Selected sphere {index} in the material editor");
+        selected.index = Some(index);
+    }
+    if let Some((index, material)) = edited {
+        spheres.set_material(index, material.to_gpu());
+        camera_settings.camera_has_moved = true;
+    }
+}
+
+// Dropdown for the material kind and the controls for its parameters. Returns true if the
+// user changed anything; comparing materials would also catch rounding in the round trip
+// through GpuMaterial.
+fn material_controls(ui: &mut egui::Ui, material: &mut SurfaceMaterial) -> bool {
+    let mut kind = material.to_gpu().kind();
+    let mut changed = false;
+    egui::ComboBox::from_label("Type")
+        .selected_text(format!("{kind:?}"))
+        .show_ui(ui, |ui| {
+            for option in MATERIAL_KINDS {
+                changed |= ui
+                    .selectable_value(&mut kind, option, format!("{option:?}"))
+                    .changed();
+            }
+        });
+    *material = with_kind(*material, kind);
+
+    match material {
+        SurfaceMaterial::Lambertian(albedo) => changed |= color_control(ui, "Albedo", albedo),
+        SurfaceMaterial::Metal { albedo, fuzz } => {
+            changed |= color_control(ui, "Albedo", albedo);
+            changed |= ui
+                .add(egui::Slider::new(fuzz, 0.0..=1.0).text("Fuzz"))
+                .changed();
+        }
+        SurfaceMaterial::Dielectric(refractive_index) => {
+            changed |= ui
+                .add(egui::Slider::new(refractive_index, 1.0..=2.5).text("IOR"))
+                .changed();
+        }
+        SurfaceMaterial::AbsorbingDielectric {
+            refractive_index,
+            absorption,
+        } => {
+            changed |= ui
+                .add(egui::Slider::new(refractive_index, 1.0..=2.5).text("IOR"))
+                .changed();
+            ui.horizontal(|ui| {
+                for value in [&mut absorption.x, &mut absorption.y, &mut absorption.z] {
+                    changed |= ui
+                        .add(egui::DragValue::new(value).speed(0.01).range(0.0..=20.0))
+                        .changed();
+                }
+                ui.label("Absorption");
+            });
+        }
+        SurfaceMaterial::Emissive(radiance) => {
+            // Radiance goes above 1, so edit it as a color and an intensity
+            let mut intensity = radiance.max_element();
+            let mut color = if intensity > 0.0 {
+                *radiance / intensity
+            } else {
+                Vec3::ONE
+            };
+            changed |= color_control(ui, "Color", &mut color);
+            changed |= ui
+                .add(egui::Slider::new(&mut intensity, 0.0..=50.0).text("Intensity"))
+                .changed();
+            *radiance = color * intensity;
+        }
+        SurfaceMaterial::Noise { base, scale } => {
+            changed |= color_control(ui, "Base", base);
+            changed |= ui
+                .add(egui::Slider::new(scale, 0.1..=20.0).text("Scale"))
+                .changed();
+        }
+    }
+    changed
+}
+
+// The material turned into one of `kind`, keeping its color where the new kind has one
+fn with_kind(material: SurfaceMaterial, kind: MaterialKind) -> SurfaceMaterial {
+    if material.to_gpu().kind() == kind {
+        return material;
+    }
+
+    let color = match material {
+        SurfaceMaterial::Lambertian(color)
+        | SurfaceMaterial::Metal { albedo: color, .. }
+        | SurfaceMaterial::Emissive(color)
+        | SurfaceMaterial::Noise { base: color, .. } => color.min(Vec3::ONE),
+        SurfaceMaterial::Dielectric(_) | SurfaceMaterial::AbsorbingDielectric { .. } => {
+            Vec3::splat(0.8)
+        }
+    };
+    match kind {
+        MaterialKind::Lambertian => SurfaceMaterial::Lambertian(color),
+        MaterialKind::Metal => SurfaceMaterial::Metal {
+            albedo: color,
+            fuzz: 0.0,
+        },
+        MaterialKind::Dielectric => SurfaceMaterial::Dielectric(1.5),
+        MaterialKind::Emissive => SurfaceMaterial::emissive(color, 4.0),
+        MaterialKind::Noise => SurfaceMaterial::Noise {
+            base: color,
+            scale: 4.0,
+        },
+    }
+}
+
+// Labeled color picker for a linear RGB color, returning true if it was changed
+fn color_control(ui: &mut egui::Ui, label: &str, color: &mut Vec3) -> bool {
+    let mut rgb = color.to_array();
+    let changed = ui
+        .horizontal(|ui| {
+            let changed = ui.color_edit_button_rgb(&mut rgb).changed();
+            ui.label(label);
+            changed
+        })
+        .inner;
+    *color = Vec3::from_array(rgb);
+    changed
+}
@@ -0,0 +1,312 @@
+use bevy::{
+    prelude::*,
+    render::{camera::Viewport, extract_resource::ExtractResource, view::RenderLayers},
+    window::PrimaryWindow,
+};
+
+use crate::{
+    camera::{CameraSettings, LensProjection, SceneCamera, FOV_MAX},
+    denoise::DenoiseSettings,
+    display::{DisplaySettings, DisplayedImage, GpuDisplay, PostProcessMaterial, TonemapSettings},
+    step::StepMode,
+    ComputeShaderImages, RenderSize, RenderTargetAssets,
+};
+
+// How much wider the view F1 adds sees than the main camera
+const WIDE_VIEW_FACTOR: f32 = 1.5;
+
+// A camera rendered next to the main view, into render targets of its own. The scene,
+// the sky and the integrator are shared with the main view, so two views of the same
+// scene with different cameras can be compared side by side.
+#[derive(Clone)]
+pub struct RenderView {
+    pub camera: CameraSettings,
+    // Take the main camera every frame instead, and only scale its field of view, or the
+    // height of an orthographic camera, by this factor; see RenderView::following
+    pub follow_main: Option<f32>,
+    // Created at RenderSize by prepare_views_system
+    targets: Option<ComputeShaderImages>,
+    // Uniform the compute shader reads for this view, set by update_view_cameras_system
+    scene_camera: Option<SceneCamera>,
+}
+
+impl RenderView {
+    pub fn new(camera: CameraSettings) -> Self {
+        Self {
+            camera,
+            follow_main: None,
+            targets: None,
+            scene_camera: None,
+        }
+    }
+
+    // A view that moves with the main camera, `camera`, and sees `fov_factor` times as wide
+    pub fn following(camera: &CameraSettings, fov_factor: f32) -> Self {
+        Self {
+            follow_main: Some(fov_factor),
+            ..Self::new(widened(camera, fov_factor))
+        }
+    }
+
+    // Render targets and camera of a view that is ready to be dispatched
+    pub(crate) fn gpu_view(&self) -> Option<(&ComputeShaderImages, &SceneCamera)> {
+        Some((self.targets.as_ref()?, self.scene_camera.as_ref()?))
+    }
+}
+
+// Views rendered in addition to the main one, each in its own slice of the window to the
+// right of it. Every view renders at RenderSize and the compute pass runs once per view,
+// so each one costs as much as the main view. Empty by default, which renders only the
+// main view.
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct RenderViews(pub Vec<RenderView>);
+
+impl RenderViews {
+    // Slices the window is split into, one for the main view and one for each other view
+    pub fn slices(&self) -> u32 {
+        self.0.len() as u32 + 1
+    }
+}
+
+// Index into RenderViews of the view a sprite shows, or that a 2D camera draws into its
+// slice of the window
+#[derive(Component)]
+pub struct ViewIndex(usize);
+
+// `camera` with its field of view, or the height of an orthographic camera, scaled by
+// `factor`
+fn widened(camera: &CameraSettings, factor: f32) -> CameraSettings {
+    let projection = match camera.projection {
+        LensProjection::Perspective { fov } => LensProjection::Perspective {
+            fov: (fov * factor).min(FOV_MAX),
+        },
+        LensProjection::Orthographic { height } => LensProjection::Orthographic {
+            height: height * factor,
+        },
+    };
+    CameraSettings {
+        projection,
+        target_fov: None,
+        ..camera.clone()
+    }
+}
+
+/// System to toggle a split screen with F1: a second view that follows the main camera,
+/// but wider
+pub fn toggle_split_screen_system(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    camera_settings: Res<CameraSettings>,
+    mut views: ResMut<RenderViews>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F1) {
+        return;
+    }
+
+    if !views.0.is_empty() {
+        views.0.clear();
+        info!("Split screen: off");
+        return;
+    }
+    views
+        .0
+        .push(RenderView::following(&camera_settings, WIDE_VIEW_FACTOR));
+    info!("Split screen: on, the right view is {WIDE_VIEW_FACTOR}x wider");
+}
+
+/// System to create the render targets of new views, or of every view when the render
+/// size changes, and the sprites and 2D cameras that show them
+pub fn prepare_views_system(
+    mut commands: Commands,
+    mut views: ResMut<RenderViews>,
+    render_size: Res<RenderSize>,
+    mut assets: RenderTargetAssets,
+    view_entities: Query<Entity, With<ViewIndex>>,
+    main_camera: Query<(), (With<Camera2d>, Without<ViewIndex>)>,
+    mut built: Local<(usize, UVec2)>,
+) {
+    let layout = (views.0.len(), **render_size);
+    let missing_targets = views.0.iter().any(|view| view.targets.is_none());
+    if *built == layout && !missing_targets {
+        return;
+    }
+    *built = layout;
+
+    for view in &mut views.0 {
+        view.targets = Some(crate::create_render_targets(
+            &mut assets.images,
+            &mut assets.buffers,
+            **render_size,
+        ));
+        view.camera.camera_has_moved = true;
+    }
+
+    for entity in &view_entities {
+        commands.entity(entity).despawn();
+    }
+    // Apps that draw the image themselves have no 2D camera to put the views next to
+    if main_camera.is_empty() {
+        return;
+    }
+    let mesh = assets
+        .meshes
+        .add(Rectangle::from_size(render_size.as_vec2()));
+    for (index, view) in views.0.iter().enumerate() {
+        let Some(targets) = &view.targets else {
+            continue;
+        };
+        let layer = RenderLayers::layer(index + 1);
+        commands.spawn((
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(assets.materials.add(PostProcessMaterial {
+                display: GpuDisplay::new(
+                    &DisplaySettings::default(),
+                    &TonemapSettings::default(),
+                    &DenoiseSettings::default(),
+                ),
+                source: targets.texture_a.clone(),
+                gbuffer: targets.gbuffer_a.clone(),
+                albedo: targets.albedo.clone(),
+            })),
+            Transform::from_scale(Vec3::splat(crate::DISPLAY_FACTOR as f32)),
+            layer.clone(),
+            ViewIndex(index),
+        ));
+        commands.spawn((
+            Camera2d,
+            Camera {
+                order: index as isize + 1,
+                ..default()
+            },
+            layer,
+            ViewIndex(index),
+        ));
+    }
+}
+
+/// System to give each view a slice of the window, from the main view on the left to the
+/// last one on the right, zoomed out to show the whole image
+pub fn layout_views_system(
+    window: Single<&Window, With<PrimaryWindow>>,
+    views: Res<RenderViews>,
+    mut cameras: Query<
+        (&mut Camera, &mut OrthographicProjection, Option<&ViewIndex>),
+        With<Camera2d>,
+    >,
+) {
+    let slices = views.slices();
+    let slice = window.physical_size() / UVec2::new(slices, 1);
+    for (mut camera, mut projection, view) in &mut cameras {
+        let index = view.map_or(0, |view| view.0 as u32 + 1);
+        // Minimized windows have no room for a viewport
+        let viewport = (slices > 1 && slice.min_element() > 0).then(|| Viewport {
+            physical_position: UVec2::new(index * slice.x, 0),
+            physical_size: slice,
+            ..default()
+        });
+        let current = camera
+            .viewport
+            .as_ref()
+            .map(|viewport| (viewport.physical_position, viewport.physical_size));
+        let wanted = viewport
+            .as_ref()
+            .map(|viewport| (viewport.physical_position, viewport.physical_size));
+        // Avoid triggering change detection every frame
+        if current != wanted {
+            camera.viewport = viewport;
+        }
+        if projection.scale != slices as f32 {
+            projection.scale = slices as f32;
+        }
+    }
+}
+
+/// System to restart the views along with the main one and turn their cameras into the
+/// uniforms the compute shader reads
+pub fn update_view_cameras_system(
+    camera_settings: Res<CameraSettings>,
+    step_mode: Res<StepMode>,
+    render_size: Res<RenderSize>,
+    mut views: ResMut<RenderViews>,
+) {
+    // Avoid triggering change detection, and with it the extraction, with a single view
+    if views.0.is_empty() {
+        return;
+    }
+
+    for view in &mut views.0 {
+        if let Some(factor) = view.follow_main {
+            // Only the view's own accumulation is kept
+            view.camera = CameraSettings {
+                accumulated_frames: view.camera.accumulated_frames,
+                camera_has_moved: view.camera.camera_has_moved,
+                ..widened(&camera_settings, factor)
+            };
+        }
+        // Scene and sky edits only flag the main camera, so restart whenever it does
+        if camera_settings.camera_has_moved {
+            view.camera.camera_has_moved = true;
+        }
+        // Only the main view feeds the pixel probe and temporal reprojection, which
+        // SceneCamera::new leaves off
+        let mut scene_camera = SceneCamera::new(&view.camera, **render_size);
+        scene_camera.samples_per_frame = view.camera.samples_this_frame(&step_mode) as f32;
+        view.scene_camera = Some(scene_camera);
+
+        // Same accounting as extract_camera; views always render the whole image
+        if step_mode.should_dispatch() {
+            let camera = &mut view.camera;
+            camera.accumulated_frames = if camera.camera_has_moved {
+                0
+            } else {
+                camera
+                    .accumulated_frames
+                    .saturating_add(camera.samples_this_frame(&step_mode))
+            };
+            camera.camera_has_moved = false;
+        }
+    }
+}
+
+/// System to show each view's latest texture, through the same display settings as the
+/// main view
+pub fn update_view_sprites_system(
+    (views, step_mode): (Res<RenderViews>, Res<StepMode>),
+    (settings, tonemap, denoise): (
+        Res<DisplaySettings>,
+        Res<TonemapSettings>,
+        Res<DenoiseSettings>,
+    ),
+    (main_targets, displayed): (Res<ComputeShaderImages>, Res<DisplayedImage>),
+    sprites: Query<(&ViewIndex, &MeshMaterial2d<PostProcessMaterial>)>,
+    mut materials: ResMut<Assets<PostProcessMaterial>>,
+) {
+    if !displayed.is_changed()
+        && !settings.is_changed()
+        && !tonemap.is_changed()
+        && !denoise.is_changed()
+    {
+        return;
+    }
+
+    // Every view ping-pongs in step with the main one
+    let showing_a = displayed.0 == main_targets.texture_a;
+    for (sprite, material) in &sprites {
+        let (Some(view), Some(material)) = (views.0.get(sprite.0), materials.get_mut(material))
+        else {
+            continue;
+        };
+        let Some(targets) = &view.targets else {
+            continue;
+        };
+        material.display = GpuDisplay::new(&settings, &tonemap, &denoise);
+        material.display.label_samples = view.camera.samples_shown(&step_mode) as f32;
+        if showing_a {
+            material.source = targets.texture_a.clone_weak();
+            material.gbuffer = targets.gbuffer_a.clone_weak();
+        } else {
+            material.source = targets.texture_b.clone_weak();
+            material.gbuffer = targets.gbuffer_b.clone_weak();
+        }
+        material.albedo = targets.albedo.clone_weak();
+    }
+}